@@ -0,0 +1,24 @@
+-- Full text index over commands, for the "fts" search mode. This is an
+-- external content table, so the command text itself is only stored once, in
+-- history. The triggers below keep the index in step with it.
+create virtual table if not exists history_fts using fts5(
+	command,
+	content='history',
+	content_rowid='rowid'
+);
+
+create trigger if not exists history_fts_insert after insert on history begin
+	insert into history_fts(rowid, command) values (new.rowid, new.command);
+end;
+
+create trigger if not exists history_fts_delete after delete on history begin
+	insert into history_fts(history_fts, rowid, command) values ('delete', old.rowid, old.command);
+end;
+
+create trigger if not exists history_fts_update after update of command on history begin
+	insert into history_fts(history_fts, rowid, command) values ('delete', old.rowid, old.command);
+	insert into history_fts(rowid, command) values (new.rowid, new.command);
+end;
+
+-- Index everything that was already there
+insert into history_fts(history_fts) values ('rebuild');
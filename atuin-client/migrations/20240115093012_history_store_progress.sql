@@ -0,0 +1,6 @@
+-- The index of the last history record, per host, that has been replayed from
+-- the record store into this database
+create table if not exists history_store_progress (
+	host text primary key,
+	idx integer not null
+);
@@ -0,0 +1,7 @@
+-- git metadata and a user-provided tag, captured alongside each command
+alter table history add column git_branch text;
+alter table history add column git_commit text;
+alter table history add column tag text;
+
+create index if not exists idx_history_git_branch on history(git_branch);
+create index if not exists idx_history_tag on history(tag);
@@ -0,0 +1,5 @@
+-- Deletes replayed from the record store before the history they refer to. They are
+-- applied once that history arrives
+create table if not exists history_store_pending_deletes (
+	id text primary key
+);
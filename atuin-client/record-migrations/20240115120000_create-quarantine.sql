@@ -0,0 +1,15 @@
+-- Records that failed verification are moved here, out of the way of sync, rather than deleted
+create table if not exists quarantine (
+  id text primary key,
+  idx integer,
+  host text not null,
+  tag text not null,
+
+  timestamp integer not null,
+  version text not null,
+  data blob not null,
+  cek blob not null,
+
+  reason text not null,
+  quarantined_at integer not null
+);
@@ -4,16 +4,20 @@ use std::time::Duration;
 
 use eyre::{bail, Result};
 use reqwest::{
-    header::{HeaderMap, AUTHORIZATION, USER_AGENT},
+    header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, USER_AGENT},
     Response, StatusCode, Url,
 };
 
 use atuin_common::{
     api::{
-        AddHistoryRequest, CountResponse, DeleteHistoryRequest, ErrorResponse, IndexResponse,
-        LoginRequest, LoginResponse, RegisterResponse, StatusResponse, SyncHistoryResponse,
+        AddHistoryRequest, ChangeEmailRequest, ChangePasswordRequest, ChangePasswordResponse,
+        CountResponse, DeleteHistoryRequest, ErrorResponse, IndexResponse, ListSessionsResponse,
+        LoginRequest, LoginResponse, RecordEvent, RecordRange, RegisterResponse,
+        ReplaceRecordsResponse, SessionResponse, StatusResponse, StreamDownloadRequest,
+        StreamUploadResponse, SyncHistoryResponse, UsageResponse,
     },
     record::RecordStatus,
+    stream::{self, Decoder},
 };
 use atuin_common::{
     api::{ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION, ATUIN_VERSION},
@@ -27,6 +31,103 @@ use crate::{history::History, sync::hash_str};
 
 static APP_USER_AGENT: &str = concat!("atuin/", env!("CARGO_PKG_VERSION"),);
 
+/// The server refused an upload, as it would take the user over their storage quota. Holds the
+/// server's explanation
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct QuotaExceeded(pub String);
+
+/// Records arriving from the server, a batch at a time
+pub struct RecordStream {
+    resp: Response,
+    decoder: Decoder,
+}
+
+impl RecordStream {
+    pub async fn next_batch(&mut self) -> Result<Option<Vec<Record<EncryptedData>>>> {
+        loop {
+            if let Some(batch) = self.decoder.next_batch()? {
+                return Ok(Some(batch));
+            }
+
+            match self.resp.chunk().await? {
+                Some(chunk) => self.decoder.push(&chunk),
+                None => {
+                    self.decoder.finish()?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// How long to hold an event stream open before reconnecting
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// The server sends a keep-alive every 15 seconds, so if nothing arrives for this long, the
+/// connection is dead
+const EVENTS_IDLE: Duration = Duration::from_secs(60);
+
+/// Notifications from the server that new records have been uploaded
+pub struct RecordEvents {
+    resp: Response,
+    buf: String,
+    /// When we last heard from the server. Kept here, rather than timing each wait, so the
+    /// connection is still noticed dying if `next_event` is cancelled and called again
+    last_seen: tokio::time::Instant,
+}
+
+impl RecordEvents {
+    /// Wait for the next event. None if the server closed the stream
+    pub async fn next_event(&mut self) -> Result<Option<RecordEvent>> {
+        loop {
+            // events are separated by a blank line
+            while let Some(end) = self.buf.find("\n\n") {
+                let block: String = self.buf.drain(..end + 2).collect();
+
+                if let Some(event) = parse_event(&block)? {
+                    return Ok(Some(event));
+                }
+            }
+
+            let deadline = self.last_seen + EVENTS_IDLE;
+
+            let Ok(chunk) = tokio::time::timeout_at(deadline, self.resp.chunk()).await else {
+                bail!("no events or keep-alives from the server for {EVENTS_IDLE:?}");
+            };
+
+            self.last_seen = tokio::time::Instant::now();
+
+            match chunk? {
+                Some(chunk) => self
+                    .buf
+                    .push_str(&String::from_utf8_lossy(&chunk).replace('\r', "")),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Parse one server-sent event. Keep-alives, and events we don't know, are None
+fn parse_event(block: &str) -> Result<Option<RecordEvent>> {
+    let mut event = "message";
+    let mut data = String::new();
+
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            event = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push_str(value.trim());
+        }
+    }
+
+    if event != "records" || data.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&data)?))
+}
+
 pub struct Client<'a> {
     sync_addr: &'a str,
     client: reqwest::Client,
@@ -38,10 +139,13 @@ pub async fn register(
     email: &str,
     password: &str,
 ) -> Result<RegisterResponse> {
+    let device = whoami::hostname();
+
     let mut map = HashMap::new();
     map.insert("username", username);
     map.insert("email", email);
     map.insert("password", password);
+    map.insert("device", &device);
 
     let url = format!("{address}/user/{username}");
     let resp = reqwest::get(url).await?;
@@ -248,7 +352,12 @@ impl<'a> Client<'a> {
         let url = format!("{}/history", self.sync_addr);
         let url = Url::parse(url.as_str())?;
 
-        self.client.post(url).json(history).send().await?;
+        let resp = self.client.post(url).json(history).send().await?;
+
+        if resp.status() == StatusCode::INSUFFICIENT_STORAGE {
+            let error = resp.json::<ErrorResponse>().await?;
+            return Err(QuotaExceeded(error.reason.into_owned()).into());
+        }
 
         Ok(())
     }
@@ -275,6 +384,11 @@ impl<'a> Client<'a> {
         let resp = self.client.post(url).json(records).send().await?;
         info!("posted records, got {}", resp.status());
 
+        if resp.status() == StatusCode::INSUFFICIENT_STORAGE {
+            let error = resp.json::<ErrorResponse>().await?;
+            return Err(QuotaExceeded(error.reason.into_owned()).into());
+        }
+
         if !resp.status().is_success() {
             error!(
                 "failed to post records to server; got: {:?}",
@@ -285,6 +399,129 @@ impl<'a> Client<'a> {
         Ok(())
     }
 
+    /// Upload records made into a stream with `stream::encode_batches`. Returns how many the
+    /// server received, or None if it is too old to accept a stream
+    pub async fn stream_upload(&self, body: Vec<u8>) -> Result<Option<u64>> {
+        let url = format!("{}/api/v0/record/stream/upload", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, stream::CONTENT_TYPE)
+            .body(body)
+            .send()
+            .await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not upload records due to version mismatch");
+        }
+
+        match resp.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            StatusCode::INSUFFICIENT_STORAGE => {
+                let error = resp.json::<ErrorResponse>().await?;
+                return Err(QuotaExceeded(error.reason.into_owned()).into());
+            }
+            status if !status.is_success() => {
+                let error = resp.json::<ErrorResponse>().await?;
+                bail!("failed to upload records: {}", error.reason);
+            }
+            _ => {}
+        }
+
+        Ok(Some(resp.json::<StreamUploadResponse>().await?.uploaded))
+    }
+
+    /// Download the records in `ranges`, or None if the server is too old to send a stream
+    pub async fn stream_download(&self, ranges: &[RecordRange]) -> Result<Option<RecordStream>> {
+        let url = format!("{}/api/v0/record/stream/download", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self
+            .client
+            .post(url)
+            .json(&StreamDownloadRequest {
+                ranges: ranges.to_vec(),
+            })
+            .send()
+            .await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not download records due to version mismatch");
+        }
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to download records: {}", error.reason);
+        }
+
+        Ok(Some(RecordStream {
+            resp,
+            decoder: Decoder::default(),
+        }))
+    }
+
+    /// Listen for records uploaded to this account, or None if the server can't send events
+    pub async fn record_events(&self) -> Result<Option<RecordEvents>> {
+        let url = format!("{}/api/v0/record/events", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self.client.get(url).timeout(EVENTS_TIMEOUT).send().await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not listen for records due to version mismatch");
+        }
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to listen for records: {}", error.reason);
+        }
+
+        Ok(Some(RecordEvents {
+            resp,
+            buf: String::new(),
+            last_seen: tokio::time::Instant::now(),
+        }))
+    }
+
+    /// Replace records the server already has with new versions of them. Unlike `post_records`,
+    /// this fails if the server doesn't accept them
+    pub async fn replace_records(&self, records: &[Record<EncryptedData>]) -> Result<u64> {
+        let url = format!("{}/api/v0/record", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self.client.put(url).json(records).send().await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not replace records due to version mismatch");
+        }
+
+        if resp.status() == StatusCode::INSUFFICIENT_STORAGE {
+            let error = resp.json::<ErrorResponse>().await?;
+            return Err(QuotaExceeded(error.reason.into_owned()).into());
+        }
+
+        let status = resp.status();
+        if status.is_success() {
+            let resp = resp.json::<ReplaceRecordsResponse>().await?;
+            Ok(resp.replaced)
+        } else if status.is_client_error() || status.is_server_error() {
+            let error = resp.json::<ErrorResponse>().await?.reason;
+            bail!("failed to replace records: {error}")
+        } else {
+            bail!("failed to replace records: status {status:?}")
+        }
+    }
+
     pub async fn next_records(
         &self,
         host: HostId,
@@ -323,6 +560,11 @@ impl<'a> Client<'a> {
             bail!("could not sync records due to version mismatch");
         }
 
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?.reason;
+            bail!("could not get the record status: {error}");
+        }
+
         let index = resp.json().await?;
 
         debug!("got remote index {:?}", index);
@@ -330,6 +572,116 @@ impl<'a> Client<'a> {
         Ok(index)
     }
 
+    /// Change the account's password. This ends every session, including this one, so the new
+    /// session returned must be used from now on
+    pub async fn change_password(
+        &self,
+        current_password: String,
+        new_password: String,
+    ) -> Result<ChangePasswordResponse> {
+        let url = format!("{}/account/password", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self
+            .client
+            .patch(url)
+            .json(&ChangePasswordRequest {
+                current_password,
+                new_password,
+            })
+            .send()
+            .await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not change password due to version mismatch");
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to change password: {}", error.reason);
+        }
+
+        Ok(resp.json::<ChangePasswordResponse>().await?)
+    }
+
+    pub async fn change_email(&self, password: String, email: String) -> Result<()> {
+        let url = format!("{}/account/email", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self
+            .client
+            .patch(url)
+            .json(&ChangeEmailRequest { password, email })
+            .send()
+            .await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not change email due to version mismatch");
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to change email: {}", error.reason);
+        }
+
+        Ok(())
+    }
+
+    /// How much we are storing on the server, and how much we can
+    pub async fn usage(&self) -> Result<UsageResponse> {
+        let url = format!("{}/api/v0/me/usage", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self.client.get(url).send().await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not get usage due to version mismatch");
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to get usage: {}", error.reason);
+        }
+
+        Ok(resp.json::<UsageResponse>().await?)
+    }
+
+    pub async fn sessions(&self) -> Result<Vec<SessionResponse>> {
+        let url = format!("{}/account/sessions", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self.client.get(url).send().await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not list sessions due to version mismatch");
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to list sessions: {}", error.reason);
+        }
+
+        Ok(resp.json::<ListSessionsResponse>().await?.sessions)
+    }
+
+    pub async fn revoke_session(&self, id: i64) -> Result<()> {
+        let url = format!("{}/account/sessions/{id}", self.sync_addr);
+        let url = Url::parse(url.as_str())?;
+
+        let resp = self.client.delete(url).send().await?;
+
+        if !ensure_version(&resp)? {
+            bail!("could not revoke session due to version mismatch");
+        }
+
+        if !resp.status().is_success() {
+            let error = resp.json::<ErrorResponse>().await?;
+            bail!("failed to revoke session: {}", error.reason);
+        }
+
+        Ok(())
+    }
+
     pub async fn delete(&self) -> Result<()> {
         let url = format!("{}/account", self.sync_addr);
         let url = Url::parse(url.as_str())?;
@@ -5,12 +5,16 @@ use std::{
 };
 
 use async_trait::async_trait;
-use atuin_common::utils;
+use atuin_common::{
+    record::{HostId, RecordIdx},
+    utils,
+};
 use fs_err as fs;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use rand::{distributions::Alphanumeric, Rng};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use sql_builder::{esc, quote, SqlBuilder, SqlName};
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow},
@@ -19,11 +23,12 @@ use sqlx::{
 use time::OffsetDateTime;
 
 use super::{
-    history::History,
+    history::{History, HistoryId, HistoryStats},
     ordering,
     settings::{FilterMode, SearchMode, Settings},
 };
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Context {
     pub session: String,
     pub cwd: String,
@@ -38,6 +43,11 @@ pub struct OptFilters {
     pub exclude_exit: Option<i64>,
     pub cwd: Option<String>,
     pub exclude_cwd: Option<String>,
+    pub hostname: Option<String>,
+    pub session: Option<String>,
+    pub git_branch: Option<String>,
+    pub git_commit: Option<String>,
+    pub tag: Option<String>,
     pub before: Option<String>,
     pub after: Option<String>,
     pub limit: Option<i64>,
@@ -106,9 +116,36 @@ pub trait Database: Send + Sync + 'static {
         filter_options: OptFilters,
     ) -> Result<Vec<History>>;
 
+    /// Every history entry matching the filters, oldest first. Unlike `search`, commands are
+    /// not deduplicated.
+    async fn list_filtered(
+        &self,
+        filter: FilterMode,
+        context: &Context,
+        filter_options: OptFilters,
+    ) -> Result<Vec<History>>;
+
+    /// The session on the same host whose commands ran immediately before (or after, if `before`
+    /// is false) the commands of `session`
+    async fn adjacent_session(&self, session: &str, before: bool) -> Result<Option<String>>;
+
     async fn query_history(&self, query: &str) -> Result<Vec<History>>;
 
     async fn all_with_count(&self) -> Result<Vec<(History, i32)>>;
+
+    /// How often the command in `h` has been run and how it went, along with its neighbours in
+    /// the session
+    async fn stats(&self, h: &History) -> Result<HistoryStats>;
+
+    /// The index of the last record from this host's history store that has been applied here
+    async fn history_store_idx(&self, host: HostId) -> Result<Option<RecordIdx>>;
+    async fn set_history_store_idx(&self, host: HostId, idx: RecordIdx) -> Result<()>;
+    /// Forget how far every host's history store has been applied, along with any pending deletes
+    async fn clear_history_store_idx(&self) -> Result<()>;
+
+    /// Deletes from the history store whose history hasn't been applied yet
+    async fn pending_history_deletes(&self) -> Result<Vec<HistoryId>>;
+    async fn set_history_delete_pending(&self, id: &HistoryId, pending: bool) -> Result<()>;
 }
 
 // Intended for use on a developer machine and not a sync server.
@@ -150,8 +187,8 @@ impl Sqlite {
 
     async fn save_raw(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, h: &History) -> Result<()> {
         sqlx::query(
-            "insert or ignore into history(id, timestamp, duration, exit, command, cwd, session, hostname, deleted_at)
-                values(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            "insert or ignore into history(id, timestamp, duration, exit, command, cwd, session, hostname, deleted_at, git_branch, git_commit, tag)
+                values(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         )
         .bind(h.id.0.as_str())
         .bind(h.timestamp.unix_timestamp_nanos() as i64)
@@ -162,6 +199,9 @@ impl Sqlite {
         .bind(h.session.as_str())
         .bind(h.hostname.as_str())
         .bind(h.deleted_at.map(|t|t.unix_timestamp_nanos() as i64))
+        .bind(h.git_branch.as_deref())
+        .bind(h.git_commit.as_deref())
+        .bind(h.tag.as_deref())
         .execute(&mut **tx)
         .await?;
 
@@ -183,12 +223,89 @@ impl Sqlite {
             .cwd(row.get("cwd"))
             .session(row.get("session"))
             .hostname(row.get("hostname"))
+            .git_branch(row.get("git_branch"))
+            .git_commit(row.get("git_commit"))
+            .tag(row.get("tag"))
             .deleted_at(
                 deleted_at.and_then(|t| OffsetDateTime::from_unix_timestamp_nanos(t as i128).ok()),
             )
             .build()
             .into()
     }
+
+    fn filter_mode_condition(sql: &mut SqlBuilder, filter: FilterMode, context: &Context) {
+        let git_root = if let Some(git_root) = context.git_root.clone() {
+            git_root.to_str().unwrap_or("/").to_string()
+        } else {
+            context.cwd.clone()
+        };
+
+        match filter {
+            FilterMode::Global => sql,
+            FilterMode::Host => sql.and_where_eq("hostname", quote(&context.hostname)),
+            FilterMode::Session => sql.and_where_eq("session", quote(&context.session)),
+            FilterMode::Directory => sql.and_where_eq("cwd", quote(&context.cwd)),
+            FilterMode::Workspace => sql.and_where_like_left("cwd", git_root),
+        };
+    }
+
+    fn opt_filter_conditions(sql: &mut SqlBuilder, filter_options: OptFilters) {
+        filter_options
+            .exit
+            .map(|exit| sql.and_where_eq("exit", exit));
+
+        filter_options
+            .exclude_exit
+            .map(|exclude_exit| sql.and_where_ne("exit", exclude_exit));
+
+        filter_options
+            .cwd
+            .map(|cwd| sql.and_where_eq("cwd", quote(cwd)));
+
+        filter_options
+            .exclude_cwd
+            .map(|exclude_cwd| sql.and_where_ne("cwd", quote(exclude_cwd)));
+
+        filter_options
+            .hostname
+            .map(|hostname| sql.and_where_eq("hostname", quote(hostname)));
+
+        filter_options
+            .session
+            .map(|session| sql.and_where_eq("session", quote(session)));
+
+        filter_options
+            .git_branch
+            .map(|git_branch| sql.and_where_eq("git_branch", quote(git_branch)));
+
+        filter_options
+            .git_commit
+            .map(|git_commit| sql.and_where_like_left("git_commit", git_commit));
+
+        filter_options
+            .tag
+            .map(|tag| sql.and_where_eq("tag", quote(tag)));
+
+        filter_options.before.map(|before| {
+            interim::parse_date_string(
+                before.as_str(),
+                OffsetDateTime::now_utc(),
+                interim::Dialect::Uk,
+            )
+            .map(|before| {
+                sql.and_where_lt("timestamp", quote(before.unix_timestamp_nanos() as i64))
+            })
+        });
+
+        filter_options.after.map(|after| {
+            interim::parse_date_string(
+                after.as_str(),
+                OffsetDateTime::now_utc(),
+                interim::Dialect::Uk,
+            )
+            .map(|after| sql.and_where_gt("timestamp", quote(after.unix_timestamp_nanos() as i64)))
+        });
+    }
 }
 
 #[async_trait]
@@ -233,7 +350,8 @@ impl Database for Sqlite {
 
         sqlx::query(
             "update history
-                set timestamp = ?2, duration = ?3, exit = ?4, command = ?5, cwd = ?6, session = ?7, hostname = ?8, deleted_at = ?9
+                set timestamp = ?2, duration = ?3, exit = ?4, command = ?5, cwd = ?6, session = ?7, hostname = ?8, deleted_at = ?9,
+                    git_branch = ?10, git_commit = ?11, tag = ?12
                 where id = ?1",
         )
         .bind(h.id.0.as_str())
@@ -245,6 +363,9 @@ impl Database for Sqlite {
         .bind(h.session.as_str())
         .bind(h.hostname.as_str())
         .bind(h.deleted_at.map(|t|t.unix_timestamp_nanos() as i64))
+        .bind(h.git_branch.as_deref())
+        .bind(h.git_commit.as_deref())
+        .bind(h.tag.as_deref())
         .execute(&self.pool)
         .await?;
 
@@ -382,25 +503,7 @@ impl Database for Sqlite {
             sql.offset(offset);
         }
 
-        if filter_options.reverse {
-            sql.order_asc("timestamp");
-        } else {
-            sql.order_desc("timestamp");
-        }
-
-        let git_root = if let Some(git_root) = context.git_root.clone() {
-            git_root.to_str().unwrap_or("/").to_string()
-        } else {
-            context.cwd.clone()
-        };
-
-        match filter {
-            FilterMode::Global => &mut sql,
-            FilterMode::Host => sql.and_where_eq("hostname", quote(&context.hostname)),
-            FilterMode::Session => sql.and_where_eq("session", quote(&context.session)),
-            FilterMode::Directory => sql.and_where_eq("cwd", quote(&context.cwd)),
-            FilterMode::Workspace => sql.and_where_like_left("cwd", git_root),
-        };
+        Self::filter_mode_condition(&mut sql, filter, context);
 
         let orig_query = query;
         let query = query.replace('*', "%"); // allow wildcard char
@@ -408,6 +511,18 @@ impl Database for Sqlite {
         match search_mode {
             SearchMode::Prefix => sql.and_where_like_left("command", query),
             SearchMode::FullText => sql.and_where_like_any("command", query),
+            SearchMode::Fts => match fts_query(orig_query) {
+                // bm25 scores better matches lower, so rank ascending is best first
+                Some(fts) => sql
+                    .field("history.*")
+                    .join(format!(
+                        "(select rowid, rank from history_fts where history_fts match {}) fts",
+                        quote(fts)
+                    ))
+                    .on("fts.rowid = history.rowid")
+                    .order_by("fts.rank", filter_options.reverse),
+                None => &mut sql,
+            },
             _ => {
                 // don't recompile the regex on successive calls!
                 lazy_static! {
@@ -454,52 +569,84 @@ impl Database for Sqlite {
             }
         };
 
-        filter_options
-            .exit
-            .map(|exit| sql.and_where_eq("exit", exit));
+        if filter_options.reverse {
+            sql.order_asc("timestamp");
+        } else {
+            sql.order_desc("timestamp");
+        }
 
-        filter_options
-            .exclude_exit
-            .map(|exclude_exit| sql.and_where_ne("exit", exclude_exit));
+        Self::opt_filter_conditions(&mut sql, filter_options);
 
-        filter_options
-            .cwd
-            .map(|cwd| sql.and_where_eq("cwd", quote(cwd)));
+        sql.and_where_is_null("deleted_at");
 
-        filter_options
-            .exclude_cwd
-            .map(|exclude_cwd| sql.and_where_ne("cwd", quote(exclude_cwd)));
+        let query = sql.sql().expect("bug in search query. please report");
 
-        filter_options.before.map(|before| {
-            interim::parse_date_string(
-                before.as_str(),
-                OffsetDateTime::now_utc(),
-                interim::Dialect::Uk,
-            )
-            .map(|before| {
-                sql.and_where_lt("timestamp", quote(before.unix_timestamp_nanos() as i64))
-            })
-        });
+        let res = sqlx::query(&query)
+            .map(Self::query_history)
+            .fetch_all(&self.pool)
+            .await?;
 
-        filter_options.after.map(|after| {
-            interim::parse_date_string(
-                after.as_str(),
-                OffsetDateTime::now_utc(),
-                interim::Dialect::Uk,
-            )
-            .map(|after| sql.and_where_gt("timestamp", quote(after.unix_timestamp_nanos() as i64)))
-        });
+        Ok(ordering::reorder_fuzzy(search_mode, orig_query, res))
+    }
+
+    async fn list_filtered(
+        &self,
+        filter: FilterMode,
+        context: &Context,
+        filter_options: OptFilters,
+    ) -> Result<Vec<History>> {
+        let mut sql = SqlBuilder::select_from("history");
+
+        if let Some(limit) = filter_options.limit {
+            sql.limit(limit);
+        }
+
+        if let Some(offset) = filter_options.offset {
+            sql.offset(offset);
+        }
+
+        if filter_options.reverse {
+            sql.order_desc("timestamp");
+        } else {
+            sql.order_asc("timestamp");
+        }
+
+        Self::filter_mode_condition(&mut sql, filter, context);
+        Self::opt_filter_conditions(&mut sql, filter_options);
 
         sql.and_where_is_null("deleted_at");
 
-        let query = sql.sql().expect("bug in search query. please report");
+        let query = sql.sql().expect("bug in list query. please report");
 
         let res = sqlx::query(&query)
             .map(Self::query_history)
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(ordering::reorder_fuzzy(search_mode, orig_query, res))
+        Ok(res)
+    }
+
+    async fn adjacent_session(&self, session: &str, before: bool) -> Result<Option<String>> {
+        let query = if before {
+            "select session from history
+                where hostname = (select hostname from history where session = ?1 limit 1)
+                and session != ?1 and deleted_at is null
+                and timestamp < (select min(timestamp) from history where session = ?1)
+                order by timestamp desc limit 1"
+        } else {
+            "select session from history
+                where hostname = (select hostname from history where session = ?1 limit 1)
+                and session != ?1 and deleted_at is null
+                and timestamp > (select max(timestamp) from history where session = ?1)
+                order by timestamp asc limit 1"
+        };
+
+        let res: Option<(String,)> = sqlx::query_as(query)
+            .bind(session)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(|(session,)| session))
     }
 
     async fn query_history(&self, query: &str) -> Result<Vec<History>> {
@@ -527,6 +674,9 @@ impl Database for Sqlite {
                 "group_concat(cwd, ':') as cwd",
                 "group_concat(session) as session",
                 "group_concat(hostname, ',') as hostname",
+                "git_branch",
+                "git_commit",
+                "tag",
                 "count(*) as count",
             ])
             .group_by("command")
@@ -547,6 +697,119 @@ impl Database for Sqlite {
         Ok(res)
     }
 
+    async fn stats(&self, h: &History) -> Result<HistoryStats> {
+        let timestamp = h.timestamp.unix_timestamp_nanos() as i64;
+
+        let previous = sqlx::query(
+            "select * from history
+                where session = ?1 and timestamp < ?2 and deleted_at is null
+                order by timestamp desc limit 1",
+        )
+        .bind(h.session.as_str())
+        .bind(timestamp)
+        .map(Self::query_history)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let next = sqlx::query(
+            "select * from history
+                where session = ?1 and timestamp > ?2 and deleted_at is null
+                order by timestamp asc limit 1",
+        )
+        .bind(h.session.as_str())
+        .bind(timestamp)
+        .map(Self::query_history)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let exits: Vec<(i64, i64)> = sqlx::query_as(
+            "select exit, count(1) as runs from history
+                where command = ?1 and deleted_at is null
+                group by exit order by runs desc, exit asc",
+        )
+        .bind(h.command.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let (average_duration,): (Option<f64>,) = sqlx::query_as(
+            "select avg(duration) from history
+                where command = ?1 and duration >= 0 and deleted_at is null",
+        )
+        .bind(h.command.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let exits: Vec<(i64, u64)> = exits
+            .into_iter()
+            .map(|(exit, runs)| (exit, runs as u64))
+            .collect();
+
+        Ok(HistoryStats {
+            previous,
+            next,
+            total: exits.iter().map(|(_, runs)| runs).sum(),
+            exits,
+            average_duration: average_duration.map(|d| d as i64),
+        })
+    }
+
+    async fn history_store_idx(&self, host: HostId) -> Result<Option<RecordIdx>> {
+        let res: Option<(i64,)> =
+            sqlx::query_as("select idx from history_store_progress where host = ?1")
+                .bind(host.0.as_simple().to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(res.map(|(idx,)| idx as u64))
+    }
+
+    async fn set_history_store_idx(&self, host: HostId, idx: RecordIdx) -> Result<()> {
+        sqlx::query(
+            "insert into history_store_progress(host, idx) values(?1, ?2)
+                on conflict(host) do update set idx = ?2",
+        )
+        .bind(host.0.as_simple().to_string())
+        .bind(idx as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear_history_store_idx(&self) -> Result<()> {
+        sqlx::query("delete from history_store_progress")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("delete from history_store_pending_deletes")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn pending_history_deletes(&self) -> Result<Vec<HistoryId>> {
+        let res: Vec<(String,)> = sqlx::query_as("select id from history_store_pending_deletes")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.into_iter().map(|(id,)| HistoryId(id)).collect())
+    }
+
+    async fn set_history_delete_pending(&self, id: &HistoryId, pending: bool) -> Result<()> {
+        let query = if pending {
+            "insert or ignore into history_store_pending_deletes(id) values(?1)"
+        } else {
+            "delete from history_store_pending_deletes where id = ?1"
+        };
+
+        sqlx::query(query)
+            .bind(id.0.as_str())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     // deleted_at doesn't mean the actual time that the user deleted it,
     // but the time that the system marks it as deleted
     async fn delete(&self, mut h: History) -> Result<()> {
@@ -564,10 +827,64 @@ impl Database for Sqlite {
     }
 }
 
+/// Turn a search query into an FTS5 query. Bare words match as tokens, "double
+/// quoted" text matches as a phrase, and a trailing `*` on either makes it a
+/// prefix match. Every term is quoted, so punctuation in commands is never
+/// mistaken for FTS5 syntax.
+fn fts_query(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut term = String::new();
+
+        if c == '"' {
+            // a phrase runs to the closing quote, or the end of the query
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                term.push(c);
+            }
+        } else {
+            term.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                term.push(c);
+            }
+        }
+
+        let prefix = if let Some(stripped) = term.strip_suffix('*') {
+            term = stripped.to_string();
+            true
+        } else {
+            chars.next_if_eq(&'*').is_some()
+        };
+
+        // only keep terms that the tokenizer will actually turn into tokens
+        if !term.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+
+        let star = if prefix { "*" } else { "" };
+        terms.push(format!("\"{}\"{star}", term.replace('"', "\"\"")));
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use std::time::{Duration, Instant};
+    use time::macros::datetime;
 
     async fn assert_search_eq<'a>(
         db: &impl Database,
@@ -668,6 +985,91 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(
+            fts_query("git commit"),
+            Some(r#""git" "commit""#.to_string())
+        );
+        assert_eq!(
+            fts_query("\"git commit\""),
+            Some(r#""git commit""#.to_string())
+        );
+        assert_eq!(fts_query("git comm*"), Some(r#""git" "comm"*"#.to_string()));
+        assert_eq!(
+            fts_query("\"git comm\"*"),
+            Some(r#""git comm"*"#.to_string())
+        );
+        assert_eq!(
+            fts_query("\"unterminated"),
+            Some(r#""unterminated""#.to_string())
+        );
+        assert_eq!(fts_query("ls -la"), Some(r#""ls" "-la""#.to_string()));
+        assert_eq!(fts_query("  | * \"\" "), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_fts() {
+        let mut db = Sqlite::new("sqlite::memory:").await.unwrap();
+        new_history_item(&mut db, "ls /home/ellie").await.unwrap();
+        new_history_item(&mut db, "cargo build --release")
+            .await
+            .unwrap();
+        new_history_item(&mut db, "git commit -m 'fix the build'")
+            .await
+            .unwrap();
+        new_history_item(&mut db, "git checkout main")
+            .await
+            .unwrap();
+
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "git", 2)
+            .await
+            .unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "home ellie", 1)
+            .await
+            .unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "ellie home", 1)
+            .await
+            .unwrap();
+        assert_search_eq(
+            &db,
+            SearchMode::Fts,
+            FilterMode::Global,
+            "\"ellie home\"",
+            0,
+        )
+        .await
+        .unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "gi", 0)
+            .await
+            .unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "ch*", 1)
+            .await
+            .unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "\"git c\"*", 2)
+            .await
+            .unwrap();
+
+        // ranked by relevance, not recency
+        assert_search_commands(
+            &db,
+            SearchMode::Fts,
+            FilterMode::Global,
+            "build",
+            vec!["cargo build --release", "git commit -m 'fix the build'"],
+        )
+        .await;
+
+        // the index follows deletes, which overwrite the command
+        let mut found = assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "checkout", 1)
+            .await
+            .unwrap();
+        db.delete(found.remove(0)).await.unwrap();
+        assert_search_eq(&db, SearchMode::Fts, FilterMode::Global, "checkout", 0)
+            .await
+            .unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_search_fuzzy() {
         let mut db = Sqlite::new("sqlite::memory:").await.unwrap();
@@ -784,6 +1186,96 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stats() {
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+
+        let run = |command: &str, session: &str, exit: i64, duration: i64, minute: i64| {
+            let mut h: History = History::import()
+                .timestamp(datetime!(2024-01-04 00:00 +00:00) + time::Duration::minutes(minute))
+                .command(command)
+                .exit(exit)
+                .duration(duration)
+                .session(session)
+                .build()
+                .into();
+            h.id = format!("{command}-{minute}").into();
+            h
+        };
+
+        let history = vec![
+            run("cargo build", "a", 0, 100, 0),
+            run("cargo test", "a", 1, 200, 1),
+            run("cargo fmt", "b", 0, 10, 2),
+            run("cargo test", "a", 0, 400, 3),
+            run("cargo test", "a", 0, -1, 4),
+            run("git push", "a", 0, 50, 5),
+        ];
+        db.save_bulk(&history).await.unwrap();
+
+        let stats = db.stats(&history[3]).await.unwrap();
+
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.exits, vec![(0, 2), (1, 1)]);
+        assert_eq!(stats.average_duration, Some(300));
+        assert_eq!(stats.success_rate(), Some(2.0 / 3.0));
+        assert_eq!(stats.previous.unwrap().command, "cargo test");
+        assert_eq!(stats.next.unwrap().command, "cargo test");
+
+        let stats = db.stats(&history[0]).await.unwrap();
+        assert_eq!(stats.previous, None);
+        assert_eq!(stats.next.unwrap().command, "cargo test");
+
+        // the last command in a session has nothing after it
+        let stats = db.stats(&history[5]).await.unwrap();
+        assert_eq!(stats.next, None);
+
+        // imported history with no known exit code has no success rate
+        let unknown = HistoryStats {
+            exits: vec![(-1, 4)],
+            ..HistoryStats::default()
+        };
+        assert_eq!(unknown.success_rate(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_adjacent_session() {
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+
+        let run = |session: &str, hostname: &str, minute: i64| -> History {
+            History::import()
+                .timestamp(datetime!(2024-01-04 00:00 +00:00) + time::Duration::minutes(minute))
+                .command(format!("{session}-{minute}"))
+                .session(session)
+                .hostname(hostname)
+                .build()
+                .into()
+        };
+
+        db.save_bulk(&[
+            run("a", "laptop:ellie", 0),
+            run("a", "laptop:ellie", 1),
+            run("other", "desktop:ellie", 2),
+            run("b", "laptop:ellie", 3),
+            run("b", "laptop:ellie", 4),
+            run("c", "laptop:ellie", 5),
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.adjacent_session("b", true).await.unwrap(),
+            Some("a".to_string())
+        );
+        assert_eq!(
+            db.adjacent_session("b", false).await.unwrap(),
+            Some("c".to_string())
+        );
+        assert_eq!(db.adjacent_session("a", true).await.unwrap(), None);
+        assert_eq!(db.adjacent_session("c", false).await.unwrap(), None);
+        assert_eq!(db.adjacent_session("other", false).await.unwrap(), None);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_search_bench_dupes() {
         let context = Context {
@@ -8,7 +8,10 @@
 // clients must share the secret in order to be able to sync, as it is needed
 // to decrypt
 
-use std::{io::prelude::*, path::PathBuf};
+use std::{
+    io::prelude::*,
+    path::{Path, PathBuf},
+};
 
 use base64::prelude::{Engine, BASE64_STANDARD};
 pub use crypto_secretbox::Key;
@@ -24,6 +27,8 @@ use time::{format_description::well_known::Rfc3339, macros::format_description,
 
 use crate::{history::History, settings::Settings};
 
+pub mod protect;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EncryptedHistory {
     pub ciphertext: Vec<u8>,
@@ -31,15 +36,23 @@ pub struct EncryptedHistory {
 }
 
 pub fn new_key(settings: &Settings) -> Result<Key> {
-    let path = settings.key_path.as_str();
+    let key = generate_key();
+    save_key(&settings.key_path, &key)?;
+
+    Ok(key)
+}
+
+pub fn generate_key() -> Key {
+    XSalsa20Poly1305::generate_key(&mut OsRng)
+}
 
-    let key = XSalsa20Poly1305::generate_key(&mut OsRng);
-    let encoded = encode_key(&key)?;
+pub fn save_key(path: impl AsRef<Path>, key: &Key) -> Result<()> {
+    let encoded = encode_key(key)?;
 
-    let mut file = fs::File::create(path)?;
+    let mut file = fs::File::create(path.as_ref())?;
     file.write_all(encoded.as_bytes())?;
 
-    Ok(key)
+    Ok(())
 }
 
 // Loads the secret key, will create + save if it doesn't exist
@@ -48,7 +61,12 @@ pub fn load_key(settings: &Settings) -> Result<Key> {
 
     let key = if PathBuf::from(path).exists() {
         let key = fs_err::read_to_string(path)?;
-        decode_key(key)?
+
+        if protect::is_protected(&key) {
+            protect::unlock_from_env(&key)?
+        } else {
+            decode_key(key)?
+        }
     } else {
         new_key(settings)?
     };
@@ -56,6 +74,19 @@ pub fn load_key(settings: &Settings) -> Result<Key> {
     Ok(key)
 }
 
+// Whether the key file is protected, and load_key would fail with protect::Locked
+pub fn key_locked(settings: &Settings) -> Result<bool> {
+    let path = settings.key_path.as_str();
+
+    if !PathBuf::from(path).exists() {
+        return Ok(false);
+    }
+
+    let key = fs_err::read_to_string(path)?;
+
+    Ok(protect::is_protected(&key) && protect::is_locked(&key)?)
+}
+
 pub fn encode_key(key: &Key) -> Result<String> {
     let mut buf = vec![];
     rmp::encode::write_array_len(&mut buf, key.len() as u32)
@@ -164,6 +195,8 @@ fn encode(h: &History) -> Result<Vec<u8>> {
 
     let mut output = vec![];
     // INFO: ensure this is updated when adding new fields
+    // git_branch, git_commit and tag are left out. Older versions refuse history with more than 9
+    // fields, so including them would stop those syncing. Only the record store carries them
     encode::write_array_len(&mut output, 9)?;
 
     encode::write_str(&mut output, &h.id.0)?;
@@ -242,6 +275,9 @@ fn decode(bytes: &[u8]) -> Result<History> {
         cwd: cwd.to_owned(),
         session: session.to_owned(),
         hostname: hostname.to_owned(),
+        git_branch: None,
+        git_commit: None,
+        tag: None,
         deleted_at: deleted_at
             .map(|t| OffsetDateTime::parse(t, &Rfc3339))
             .transpose()?,
@@ -276,6 +312,9 @@ mod test {
             .duration(1)
             .session("beep boop".into())
             .hostname("booop".into())
+            .git_branch(None)
+            .git_commit(None)
+            .tag(None)
             .deleted_at(None)
             .build()
             .into();
@@ -320,6 +359,9 @@ mod test {
             cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
             session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
             hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at: None,
         };
 
@@ -341,6 +383,9 @@ mod test {
             cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
             session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
             hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at: Some(datetime!(2023-05-28 18:35:40.633872 +00:00)),
         };
 
@@ -349,6 +394,39 @@ mod test {
         assert_eq!(history, h);
     }
 
+    #[test]
+    fn test_encode_leaves_out_git_and_tag() {
+        let history = History {
+            id: "66d16cbee7cd47538e5c5b8b44e9006e".to_owned().into(),
+            timestamp: datetime!(2023-05-28 18:35:40.633872 +00:00),
+            duration: 49206000,
+            exit: 0,
+            command: "git status".to_owned(),
+            cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
+            session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
+            hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: Some("main".to_owned()),
+            git_commit: Some("6a0eacf2b3c1".to_owned()),
+            tag: Some("venv".to_owned()),
+            deleted_at: None,
+        };
+
+        // still readable by versions that only know the first 9 fields
+        let b = encode(&history).unwrap();
+        assert_eq!(b[0], 0x99);
+
+        let h = decode(&b).unwrap();
+        assert_eq!(
+            h,
+            History {
+                git_branch: None,
+                git_commit: None,
+                tag: None,
+                ..history
+            }
+        );
+    }
+
     #[test]
     fn test_decode_old() {
         let bytes = [
@@ -372,6 +450,9 @@ mod test {
             cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
             session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
             hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at: None,
         };
 
@@ -0,0 +1,231 @@
+// Protecting the key file with a passphrase.
+// A protected key file holds a PASERK `k4.local-pw` key: the encryption key, wrapped with a key
+// derived from the passphrase with Argon2id. The `k4.local-pw.` header versions the format, and the
+// Argon2 parameters are stored alongside the salt, so they can be raised later without breaking
+// existing files.
+//
+// Deriving the wrapping key is deliberately slow, and the key is needed for every command we
+// record. So `atuin key unlock` unwraps it once, and keeps it in the data dir wrapped with a random
+// session key. The session key only lives in the environment of the shell that ran unlock, so the
+// unlocked file is no use on its own.
+
+use std::{env, time::Duration};
+
+use atuin_common::utils::{data_dir, uuid_v4};
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use eyre::{ensure, eyre, Result};
+use fs_err as fs;
+use rusty_paserk::{Key as PaserkKey, Local, PieWrappedKey, PwWrappedKey, V4};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::Key;
+
+const PROTECTED_PREFIX: &str = "k4.local-pw.";
+
+/// If set, the passphrase used to unlock a protected key file
+pub const PASSPHRASE_VAR: &str = "ATUIN_KEY_PASSPHRASE";
+
+/// Set by `atuin key unlock`, to find the key it unlocked
+pub const UNLOCK_VAR: &str = "ATUIN_KEY_UNLOCK";
+
+#[derive(Serialize, Deserialize)]
+struct UnlockedKey {
+    /// The protected key this was unlocked from. If the key file has changed since, we don't use it
+    protected: String,
+    /// Unix timestamp
+    expires: i64,
+    key: PieWrappedKey<V4, Local>,
+}
+
+/// The key file is protected, and neither the passphrase nor an unlocked key is in the environment
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "the encryption key is protected with a passphrase. Run `eval \"$(atuin key unlock)\"`, or set {}",
+    PASSPHRASE_VAR
+)]
+pub struct Locked;
+
+pub fn is_protected(contents: &str) -> bool {
+    contents.trim_start().starts_with(PROTECTED_PREFIX)
+}
+
+pub fn protect(key: &Key, passphrase: &str) -> String {
+    PaserkKey::<V4, Local>::from_bytes((*key).into())
+        .pw_wrap(passphrase.as_bytes())
+        .to_string()
+}
+
+pub fn unprotect(protected: &str, passphrase: &str) -> Result<Key> {
+    let wrapped: PwWrappedKey<V4, Local> = protected
+        .trim()
+        .parse()
+        .map_err(|_| eyre!("the protected key file is not valid"))?;
+
+    let key = wrapped
+        .unwrap_key(passphrase.as_bytes())
+        .map_err(|_| eyre!("incorrect passphrase"))?;
+
+    Ok(key.to_bytes().into())
+}
+
+/// Unlock a protected key file, with the passphrase from the environment or the key unlocked by
+/// `atuin key unlock`
+pub fn unlock_from_env(protected: &str) -> Result<Key> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return unprotect(protected, &passphrase);
+    }
+
+    if let Some(key) = unlocked(protected)? {
+        return Ok(key);
+    }
+
+    Err(Locked.into())
+}
+
+/// Whether a protected key file can't be unlocked from the environment. Unlike `unlock_from_env`,
+/// this doesn't check the passphrase, which is slow
+pub fn is_locked(protected: &str) -> Result<bool> {
+    Ok(env::var(PASSPHRASE_VAR).is_err() && unlocked(protected)?.is_none())
+}
+
+/// Unlock a protected key file for `timeout`. Returns the value to set `ATUIN_KEY_UNLOCK` to
+pub fn unlock(protected: &str, passphrase: &str, timeout: Duration) -> Result<String> {
+    let key = unprotect(protected, passphrase)?;
+
+    let id = uuid_v4();
+    let session = PaserkKey::<V4, Local>::new_os_random();
+
+    let unlocked = UnlockedKey {
+        protected: protected.trim().to_string(),
+        expires: (OffsetDateTime::now_utc() + timeout).unix_timestamp(),
+        key: PaserkKey::<V4, Local>::from_bytes(key.into()).wrap_pie(&session),
+    };
+
+    let dir = data_dir().join("unlocked");
+    fs::create_dir_all(&dir)?;
+    remove_expired()?;
+
+    let path = dir.join(&id);
+    fs::write(&path, serde_json::to_string(&unlocked)?)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(format!(
+        "{id}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(session.to_bytes())
+    ))
+}
+
+/// Forget the key unlocked for this shell, if there is one
+pub fn lock() -> Result<bool> {
+    let Some((id, _)) = unlock_var()? else {
+        return Ok(false);
+    };
+
+    let path = data_dir().join("unlocked").join(id);
+
+    if path.exists() {
+        fs::remove_file(path)?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+fn unlock_var() -> Result<Option<(String, [u8; 32])>> {
+    let Ok(var) = env::var(UNLOCK_VAR) else {
+        return Ok(None);
+    };
+
+    let invalid = || eyre!("{UNLOCK_VAR} is not valid");
+
+    let (id, session) = var.split_once('.').ok_or_else(invalid)?;
+    ensure!(
+        !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()),
+        invalid()
+    );
+
+    let session = BASE64_URL_SAFE_NO_PAD
+        .decode(session)
+        .map_err(|_| invalid())?
+        .try_into()
+        .map_err(|_| invalid())?;
+
+    Ok(Some((id.to_string(), session)))
+}
+
+fn unlocked(protected: &str) -> Result<Option<Key>> {
+    let Some((id, session)) = unlock_var()? else {
+        return Ok(None);
+    };
+
+    let path = data_dir().join("unlocked").join(id);
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let unlocked: UnlockedKey = serde_json::from_str(&fs::read_to_string(&path)?)?;
+
+    if unlocked.expires < OffsetDateTime::now_utc().unix_timestamp() {
+        fs::remove_file(path)?;
+        return Ok(None);
+    }
+
+    if unlocked.protected != protected.trim() {
+        return Ok(None);
+    }
+
+    let key = unlocked
+        .key
+        .unwrap_key(&PaserkKey::<V4, Local>::from_bytes(session))
+        .map_err(|_| eyre!("{UNLOCK_VAR} does not match the unlocked key"))?;
+
+    Ok(Some(key.to_bytes().into()))
+}
+
+fn remove_expired() -> Result<()> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    for entry in fs::read_dir(data_dir().join("unlocked"))? {
+        let path = entry?.path();
+
+        let expired = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<UnlockedKey>(&s).ok())
+            .map_or(true, |u| u.expires < now);
+
+        if expired {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_secretbox::{aead::OsRng, KeyInit, XSalsa20Poly1305};
+
+    use super::{is_protected, protect, unprotect};
+    use crate::encryption::encode_key;
+
+    #[test]
+    fn protect_unprotect() {
+        let key = XSalsa20Poly1305::generate_key(&mut OsRng);
+
+        let protected = protect(&key, "correct horse");
+        assert!(is_protected(&protected));
+        assert!(!is_protected(&encode_key(&key).unwrap()));
+
+        assert_eq!(unprotect(&protected, "correct horse").unwrap(), key);
+        assert!(unprotect(&protected, "battery staple").is_err());
+        assert!(unprotect("k4.local-pw.nonsense", "correct horse").is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::history::History;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One JSON object per line, with every history field
+    Jsonl,
+
+    /// Comma separated values, with a header row
+    Csv,
+
+    /// zsh extended history, as written with EXTENDED_HISTORY set
+    Zsh,
+
+    /// bash history, with timestamps as written when HISTTIMEFORMAT is set. Multiline commands
+    /// are left out, as bash can't read them back
+    Bash,
+
+    /// fish history
+    Fish,
+}
+
+/// A history entry, as exported to JSON. Every field of [`History`] is included, so this can be
+/// read back in without losing anything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryJson {
+    pub id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub duration: i64,
+    pub exit: i64,
+    pub command: String,
+    pub cwd: String,
+    pub session: String,
+    pub hostname: String,
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    #[serde(default)]
+    pub git_commit: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deleted_at: Option<OffsetDateTime>,
+}
+
+impl From<&History> for HistoryJson {
+    fn from(h: &History) -> Self {
+        Self {
+            id: h.id.0.clone(),
+            timestamp: h.timestamp,
+            duration: h.duration,
+            exit: h.exit,
+            command: h.command.clone(),
+            cwd: h.cwd.clone(),
+            session: h.session.clone(),
+            hostname: h.hostname.clone(),
+            git_branch: h.git_branch.clone(),
+            git_commit: h.git_commit.clone(),
+            tag: h.tag.clone(),
+            deleted_at: h.deleted_at,
+        }
+    }
+}
+
+impl From<HistoryJson> for History {
+    fn from(h: HistoryJson) -> Self {
+        History::from_db()
+            .id(h.id)
+            .timestamp(h.timestamp)
+            .duration(h.duration)
+            .exit(h.exit)
+            .command(h.command)
+            .cwd(h.cwd)
+            .session(h.session)
+            .hostname(h.hostname)
+            .git_branch(h.git_branch)
+            .git_commit(h.git_commit)
+            .tag(h.tag)
+            .deleted_at(h.deleted_at)
+            .build()
+            .into()
+    }
+}
+
+/// Write history out in the given format. Entries are written in the order given.
+pub fn export(w: &mut impl Write, format: ExportFormat, history: &[History]) -> Result<()> {
+    if format == ExportFormat::Csv {
+        writeln!(
+            w,
+            "id,timestamp,duration,exit,command,cwd,session,hostname,deleted_at,git_branch,git_commit,tag"
+        )?;
+    }
+
+    for h in history {
+        match format {
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut *w, &HistoryJson::from(h))?;
+                writeln!(w)?;
+            }
+            ExportFormat::Csv => write_csv(w, h)?,
+            ExportFormat::Zsh => write_zsh(w, h)?,
+            ExportFormat::Bash => write_bash(w, h)?,
+            ExportFormat::Fish => write_fish(w, h)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(w: &mut impl Write, h: &History) -> Result<()> {
+    let deleted_at = h
+        .deleted_at
+        .map(|t| t.unix_timestamp_nanos().to_string())
+        .unwrap_or_default();
+
+    writeln!(
+        w,
+        "{},{},{},{},{},{},{},{},{},{},{},{}",
+        csv_field(&h.id.0),
+        h.timestamp.unix_timestamp_nanos(),
+        h.duration,
+        h.exit,
+        csv_field(&h.command),
+        csv_field(&h.cwd),
+        csv_field(&h.session),
+        csv_field(&h.hostname),
+        deleted_at,
+        csv_field(h.git_branch.as_deref().unwrap_or_default()),
+        csv_field(h.git_commit.as_deref().unwrap_or_default()),
+        csv_field(h.tag.as_deref().unwrap_or_default()),
+    )?;
+
+    Ok(())
+}
+
+// zsh marks a newline inside a command by escaping it with a backslash
+fn write_zsh(w: &mut impl Write, h: &History) -> Result<()> {
+    let duration = h.duration.max(0) / 1_000_000_000;
+    let command = h.command.replace('\n', "\\\n");
+
+    writeln!(w, ": {}:{duration};{command}", h.timestamp.unix_timestamp())?;
+
+    Ok(())
+}
+
+// bash reads each line of its history as a command, with no way to escape a newline, so
+// multiline commands would come back in pieces. They're left out
+fn write_bash(w: &mut impl Write, h: &History) -> Result<()> {
+    if h.command.contains('\n') {
+        return Ok(());
+    }
+
+    writeln!(w, "#{}", h.timestamp.unix_timestamp())?;
+    writeln!(w, "{}", h.command)?;
+
+    Ok(())
+}
+
+fn write_fish(w: &mut impl Write, h: &History) -> Result<()> {
+    let command = h.command.replace('\\', "\\\\").replace('\n', "\\n");
+
+    writeln!(w, "- cmd: {command}")?;
+    writeln!(w, "  when: {}", h.timestamp.unix_timestamp())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    use crate::history::History;
+
+    use super::{export, ExportFormat, HistoryJson};
+
+    fn history() -> Vec<History> {
+        let ls = History::from_db()
+            .id("018cd4fe81757cd2aee65cd7861f9c81".to_string())
+            .timestamp(datetime!(2024-01-04 00:00:00 +00:00))
+            .duration(2_500_000_000)
+            .exit(0)
+            .command("ls".to_string())
+            .cwd("/home/ellie".to_string())
+            .session("018cd4fead897597852527a31c998059".to_string())
+            .hostname("boop:ellie".to_string())
+            .git_branch(Some("main".to_string()))
+            .git_commit(Some("6a0eacf2b3c1".to_string()))
+            .tag(Some("venv".to_string()))
+            .deleted_at(None)
+            .build()
+            .into();
+
+        let multiline = History::from_db()
+            .id("018cd4ff1a2b7cd2aee65cd7861f9c82".to_string())
+            .timestamp(datetime!(2024-01-04 00:01:00 +00:00))
+            .duration(1_000)
+            .exit(1)
+            .command("echo \"a, b\" \\\n  c".to_string())
+            .cwd("/home/ellie".to_string())
+            .session("018cd4fead897597852527a31c998059".to_string())
+            .hostname("boop:ellie".to_string())
+            .git_branch(None)
+            .git_commit(None)
+            .tag(None)
+            .deleted_at(None)
+            .build()
+            .into();
+
+        vec![ls, multiline]
+    }
+
+    fn export_string(format: ExportFormat) -> String {
+        let mut out = vec![];
+        export(&mut out, format, &history()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let out = export_string(ExportFormat::Jsonl);
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            r#"{"id":"018cd4fe81757cd2aee65cd7861f9c81","timestamp":"2024-01-04T00:00:00Z","duration":2500000000,"exit":0,"command":"ls","cwd":"/home/ellie","session":"018cd4fead897597852527a31c998059","hostname":"boop:ellie","git_branch":"main","git_commit":"6a0eacf2b3c1","tag":"venv","deleted_at":null}"#
+        );
+
+        let parsed: Vec<History> = lines
+            .iter()
+            .map(|l| serde_json::from_str::<HistoryJson>(l).unwrap().into())
+            .collect();
+        assert_eq!(parsed, history());
+
+        // exports from before git metadata was recorded are still readable
+        let old = r#"{"id":"018cd4fe81757cd2aee65cd7861f9c81","timestamp":"2024-01-04T00:00:00Z","duration":2500000000,"exit":0,"command":"ls","cwd":"/home/ellie","session":"018cd4fead897597852527a31c998059","hostname":"boop:ellie","deleted_at":null}"#;
+        let old: History = serde_json::from_str::<HistoryJson>(old).unwrap().into();
+        assert_eq!(old.git_branch, None);
+        assert_eq!(old.tag, None);
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            export_string(ExportFormat::Csv),
+            "id,timestamp,duration,exit,command,cwd,session,hostname,deleted_at,git_branch,git_commit,tag
+018cd4fe81757cd2aee65cd7861f9c81,1704326400000000000,2500000000,0,ls,/home/ellie,018cd4fead897597852527a31c998059,boop:ellie,,main,6a0eacf2b3c1,venv
+018cd4ff1a2b7cd2aee65cd7861f9c82,1704326460000000000,1000,1,\"echo \"\"a, b\"\" \\
+  c\",/home/ellie,018cd4fead897597852527a31c998059,boop:ellie,,,,
+"
+        );
+    }
+
+    #[test]
+    fn zsh() {
+        assert_eq!(
+            export_string(ExportFormat::Zsh),
+            ": 1704326400:2;ls
+: 1704326460:0;echo \"a, b\" \\\\
+  c
+"
+        );
+    }
+
+    #[test]
+    fn bash() {
+        assert_eq!(
+            export_string(ExportFormat::Bash),
+            "#1704326400
+ls
+"
+        );
+    }
+
+    #[test]
+    fn fish() {
+        assert_eq!(
+            export_string(ExportFormat::Fish),
+            "- cmd: ls
+  when: 1704326400
+- cmd: echo \"a, b\" \\\\\\n  c
+  when: 1704326460
+"
+        );
+    }
+}
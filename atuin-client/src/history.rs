@@ -16,8 +16,9 @@ use time::OffsetDateTime;
 mod builder;
 pub mod store;
 
-const HISTORY_VERSION: &str = "v0";
-const HISTORY_TAG: &str = "history";
+pub(crate) const HISTORY_VERSION_V0: &str = "v0";
+pub(crate) const HISTORY_VERSION: &str = "v1";
+pub(crate) const HISTORY_TAG: &str = "history";
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct HistoryId(pub String);
@@ -67,10 +68,54 @@ pub struct History {
     pub session: String,
     /// The hostname of the machine the command was run on.
     pub hostname: String,
+    /// The git branch checked out in the working directory, if any.
+    pub git_branch: Option<String>,
+    /// The git commit checked out in the working directory, if any.
+    pub git_commit: Option<String>,
+    /// A user-provided tag, read from the environment. Eg the active virtualenv or kube context.
+    pub tag: Option<String>,
     /// Timestamp, which is set when the entry is deleted, allowing a soft delete.
     pub deleted_at: Option<OffsetDateTime>,
 }
 
+/// Everything known about the runs of a single command, as shown in the inspector.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryStats {
+    /// The command run immediately before this one, in the same session.
+    pub previous: Option<History>,
+    /// The command run immediately after this one, in the same session.
+    pub next: Option<History>,
+    /// How many times the command has been run.
+    pub total: u64,
+    /// How many times each exit code has been seen, most common first.
+    pub exits: Vec<(i64, u64)>,
+    /// The average duration in nanoseconds, over runs where it is known.
+    pub average_duration: Option<i64>,
+}
+
+impl HistoryStats {
+    /// The fraction of runs that exited successfully. Runs with an unknown exit code (eg imported
+    /// history) are not counted.
+    pub fn success_rate(&self) -> Option<f64> {
+        let known: u64 = self
+            .exits
+            .iter()
+            .filter(|(exit, _)| *exit != -1)
+            .map(|(_, count)| count)
+            .sum();
+
+        let success: u64 = self
+            .exits
+            .iter()
+            .filter(|(exit, _)| *exit == 0)
+            .map(|(_, count)| count)
+            .sum();
+
+        #[allow(clippy::cast_precision_loss)]
+        (known > 0).then(|| success as f64 / known as f64)
+    }
+}
+
 impl History {
     #[allow(clippy::too_many_arguments)]
     fn new(
@@ -103,6 +148,9 @@ impl History {
             duration,
             session,
             hostname,
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at,
         }
     }
@@ -116,9 +164,9 @@ impl History {
         let mut output = vec![];
 
         // write the version
-        encode::write_u16(&mut output, 0)?;
+        encode::write_u16(&mut output, 1)?;
         // INFO: ensure this is updated when adding new fields
-        encode::write_array_len(&mut output, 9)?;
+        encode::write_array_len(&mut output, 12)?;
 
         encode::write_str(&mut output, &self.id.0)?;
         encode::write_u64(&mut output, self.timestamp.unix_timestamp_nanos() as u64)?;
@@ -134,6 +182,13 @@ impl History {
             None => encode::write_nil(&mut output)?,
         }
 
+        for field in [&self.git_branch, &self.git_commit, &self.tag] {
+            match field {
+                Some(s) => encode::write_str(&mut output, s)?,
+                None => encode::write_nil(&mut output)?,
+            }
+        }
+
         Ok(DecryptedData(output))
     }
 
@@ -195,6 +250,90 @@ impl History {
             cwd: cwd.to_owned(),
             session: session.to_owned(),
             hostname: hostname.to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
+            deleted_at: deleted_at
+                .map(|t| OffsetDateTime::from_unix_timestamp_nanos(t as i128))
+                .transpose()?,
+        })
+    }
+
+    fn deserialize_v1(bytes: &[u8]) -> Result<History> {
+        use rmp::decode;
+
+        fn error_report<E: std::fmt::Debug>(err: E) -> eyre::Report {
+            eyre!("{err:?}")
+        }
+
+        // read a string, accepting null as None
+        fn read_opt_str(bytes: &[u8]) -> Result<(Option<String>, &[u8])> {
+            if bytes.first() == Some(&Marker::Null.to_u8()) {
+                return Ok((None, &bytes[1..]));
+            }
+
+            let (s, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+
+            Ok((Some(s.to_owned()), bytes))
+        }
+
+        let mut bytes = Bytes::new(bytes);
+
+        let version = decode::read_u16(&mut bytes).map_err(error_report)?;
+
+        if version != 1 {
+            bail!("expected decoding v1 record, found v{version}");
+        }
+
+        let nfields = decode::read_array_len(&mut bytes).map_err(error_report)?;
+
+        if nfields != 12 {
+            bail!("cannot decrypt history from a different version of Atuin");
+        }
+
+        let bytes = bytes.remaining_slice();
+        let (id, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+
+        let mut bytes = Bytes::new(bytes);
+        let timestamp = decode::read_u64(&mut bytes).map_err(error_report)?;
+        let duration = decode::read_int(&mut bytes).map_err(error_report)?;
+        let exit = decode::read_int(&mut bytes).map_err(error_report)?;
+
+        let bytes = bytes.remaining_slice();
+        let (command, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+        let (cwd, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+        let (session, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+        let (hostname, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+
+        let mut bytes = Bytes::new(bytes);
+
+        let (deleted_at, bytes) = match decode::read_u64(&mut bytes) {
+            Ok(unix) => (Some(unix), bytes.remaining_slice()),
+            // we accept null here
+            Err(ValueReadError::TypeMismatch(Marker::Null)) => (None, bytes.remaining_slice()),
+            Err(err) => return Err(error_report(err)),
+        };
+
+        let (git_branch, bytes) = read_opt_str(bytes)?;
+        let (git_commit, bytes) = read_opt_str(bytes)?;
+        let (tag, bytes) = read_opt_str(bytes)?;
+
+        if !bytes.is_empty() {
+            bail!("trailing bytes in encoded history. malformed")
+        }
+
+        Ok(History {
+            id: id.to_owned().into(),
+            timestamp: OffsetDateTime::from_unix_timestamp_nanos(timestamp as i128)?,
+            duration,
+            exit,
+            command: command.to_owned(),
+            cwd: cwd.to_owned(),
+            session: session.to_owned(),
+            hostname: hostname.to_owned(),
+            git_branch,
+            git_commit,
+            tag,
             deleted_at: deleted_at
                 .map(|t| OffsetDateTime::from_unix_timestamp_nanos(t as i128))
                 .transpose()?,
@@ -203,7 +342,8 @@ impl History {
 
     pub fn deserialize(bytes: &[u8], version: &str) -> Result<History> {
         match version {
-            HISTORY_VERSION => Self::deserialize_v0(bytes),
+            HISTORY_VERSION_V0 => Self::deserialize_v0(bytes),
+            HISTORY_VERSION => Self::deserialize_v1(bytes),
 
             _ => bail!("unknown version {version:?}"),
         }
@@ -304,6 +444,9 @@ impl History {
     ///     .duration(100)
     ///     .session("somesession".to_string())
     ///     .hostname("localhost".to_string())
+    ///     .git_branch(None)
+    ///     .git_commit(None)
+    ///     .tag(None)
     ///     .deleted_at(None)
     ///     .build()
     ///     .into();
@@ -325,6 +468,19 @@ impl History {
             || settings.cwd_filter.is_match(&self.cwd)
             || (secret_regex.is_match(&self.command)) && settings.secrets_filter)
     }
+
+    /// Whether this history is kept out of sync, by `local_only_cwd` or `local_only_hosts`
+    pub fn is_local_only(&self, settings: &Settings) -> bool {
+        // hostnames are stored as host:user
+        let host = self.hostname.split(':').next().unwrap_or_default();
+
+        settings.sync.local_only_cwd.is_match(&self.cwd)
+            || settings
+                .sync
+                .local_only_hosts
+                .iter()
+                .any(|h| h == host || h == &self.hostname)
+    }
 }
 
 #[cfg(test)]
@@ -332,7 +488,10 @@ mod tests {
     use regex::RegexSet;
     use time::macros::datetime;
 
-    use crate::{history::HISTORY_VERSION, settings::Settings};
+    use crate::{
+        history::{HISTORY_VERSION, HISTORY_VERSION_V0},
+        settings::{Globs, Settings},
+    };
 
     use super::History;
 
@@ -387,6 +546,28 @@ mod tests {
         assert!(!with_psql.should_save(&settings));
     }
 
+    #[test]
+    fn local_only() {
+        let mut settings = Settings::default();
+        settings.sync.local_only_cwd = Globs::new(["/work/clients"]).unwrap();
+        settings.sync.local_only_hosts = vec!["shared".to_string()];
+
+        let history = |cwd: &str, hostname: &str| -> History {
+            History::import()
+                .timestamp(time::OffsetDateTime::now_utc())
+                .command("ls")
+                .cwd(cwd)
+                .hostname(hostname)
+                .build()
+                .into()
+        };
+
+        assert!(!history("/work/atuin", "laptop:ellie").is_local_only(&settings));
+        assert!(history("/work/clients/acme", "laptop:ellie").is_local_only(&settings));
+        assert!(history("/work/atuin", "shared:ellie").is_local_only(&settings));
+        assert!(!history("/work/atuin", "shared2:ellie").is_local_only(&settings));
+    }
+
     #[test]
     fn disable_secrets() {
         let settings = Settings {
@@ -427,6 +608,51 @@ mod tests {
             cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
             session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
             hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
+            deleted_at: None,
+        };
+
+        let serialized = history.serialize().expect("failed to serialize history");
+
+        let deserialized = History::deserialize(&serialized.0, HISTORY_VERSION)
+            .expect("failed to deserialize history");
+        assert_eq!(history, deserialized);
+
+        // records written before v1 must still be readable
+        let deserialized = History::deserialize(&bytes, HISTORY_VERSION_V0)
+            .expect("failed to deserialize history");
+        assert_eq!(history, deserialized);
+    }
+
+    #[test]
+    fn test_serialize_deserialize_v1() {
+        let bytes = [
+            205, 0, 1, 156, 217, 32, 54, 54, 100, 49, 54, 99, 98, 101, 101, 55, 99, 100, 52, 55,
+            53, 51, 56, 101, 53, 99, 53, 98, 56, 98, 52, 52, 101, 57, 48, 48, 54, 101, 207, 23, 99,
+            98, 117, 24, 210, 246, 128, 206, 2, 238, 210, 240, 0, 170, 103, 105, 116, 32, 115, 116,
+            97, 116, 117, 115, 217, 42, 47, 85, 115, 101, 114, 115, 47, 99, 111, 110, 114, 97, 100,
+            46, 108, 117, 100, 103, 97, 116, 101, 47, 68, 111, 99, 117, 109, 101, 110, 116, 115,
+            47, 99, 111, 100, 101, 47, 97, 116, 117, 105, 110, 217, 32, 98, 57, 55, 100, 57, 97,
+            51, 48, 54, 102, 50, 55, 52, 52, 55, 51, 97, 50, 48, 51, 100, 50, 101, 98, 97, 52, 49,
+            102, 57, 52, 53, 55, 187, 102, 118, 102, 103, 57, 51, 54, 99, 48, 107, 112, 102, 58,
+            99, 111, 110, 114, 97, 100, 46, 108, 117, 100, 103, 97, 116, 101, 192, 164, 109, 97,
+            105, 110, 167, 54, 97, 48, 101, 97, 99, 102, 192,
+        ];
+
+        let history = History {
+            id: "66d16cbee7cd47538e5c5b8b44e9006e".to_owned().into(),
+            timestamp: datetime!(2023-05-28 18:35:40.633872 +00:00),
+            duration: 49206000,
+            exit: 0,
+            command: "git status".to_owned(),
+            cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
+            session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
+            hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: Some("main".to_owned()),
+            git_commit: Some("6a0eacf".to_owned()),
+            tag: None,
             deleted_at: None,
         };
 
@@ -454,6 +680,9 @@ mod tests {
             cwd: "/Users/conrad.ludgate/Documents/code/atuin".to_owned(),
             session: "b97d9a306f274473a203d2eba41f9457".to_owned(),
             hostname: "fvfg936c0kpf:conrad.ludgate".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at: Some(datetime!(2023-11-19 20:18 +00:00)),
         };
 
@@ -493,9 +722,16 @@ mod tests {
             99, 111, 110, 114, 97, 100, 46, 108, 117, 100, 103, 97, 116, 101, 192,
         ];
 
-        let deserialized = History::deserialize(&bytes_v0, HISTORY_VERSION);
+        let deserialized = History::deserialize(&bytes_v0, HISTORY_VERSION_V0);
         assert!(deserialized.is_ok());
 
+        let deserialized = History::deserialize(&bytes_v1, HISTORY_VERSION_V0);
+        assert!(deserialized.is_err());
+
+        // the v1 decoder expects the extra fields
+        let deserialized = History::deserialize(&bytes_v0, HISTORY_VERSION);
+        assert!(deserialized.is_err());
+
         let deserialized = History::deserialize(&bytes_v1, HISTORY_VERSION);
         assert!(deserialized.is_err());
     }
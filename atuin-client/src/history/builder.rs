@@ -49,20 +49,31 @@ pub struct HistoryCaptured {
     command: String,
     #[builder(setter(into))]
     cwd: String,
+    #[builder(default)]
+    git_branch: Option<String>,
+    #[builder(default)]
+    git_commit: Option<String>,
+    #[builder(default)]
+    tag: Option<String>,
 }
 
 impl From<HistoryCaptured> for History {
     fn from(captured: HistoryCaptured) -> Self {
-        History::new(
-            captured.timestamp,
-            captured.command,
-            captured.cwd,
-            -1,
-            -1,
-            None,
-            None,
-            None,
-        )
+        History {
+            git_branch: captured.git_branch,
+            git_commit: captured.git_commit,
+            tag: captured.tag,
+            ..History::new(
+                captured.timestamp,
+                captured.command,
+                captured.cwd,
+                -1,
+                -1,
+                None,
+                None,
+                None,
+            )
+        }
     }
 }
 
@@ -79,6 +90,9 @@ pub struct HistoryFromDb {
     duration: i64,
     session: String,
     hostname: String,
+    git_branch: Option<String>,
+    git_commit: Option<String>,
+    tag: Option<String>,
     deleted_at: Option<time::OffsetDateTime>,
 }
 
@@ -93,6 +107,9 @@ impl From<HistoryFromDb> for History {
             duration: from_db.duration,
             session: from_db.session,
             hostname: from_db.hostname,
+            git_branch: from_db.git_branch,
+            git_commit: from_db.git_commit,
+            tag: from_db.tag,
             deleted_at: from_db.deleted_at,
         }
     }
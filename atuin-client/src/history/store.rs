@@ -1,10 +1,17 @@
 use eyre::{bail, eyre, Result};
 use rmp::decode::Bytes;
 
-use crate::record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store};
-use atuin_common::record::{DecryptedData, Host, HostId, Record, RecordIdx};
+use crate::{
+    database::Database,
+    record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store},
+};
+use atuin_common::record::{DecryptedData, EncryptedData, Host, HostId, Record, RecordIdx};
+use log::warn;
 
-use super::{History, HistoryId, HISTORY_TAG, HISTORY_VERSION};
+use super::{History, HistoryId, HISTORY_TAG, HISTORY_VERSION, HISTORY_VERSION_V0};
+
+// How many records to load from the store at once, when replaying history
+const BUILD_PAGE_SIZE: u64 = 1000;
 
 #[derive(Debug)]
 pub struct HistoryStore {
@@ -102,6 +109,19 @@ impl HistoryRecord {
     }
 }
 
+/// Delete history from the database, returning false if it isn't there
+async fn apply_delete(db: &impl Database, id: &HistoryId) -> Result<bool> {
+    let Some(history) = db.load(id.0.as_str()).await? else {
+        return Ok(false);
+    };
+
+    if history.deleted_at.is_none() {
+        db.delete(history).await?;
+    }
+
+    Ok(true)
+}
+
 impl HistoryStore {
     pub fn new(store: SqliteStore, host_id: HostId, encryption_key: [u8; 32]) -> Self {
         HistoryStore {
@@ -147,15 +167,134 @@ impl HistoryStore {
 
         self.push_record(record).await
     }
+
+    /// Replay every history record that has not yet been applied to the history database,
+    /// from every host, and return how many were applied.
+    ///
+    /// Creates are applied before deletes, but a delete can still arrive before the history it
+    /// refers to, if that came from another host that hasn't been synced in full yet. Such
+    /// deletes are kept as pending, and applied by a later build once the history is here.
+    ///
+    /// Records that can't be read, because they were encrypted with another key or are from a
+    /// newer version, are logged and skipped.
+    pub async fn incremental_build(&self, db: &impl Database) -> Result<usize> {
+        let status = self.store.status().await?;
+
+        let mut applied = 0;
+        let mut deletes = vec![];
+        let mut progress = vec![];
+
+        for (host, tags) in status.hosts {
+            let Some(&tail) = tags.get(HISTORY_TAG) else {
+                continue;
+            };
+
+            let start = db.history_store_idx(host).await?.map_or(0, |idx| idx + 1);
+            let mut idx = start;
+
+            while idx <= tail {
+                let records = self
+                    .store
+                    .next(host, HISTORY_TAG, idx, BUILD_PAGE_SIZE)
+                    .await?;
+
+                let Some(last) = records.last() else {
+                    break;
+                };
+                idx = last.idx + 1;
+
+                let mut creates = vec![];
+
+                for record in records {
+                    let record_idx = record.idx;
+
+                    match self.read(record) {
+                        Ok(HistoryRecord::Create(history)) => creates.push(history),
+                        Ok(HistoryRecord::Delete(id)) => deletes.push(id),
+                        Err(e) => {
+                            warn!("skipping history record {record_idx} from host {host:?}: {e}");
+                        }
+                    }
+                }
+
+                applied += creates.len();
+                db.save_bulk(&creates).await?;
+            }
+
+            if idx > start {
+                progress.push((host, idx - 1));
+            }
+        }
+
+        for id in db.pending_history_deletes().await? {
+            if apply_delete(db, &id).await? {
+                db.set_history_delete_pending(&id, false).await?;
+                applied += 1;
+            }
+        }
+
+        for id in deletes {
+            if apply_delete(db, &id).await? {
+                applied += 1;
+            } else {
+                db.set_history_delete_pending(&id, true).await?;
+            }
+        }
+
+        for (host, idx) in progress {
+            db.set_history_store_idx(host, idx).await?;
+        }
+
+        Ok(applied)
+    }
+
+    fn read(&self, record: Record<EncryptedData>) -> Result<HistoryRecord> {
+        let decrypted = match record.version.as_str() {
+            HISTORY_VERSION_V0 | HISTORY_VERSION => {
+                record.decrypt::<PASETO_V4>(&self.encryption_key)?
+            }
+            version => bail!("unknown history version {version:?}"),
+        };
+
+        HistoryRecord::deserialize(&decrypted.data.0, &decrypted.version)
+    }
+
+    /// Replay the whole history store into the history database, from the start.
+    ///
+    /// History that only exists in the database is left alone.
+    pub async fn build(&self, db: &impl Database) -> Result<usize> {
+        db.clear_history_store_idx().await?;
+
+        self.incremental_build(db).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use time::macros::datetime;
-
-    use crate::history::{store::HistoryRecord, HISTORY_VERSION};
-
-    use super::History;
+    use atuin_common::{
+        record::{Host, HostId, Record},
+        utils::uuid_v7,
+    };
+    use crypto_secretbox::{KeyInit, XSalsa20Poly1305};
+    use rand::rngs::OsRng;
+    use time::{macros::datetime, OffsetDateTime};
+
+    use crate::{
+        database::{Database, Sqlite},
+        history::{store::HistoryRecord, HISTORY_TAG, HISTORY_VERSION, HISTORY_VERSION_V0},
+        record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store},
+    };
+
+    use super::{History, HistoryStore};
+
+    fn test_history(command: &str) -> History {
+        History::import()
+            .timestamp(OffsetDateTime::now_utc())
+            .command(command)
+            .cwd("/home/ellie")
+            .build()
+            .into()
+    }
 
     #[test]
     fn test_serialize_deserialize_create() {
@@ -179,20 +318,22 @@ mod tests {
             cwd: "/Users/ellie/src/github.com/atuinsh/atuin".to_owned(),
             session: "018cd4fead897597852527a31c998059".to_owned(),
             hostname: "boop:ellie".to_owned(),
+            git_branch: None,
+            git_commit: None,
+            tag: None,
             deleted_at: None,
         };
 
         let record = HistoryRecord::Create(history);
 
         let serialized = record.serialize().expect("failed to serialize history");
-        assert_eq!(serialized.0, bytes);
 
         let deserialized = HistoryRecord::deserialize(&serialized.0, HISTORY_VERSION)
             .expect("failed to deserialize HistoryRecord");
         assert_eq!(deserialized, record);
 
-        // check the snapshot too
-        let deserialized = HistoryRecord::deserialize(&bytes, HISTORY_VERSION)
+        // check the v0 snapshot too
+        let deserialized = HistoryRecord::deserialize(&bytes, HISTORY_VERSION_V0)
             .expect("failed to deserialize HistoryRecord");
         assert_eq!(deserialized, record);
     }
@@ -216,4 +357,116 @@ mod tests {
             .expect("failed to deserialize HistoryRecord");
         assert_eq!(deserialized, record);
     }
+
+    #[tokio::test]
+    async fn incremental_build() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let key: [u8; 32] = XSalsa20Poly1305::generate_key(&mut OsRng).into();
+
+        let laptop = HistoryStore::new(store.clone(), HostId(uuid_v7()), key);
+        let desktop = HistoryStore::new(store.clone(), HostId(uuid_v7()), key);
+
+        let ls = test_history("ls");
+        let cd = test_history("cd /tmp");
+
+        laptop.push(ls.clone()).await.unwrap();
+        laptop.push(cd.clone()).await.unwrap();
+        desktop.delete(ls.id.clone()).await.unwrap();
+
+        assert_eq!(laptop.incremental_build(&db).await.unwrap(), 3);
+        assert_eq!(db.history_count(false).await.unwrap(), 1);
+        assert_eq!(db.history_count(true).await.unwrap(), 2);
+        assert!(db
+            .load(&ls.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .deleted_at
+            .is_some());
+
+        // nothing new, nothing to do
+        assert_eq!(laptop.incremental_build(&db).await.unwrap(), 0);
+
+        desktop.push(test_history("git status")).await.unwrap();
+
+        assert_eq!(laptop.incremental_build(&db).await.unwrap(), 1);
+        assert_eq!(db.history_count(false).await.unwrap(), 2);
+
+        // a full rebuild replays everything, and leaves the result the same
+        assert_eq!(laptop.build(&db).await.unwrap(), 4);
+        assert_eq!(db.history_count(false).await.unwrap(), 2);
+        assert_eq!(db.history_count(true).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn delete_before_create() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let key: [u8; 32] = XSalsa20Poly1305::generate_key(&mut OsRng).into();
+
+        // the phone's records only reach this store on the second sync
+        let phone_store = SqliteStore::new(":memory:").await.unwrap();
+        let phone = HistoryStore::new(phone_store.clone(), HostId(uuid_v7()), key);
+        let desktop = HistoryStore::new(store.clone(), HostId(uuid_v7()), key);
+
+        let secret = test_history("export TOKEN=hunter2");
+        phone.push(secret.clone()).await.unwrap();
+        desktop.delete(secret.id.clone()).await.unwrap();
+
+        assert_eq!(desktop.incremental_build(&db).await.unwrap(), 0);
+        assert_eq!(
+            db.pending_history_deletes().await.unwrap(),
+            std::slice::from_ref(&secret.id)
+        );
+
+        for record in phone_store.all_tagged(HISTORY_TAG).await.unwrap() {
+            store.push(&record).await.unwrap();
+        }
+
+        assert_eq!(desktop.incremental_build(&db).await.unwrap(), 2);
+        assert!(db
+            .load(&secret.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .deleted_at
+            .is_some());
+        assert!(db.pending_history_deletes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn skip_unreadable_records() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let key: [u8; 32] = XSalsa20Poly1305::generate_key(&mut OsRng).into();
+        let old_key: [u8; 32] = XSalsa20Poly1305::generate_key(&mut OsRng).into();
+
+        let laptop = HistoryStore::new(store.clone(), HostId(uuid_v7()), key);
+        let old_laptop = HistoryStore::new(store.clone(), laptop.host_id, old_key);
+
+        old_laptop.push(test_history("ls")).await.unwrap();
+
+        let future = Record::builder()
+            .host(Host::new(laptop.host_id))
+            .version("v9000".to_string())
+            .tag(HISTORY_TAG.to_string())
+            .idx(1)
+            .data(
+                HistoryRecord::Create(test_history("cd"))
+                    .serialize()
+                    .unwrap(),
+            )
+            .build();
+        store
+            .push(&future.encrypt::<PASETO_V4>(&key))
+            .await
+            .unwrap();
+
+        laptop.push(test_history("git status")).await.unwrap();
+
+        assert_eq!(laptop.incremental_build(&db).await.unwrap(), 1);
+        assert_eq!(db.history_count(true).await.unwrap(), 1);
+        assert_eq!(db.history_store_idx(laptop.host_id).await.unwrap(), Some(2));
+    }
 }
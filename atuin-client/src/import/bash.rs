@@ -127,7 +127,10 @@ mod test {
     use std::cmp::Ordering;
 
     use itertools::{assert_equal, Itertools};
+    use time::macros::datetime;
 
+    use crate::export::{export, ExportFormat};
+    use crate::history::History;
     use crate::import::{tests::TestLoader, Importer};
 
     use super::Bash;
@@ -207,6 +210,42 @@ cd ../
         assert!(is_strictly_sorted(loader.buf.iter().map(|h| h.timestamp)))
     }
 
+    #[tokio::test]
+    async fn round_trip_export() {
+        let history: Vec<History> = [
+            (datetime!(2024-01-04 00:00:00 +00:00), "ls"),
+            (
+                datetime!(2024-01-04 00:01:00 +00:00),
+                "for x in a b; do\n  echo $x\ndone",
+            ),
+            (datetime!(2024-01-04 00:02:00 +00:00), "cd ../"),
+        ]
+        .into_iter()
+        .map(|(timestamp, command)| {
+            History::import()
+                .timestamp(timestamp)
+                .command(command)
+                .build()
+                .into()
+        })
+        .collect();
+
+        let mut bytes = Vec::new();
+        export(&mut bytes, ExportFormat::Bash, &history).unwrap();
+
+        let mut loader = TestLoader::default();
+        Bash { bytes }.load(&mut loader).await.unwrap();
+
+        // the multiline command is left out, rather than read back as three commands
+        assert_equal(
+            loader.buf.iter().map(|h| (h.timestamp, h.command.as_str())),
+            [
+                (datetime!(2024-01-04 00:00:00 +00:00), "ls"),
+                (datetime!(2024-01-04 00:02:00 +00:00), "cd ../"),
+            ],
+        );
+    }
+
     fn is_strictly_sorted<T>(iter: impl IntoIterator<Item = T>) -> bool
     where
         T: Clone + PartialOrd,
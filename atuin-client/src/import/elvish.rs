@@ -0,0 +1,417 @@
+// Elvish keeps its history in a bbolt database: a B+tree of buckets, in fixed size pages of a
+// single file. All we need is to walk one bucket, so rather than depend on a full implementation,
+// this reads just enough of the format. See https://github.com/etcd-io/bbolt for the layout.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use directories::BaseDirs;
+use eyre::{bail, eyre, Result};
+use time::{Duration, OffsetDateTime};
+
+use super::{get_histpath, Importer, Loader};
+use crate::history::History;
+use crate::import::read_to_end;
+
+const MAGIC: u32 = 0xED0C_DAED;
+
+const PAGE_HEADER: usize = 16;
+const ELEMENT_SIZE: usize = 16;
+const BUCKET_HEADER: usize = 16;
+
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+const BUCKET_LEAF: u32 = 0x01;
+
+/// Bucket trees are only a few levels deep, so anything deeper is a loop in a corrupt file
+const MAX_DEPTH: usize = 64;
+
+/// Elvish keys each command by its sequence number
+const COMMANDS_BUCKET: &[u8] = b"cmd";
+
+#[derive(Debug)]
+pub struct Elvish {
+    bytes: Vec<u8>,
+}
+
+fn default_histpath() -> Result<PathBuf> {
+    let base = BaseDirs::new().ok_or_else(|| eyre!("could not determine data directory"))?;
+
+    let state_dir = std::env::var_os("XDG_STATE_HOME").map_or_else(
+        || base.home_dir().join(".local").join("state"),
+        PathBuf::from,
+    );
+
+    let candidates = [
+        state_dir.join("elvish").join("db.bolt"),
+        base.data_local_dir().join("elvish").join("db.bolt"),
+        // before elvish 0.17
+        base.home_dir().join(".elvish").join("db"),
+    ];
+
+    candidates
+        .iter()
+        .find(|p| p.is_file())
+        .cloned()
+        .ok_or_else(|| eyre!("could not find the elvish database"))
+}
+
+fn read_u16(b: &[u8], at: usize) -> Result<u16> {
+    let bytes = b.get(at..at + 2).ok_or_else(corrupt)?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u32(b: &[u8], at: usize) -> Result<u32> {
+    let bytes = b.get(at..at + 4).ok_or_else(corrupt)?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u64(b: &[u8], at: usize) -> Result<u64> {
+    let bytes = b.get(at..at + 8).ok_or_else(corrupt)?;
+    Ok(u64::from_le_bytes(bytes.try_into()?))
+}
+
+fn corrupt() -> eyre::Report {
+    eyre!("the elvish database is corrupt")
+}
+
+fn fnv64a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+struct Bolt<'a> {
+    bytes: &'a [u8],
+    page_size: usize,
+    root: u64,
+}
+
+impl<'a> Bolt<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        let page_size = read_u32(bytes, PAGE_HEADER + 8)? as usize;
+
+        if page_size < PAGE_HEADER {
+            bail!("not an elvish database");
+        }
+
+        // there are two meta pages, written alternately, so use the newest one that's intact
+        let (_, root) = [0, page_size]
+            .into_iter()
+            .filter_map(|at| Self::meta(bytes, at))
+            .max()
+            .ok_or_else(|| eyre!("not an elvish database"))?;
+
+        Ok(Self {
+            bytes,
+            page_size,
+            root,
+        })
+    }
+
+    /// The transaction ID and root page of the meta page at `at`, if it's valid
+    fn meta(bytes: &[u8], at: usize) -> Option<(u64, u64)> {
+        let meta = bytes.get(at + PAGE_HEADER..at + PAGE_HEADER + 64)?;
+
+        if read_u32(meta, 0).ok()? != MAGIC || read_u64(meta, 56).ok()? != fnv64a(&meta[..56]) {
+            return None;
+        }
+
+        Some((read_u64(meta, 48).ok()?, read_u64(meta, 16).ok()?))
+    }
+
+    fn page(&self, id: u64) -> Result<&'a [u8]> {
+        let start = usize::try_from(id)?
+            .checked_mul(self.page_size)
+            .ok_or_else(corrupt)?;
+        let overflow = read_u32(self.bytes, start + 12)? as usize;
+        let end = start + (overflow + 1) * self.page_size;
+
+        self.bytes.get(start..end).ok_or_else(corrupt)
+    }
+
+    /// Call `f` with the flags, key and value of every element in a tree, in key order
+    fn walk(
+        &self,
+        page: &'a [u8],
+        depth: usize,
+        f: &mut impl FnMut(u32, &'a [u8], &'a [u8]),
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(corrupt());
+        }
+
+        let flags = read_u16(page, 8)?;
+        let count = read_u16(page, 10)? as usize;
+
+        for i in 0..count {
+            let element = PAGE_HEADER + i * ELEMENT_SIZE;
+
+            match flags {
+                BRANCH_PAGE => {
+                    let child = read_u64(page, element + 8)?;
+                    self.walk(self.page(child)?, depth + 1, f)?;
+                }
+
+                LEAF_PAGE => {
+                    let flags = read_u32(page, element)?;
+                    let key = element + read_u32(page, element + 4)? as usize;
+                    let value = key + read_u32(page, element + 8)? as usize;
+                    let end = value + read_u32(page, element + 12)? as usize;
+
+                    let key = page.get(key..value).ok_or_else(corrupt)?;
+                    let value = page.get(value..end).ok_or_else(corrupt)?;
+
+                    f(flags, key, value);
+                }
+
+                _ => return Err(corrupt()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every key and value in a top level bucket, in key order
+    fn bucket(&self, name: &[u8]) -> Result<Vec<(&'a [u8], &'a [u8])>> {
+        let mut bucket = None;
+
+        self.walk(self.page(self.root)?, 0, &mut |flags, key, value| {
+            if flags & BUCKET_LEAF != 0 && key == name {
+                bucket = Some(value);
+            }
+        })?;
+
+        let Some(bucket) = bucket else {
+            return Ok(Vec::new());
+        };
+
+        // small buckets are stored inline, right after their header
+        let root = read_u64(bucket, 0)?;
+        let page = if root == 0 {
+            bucket.get(BUCKET_HEADER..).ok_or_else(corrupt)?
+        } else {
+            self.page(root)?
+        };
+
+        let mut entries = Vec::new();
+        self.walk(page, 0, &mut |flags, key, value| {
+            if flags & BUCKET_LEAF == 0 {
+                entries.push((key, value));
+            }
+        })?;
+
+        Ok(entries)
+    }
+}
+
+impl Elvish {
+    fn commands(&self) -> Result<Vec<String>> {
+        let db = Bolt::new(&self.bytes)?;
+
+        let commands = db
+            .bucket(COMMANDS_BUCKET)?
+            .into_iter()
+            // invalid utf8 is skipped
+            .filter_map(|(_, command)| std::str::from_utf8(command).ok())
+            .filter(|command| !command.trim().is_empty())
+            .map(String::from)
+            .collect();
+
+        Ok(commands)
+    }
+}
+
+#[async_trait]
+impl Importer for Elvish {
+    const NAME: &'static str = "elvish";
+
+    async fn new() -> Result<Self> {
+        let bytes = read_to_end(get_histpath(default_histpath)?)?;
+        Ok(Self { bytes })
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(self.commands()?.len())
+    }
+
+    async fn load(self, h: &mut impl Loader) -> Result<()> {
+        let commands = self.commands()?;
+
+        // elvish doesn't record when commands ran, so keep their order by spacing them a
+        // millisecond apart, up to now
+        let timestamp_increment = Duration::milliseconds(1);
+        let mut timestamp = OffsetDateTime::now_utc() - timestamp_increment * commands.len() as i32;
+
+        for command in commands {
+            let imported = History::import().timestamp(timestamp).command(command);
+
+            h.push(imported.build().into()).await?;
+            timestamp += timestamp_increment;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::assert_equal;
+
+    use crate::import::{tests::TestLoader, Importer};
+
+    use super::{fnv64a, Elvish, BRANCH_PAGE, BUCKET_LEAF, LEAF_PAGE, MAGIC};
+
+    const PAGE_SIZE: usize = 4096;
+
+    fn page(id: u64, flags: u16, count: usize) -> Vec<u8> {
+        let mut page = Vec::new();
+        page.extend(id.to_le_bytes());
+        page.extend(flags.to_le_bytes());
+        page.extend(u16::try_from(count).unwrap().to_le_bytes());
+        page.extend(0_u32.to_le_bytes());
+        page
+    }
+
+    fn meta(id: u64, root: u64, txid: u64) -> Vec<u8> {
+        let mut meta = Vec::new();
+        meta.extend(MAGIC.to_le_bytes());
+        meta.extend(2_u32.to_le_bytes());
+        meta.extend(u32::try_from(PAGE_SIZE).unwrap().to_le_bytes());
+        meta.extend(0_u32.to_le_bytes());
+        meta.extend(root.to_le_bytes());
+        meta.extend(0_u64.to_le_bytes());
+        meta.extend(0_u64.to_le_bytes());
+        meta.extend(0_u64.to_le_bytes());
+        meta.extend(txid.to_le_bytes());
+        meta.extend(fnv64a(&meta).to_le_bytes());
+
+        let mut page = page(id, 0x04, 0);
+        page.extend(meta);
+        page
+    }
+
+    fn leaf(id: u64, entries: &[(u32, &[u8], &[u8])]) -> Vec<u8> {
+        let mut page = page(id, LEAF_PAGE, entries.len());
+        let mut data = Vec::<u8>::new();
+
+        for (i, (flags, key, value)) in entries.iter().enumerate() {
+            let pos = (entries.len() - i) * 16 + data.len();
+
+            page.extend(flags.to_le_bytes());
+            page.extend(u32::try_from(pos).unwrap().to_le_bytes());
+            page.extend(u32::try_from(key.len()).unwrap().to_le_bytes());
+            page.extend(u32::try_from(value.len()).unwrap().to_le_bytes());
+
+            data.extend(*key);
+            data.extend(*value);
+        }
+
+        page.extend(data);
+        page
+    }
+
+    fn branch(id: u64, children: &[(&[u8], u64)]) -> Vec<u8> {
+        let mut page = page(id, BRANCH_PAGE, children.len());
+        let mut data = Vec::<u8>::new();
+
+        for (i, (key, child)) in children.iter().enumerate() {
+            let pos = (children.len() - i) * 16 + data.len();
+
+            page.extend(u32::try_from(pos).unwrap().to_le_bytes());
+            page.extend(u32::try_from(key.len()).unwrap().to_le_bytes());
+            page.extend(child.to_le_bytes());
+
+            data.extend(*key);
+        }
+
+        page.extend(data);
+        page
+    }
+
+    fn bucket_header(root: u64) -> Vec<u8> {
+        let mut header = root.to_le_bytes().to_vec();
+        header.extend(0_u64.to_le_bytes());
+        header
+    }
+
+    fn db(pages: Vec<Vec<u8>>) -> Vec<u8> {
+        pages
+            .into_iter()
+            .flat_map(|mut page| {
+                page.resize(PAGE_SIZE, 0);
+                page
+            })
+            .collect()
+    }
+
+    async fn load(bytes: Vec<u8>) -> Vec<String> {
+        let mut elvish = Elvish { bytes };
+        let entries = elvish.entries().await.unwrap();
+
+        let mut loader = TestLoader::default();
+        elvish.load(&mut loader).await.unwrap();
+
+        assert_eq!(entries, loader.buf.len());
+        loader.buf.into_iter().map(|h| h.command).collect()
+    }
+
+    #[tokio::test]
+    async fn inline_bucket() {
+        let mut commands = bucket_header(0);
+        commands.extend(leaf(
+            0,
+            &[
+                (0, &1_u64.to_be_bytes(), b"echo hello"),
+                (0, &2_u64.to_be_bytes(), b"put (+ 1 2)"),
+            ],
+        ));
+
+        let root = leaf(
+            2,
+            &[
+                (BUCKET_LEAF, b"cmd", &commands),
+                (BUCKET_LEAF, b"dir", &bucket_header(0)),
+            ],
+        );
+
+        // the older meta page points at a root that doesn't exist
+        let bytes = db(vec![meta(0, 2, 5), meta(1, 9, 4), root]);
+
+        assert_equal(load(bytes).await, ["echo hello", "put (+ 1 2)"]);
+    }
+
+    #[tokio::test]
+    async fn branched_bucket() {
+        let root = leaf(2, &[(BUCKET_LEAF, b"cmd", &bucket_header(3))]);
+        let commands = branch(3, &[(&1_u64.to_be_bytes(), 4), (&3_u64.to_be_bytes(), 5)]);
+        let first = leaf(
+            4,
+            &[
+                (0, &1_u64.to_be_bytes(), b"ls"),
+                (0, &2_u64.to_be_bytes(), b"cd ~/src"),
+            ],
+        );
+        let second = leaf(5, &[(0, &3_u64.to_be_bytes(), b"git status")]);
+
+        let bytes = db(vec![
+            meta(0, 2, 1),
+            meta(1, 2, 0),
+            root,
+            commands,
+            first,
+            second,
+        ]);
+
+        assert_equal(load(bytes).await, ["ls", "cd ~/src", "git status"]);
+    }
+
+    #[tokio::test]
+    async fn not_bolt() {
+        let mut elvish = Elvish {
+            bytes: b"ls\ncd ~/src\n".to_vec(),
+        };
+
+        assert!(elvish.entries().await.is_err());
+    }
+}
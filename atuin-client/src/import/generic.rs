@@ -0,0 +1,464 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use eyre::{bail, eyre, Result};
+use serde_json::{Map, Value};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use super::{get_histpath, Importer, Loader};
+use crate::export::HistoryJson;
+use crate::history::History;
+use crate::import::read_to_end;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line
+    Jsonl,
+
+    /// Comma separated values, with a header row naming the fields
+    Csv,
+}
+
+/// The name of the field holding each part of a history entry. The defaults are the names that
+/// `atuin history export` uses, so its output can be imported as it is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fields {
+    pub id: String,
+    pub timestamp: String,
+    pub duration: String,
+    pub exit: String,
+    pub command: String,
+    pub cwd: String,
+    pub session: String,
+    pub hostname: String,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self {
+            id: String::from("id"),
+            timestamp: String::from("timestamp"),
+            duration: String::from("duration"),
+            exit: String::from("exit"),
+            command: String::from("command"),
+            cwd: String::from("cwd"),
+            session: String::from("session"),
+            hostname: String::from("hostname"),
+        }
+    }
+}
+
+/// History from any JSONL or CSV file, with each field of an entry found by name. Only the
+/// command is required.
+///
+/// Timestamps are either RFC 3339, or a Unix timestamp in seconds, milliseconds, microseconds or
+/// nanoseconds, told apart by their size. Durations are in nanoseconds. Entries without a
+/// timestamp are given one that keeps them in order, as the shell importers do.
+#[derive(Debug)]
+pub struct Generic {
+    bytes: Vec<u8>,
+    format: Format,
+    fields: Fields,
+}
+
+impl Generic {
+    pub fn from_file(path: impl AsRef<Path>, format: Format, fields: Fields) -> Result<Self> {
+        let bytes = read_to_end(path.as_ref().to_path_buf())?;
+
+        Ok(Self {
+            bytes,
+            format,
+            fields,
+        })
+    }
+
+    fn records(&self) -> Vec<Map<String, Value>> {
+        let text = String::from_utf8_lossy(&self.bytes);
+
+        match self.format {
+            Format::Jsonl => text
+                .lines()
+                // skip blank lines and invalid json
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect(),
+
+            Format::Csv => {
+                let mut rows = csv_rows(&text).into_iter();
+                let header = rows.next().unwrap_or_default();
+
+                rows.map(|row| {
+                    header
+                        .iter()
+                        .cloned()
+                        .zip(row.into_iter().map(Value::String))
+                        .collect()
+                })
+                .collect()
+            }
+        }
+    }
+
+    fn history(&self) -> Result<Vec<History>> {
+        let records = self.records();
+        let count = records.len();
+
+        // timestamps for entries that don't have one, ending now
+        let timestamp_increment = Duration::milliseconds(1);
+        let start = OffsetDateTime::now_utc() - timestamp_increment * count as i32;
+
+        let history: Vec<History> = records
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, record)| {
+                let fallback = start + timestamp_increment * i as i32;
+                self.entry(record, fallback)
+            })
+            .collect();
+
+        if count > 0 && history.is_empty() {
+            bail!(
+                "none of the {count} entries have a command in the `{}` field",
+                self.fields.command
+            );
+        }
+
+        Ok(history)
+    }
+
+    fn entry(&self, record: Map<String, Value>, fallback: OffsetDateTime) -> Option<History> {
+        // our own export has everything, including fields that can't be mapped
+        if self.fields == Fields::default() {
+            if let Ok(history) =
+                serde_json::from_value::<HistoryJson>(Value::Object(record.clone()))
+            {
+                return Some(history.into());
+            }
+        }
+
+        let fields = &self.fields;
+
+        let command = string(&record, &fields.command)?;
+        let timestamp = record
+            .get(&fields.timestamp)
+            .and_then(timestamp)
+            .unwrap_or(fallback);
+
+        let mut history: History = History::import()
+            .timestamp(timestamp)
+            .command(command)
+            .build()
+            .into();
+
+        if let Some(id) = string(&record, &fields.id) {
+            history.id = id.into();
+        }
+        if let Some(duration) = integer(&record, &fields.duration) {
+            history.duration = duration;
+        }
+        if let Some(exit) = integer(&record, &fields.exit) {
+            history.exit = exit;
+        }
+        if let Some(cwd) = string(&record, &fields.cwd) {
+            history.cwd = cwd;
+        }
+        if let Some(session) = string(&record, &fields.session) {
+            history.session = session;
+        }
+        if let Some(hostname) = string(&record, &fields.hostname) {
+            history.hostname = hostname;
+        }
+
+        Some(history)
+    }
+}
+
+fn string(record: &Map<String, Value>, field: &str) -> Option<String> {
+    match record.get(field)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn integer(record: &Map<String, Value>, field: &str) -> Option<i64> {
+    match record.get(field)? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn timestamp(value: &Value) -> Option<OffsetDateTime> {
+    let nanos = match value {
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => unix_nanos(n.into()),
+            None => seconds(n.as_f64()?),
+        },
+
+        Value::String(s) => {
+            let s = s.trim();
+
+            if let Ok(timestamp) = OffsetDateTime::parse(s, &Rfc3339) {
+                return Some(timestamp);
+            }
+
+            match s.parse::<i128>() {
+                Ok(n) => unix_nanos(n),
+                Err(_) => seconds(s.parse().ok()?),
+            }
+        }
+
+        _ => return None,
+    };
+
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+}
+
+/// A whole number could be a Unix timestamp in any unit, but for any date in the last few
+/// centuries, only one of them makes sense
+fn unix_nanos(n: i128) -> i128 {
+    match n.abs() {
+        0..=99_999_999_999 => n * 1_000_000_000,
+        100_000_000_000..=99_999_999_999_999 => n * 1_000_000,
+        100_000_000_000_000..=99_999_999_999_999_999 => n * 1_000,
+        _ => n,
+    }
+}
+
+/// Fractional timestamps are in seconds. The whole seconds are kept apart, as an f64 isn't
+/// precise enough to hold nanoseconds since the epoch
+#[allow(clippy::cast_possible_truncation)]
+fn seconds(secs: f64) -> i128 {
+    secs.trunc() as i128 * 1_000_000_000 + (secs.fract() * 1_000_000_000_f64).round() as i128
+}
+
+/// Split CSV into rows of fields. Fields may be quoted, with `""` for a quote, and quoted fields
+/// can span lines.
+fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+
+                // blank lines aren't rows
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+#[async_trait]
+impl Importer for Generic {
+    const NAME: &'static str = "generic";
+
+    /// Read `$HISTFILE`, as JSONL unless it ends in `.csv`, with the default field names
+    async fn new() -> Result<Self> {
+        let path = get_histpath(|| -> Result<PathBuf> {
+            Err(eyre!("no history file given. Try setting $HISTFILE"))
+        })?;
+
+        let format = if path.extension().map_or(false, |ext| ext == "csv") {
+            Format::Csv
+        } else {
+            Format::Jsonl
+        };
+
+        Self::from_file(path, format, Fields::default())
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(self.history()?.len())
+    }
+
+    async fn load(self, h: &mut impl Loader) -> Result<()> {
+        for history in self.history()? {
+            h.push(history).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::assert_equal;
+    use time::macros::datetime;
+
+    use crate::export::{export, ExportFormat};
+    use crate::history::History;
+    use crate::import::{tests::TestLoader, Importer};
+
+    use super::{csv_rows, Fields, Format, Generic};
+
+    async fn load(bytes: &[u8], format: Format, fields: Fields) -> Vec<History> {
+        let mut generic = Generic {
+            bytes: bytes.to_vec(),
+            format,
+            fields,
+        };
+        let entries = generic.entries().await.unwrap();
+
+        let mut loader = TestLoader::default();
+        generic.load(&mut loader).await.unwrap();
+
+        assert_eq!(entries, loader.buf.len());
+        loader.buf
+    }
+
+    fn history() -> Vec<History> {
+        let ls = History::from_db()
+            .id("018cd4fe81757cd2aee65cd7861f9c81".to_string())
+            .timestamp(datetime!(2024-01-04 00:00:00.5 +00:00))
+            .duration(2_500_000_000)
+            .exit(0)
+            .command("ls".to_string())
+            .cwd("/home/ellie".to_string())
+            .session("018cd4fead897597852527a31c998059".to_string())
+            .hostname("boop:ellie".to_string())
+            .git_branch(Some("main".to_string()))
+            .git_commit(None)
+            .tag(None)
+            .deleted_at(None)
+            .build()
+            .into();
+
+        let multiline = History::from_db()
+            .id("018cd4ff1b4a7c8c9d5d1a6b2c3e4f50".to_string())
+            .timestamp(datetime!(2024-01-04 00:01:00 +00:00))
+            .duration(-1)
+            .exit(1)
+            .command("echo \"a, b\"\nfalse".to_string())
+            .cwd("/tmp".to_string())
+            .session("018cd4fead897597852527a31c998059".to_string())
+            .hostname("boop:ellie".to_string())
+            .git_branch(None)
+            .git_commit(None)
+            .tag(None)
+            .deleted_at(None)
+            .build()
+            .into();
+
+        vec![ls, multiline]
+    }
+
+    #[tokio::test]
+    async fn round_trip_export() {
+        let history = history();
+
+        let mut jsonl = Vec::new();
+        export(&mut jsonl, ExportFormat::Jsonl, &history).unwrap();
+        assert_eq!(
+            load(&jsonl, Format::Jsonl, Fields::default()).await,
+            history
+        );
+
+        let mut csv = Vec::new();
+        export(&mut csv, ExportFormat::Csv, &history).unwrap();
+        let imported = load(&csv, Format::Csv, Fields::default()).await;
+
+        // csv doesn't have room for everything
+        assert_equal(
+            imported
+                .iter()
+                .map(|h| (&h.id, h.timestamp, &h.command, &h.cwd, h.exit)),
+            history
+                .iter()
+                .map(|h| (&h.id, h.timestamp, &h.command, &h.cwd, h.exit)),
+        );
+    }
+
+    #[tokio::test]
+    async fn mapped_fields() {
+        let fields = Fields {
+            command: "cmd".to_string(),
+            timestamp: "time".to_string(),
+            cwd: "dir".to_string(),
+            exit: "status".to_string(),
+            ..Fields::default()
+        };
+
+        let jsonl = br#"{"cmd": "cargo test", "time": 1704326400, "dir": "/src", "status": 101}
+{"cmd": "ls", "time": "2024-01-04T00:00:01Z"}
+not json
+
+{"time": 1704326402000}
+{"cmd": "pwd", "time": 1704326403.25}
+"#;
+
+        let imported = load(jsonl, Format::Jsonl, fields.clone()).await;
+
+        assert_equal(
+            imported.iter().map(|h| (h.command.as_str(), h.timestamp)),
+            [
+                ("cargo test", datetime!(2024-01-04 00:00:00 +00:00)),
+                ("ls", datetime!(2024-01-04 00:00:01 +00:00)),
+                ("pwd", datetime!(2024-01-04 00:00:03.25 +00:00)),
+            ],
+        );
+        assert_eq!(imported[0].cwd, "/src");
+        assert_eq!(imported[0].exit, 101);
+        assert_eq!(imported[1].cwd, "unknown");
+        assert_eq!(imported[1].exit, -1);
+
+        let csv =
+            b"time,cmd,status\r\n1704326400000000000,\"git commit -m \"\"wip\"\"\",0\r\n,ls,\r\n";
+        let imported = load(csv, Format::Csv, fields).await;
+
+        assert_eq!(imported[0].command, "git commit -m \"wip\"");
+        assert_eq!(imported[0].timestamp, datetime!(2024-01-04 00:00:00 +00:00));
+        assert_eq!(imported[1].command, "ls");
+        assert!(imported[1].timestamp > imported[0].timestamp);
+    }
+
+    #[tokio::test]
+    async fn missing_command_field() {
+        let mut generic = Generic {
+            bytes: br#"{"cmd": "ls"}"#.to_vec(),
+            format: Format::Jsonl,
+            fields: Fields::default(),
+        };
+
+        assert!(generic.entries().await.is_err());
+    }
+
+    #[test]
+    fn split_csv() {
+        assert_eq!(
+            csv_rows("a,b,c\n1,\"two\nlines\",\n\n\"x\"\"y\",,z"),
+            vec![
+                vec!["a", "b", "c"],
+                vec!["1", "two\nlines", ""],
+                vec!["x\"y", "", "z"],
+            ]
+        );
+    }
+}
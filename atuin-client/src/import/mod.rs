@@ -9,10 +9,14 @@ use memchr::Memchr;
 use crate::history::History;
 
 pub mod bash;
+pub mod elvish;
 pub mod fish;
+pub mod generic;
 pub mod nu;
 pub mod nu_histdb;
+pub mod powershell;
 pub mod resh;
+pub mod xonsh;
 pub mod zsh;
 pub mod zsh_histdb;
 
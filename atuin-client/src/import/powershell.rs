@@ -0,0 +1,142 @@
+use std::{path::PathBuf, str};
+
+use async_trait::async_trait;
+use directories::BaseDirs;
+use eyre::{eyre, Result};
+use time::{Duration, OffsetDateTime};
+
+use super::{get_histpath, unix_byte_lines, Importer, Loader};
+use crate::history::History;
+use crate::import::read_to_end;
+
+/// PSReadLine's history, as used by pwsh. It has no timestamps, just one command per line
+#[derive(Debug)]
+pub struct PowerShell {
+    bytes: Vec<u8>,
+}
+
+fn default_histpath() -> Result<PathBuf> {
+    let base = BaseDirs::new().ok_or_else(|| eyre!("could not determine data directory"))?;
+
+    let dir = if cfg!(windows) {
+        base.config_dir()
+            .join("Microsoft")
+            .join("Windows")
+            .join("PowerShell")
+    } else {
+        // pwsh uses the XDG data dir everywhere but windows, even on macOS
+        std::env::var_os("XDG_DATA_HOME")
+            .map_or_else(
+                || base.home_dir().join(".local").join("share"),
+                PathBuf::from,
+            )
+            .join("powershell")
+    };
+
+    Ok(dir.join("PSReadLine").join("ConsoleHost_history.txt"))
+}
+
+impl PowerShell {
+    /// Commands that span several lines are saved with a backtick at the end of each line but the
+    /// last
+    fn commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        let mut command = String::new();
+
+        for line in unix_byte_lines(&self.bytes) {
+            // invalid utf8 is skipped
+            let Ok(line) = str::from_utf8(line) else {
+                continue;
+            };
+            let line = line.strip_suffix('\r').unwrap_or(line);
+
+            if let Some(line) = line.strip_suffix('`') {
+                command.push_str(line);
+                command.push('\n');
+                continue;
+            }
+
+            command.push_str(line);
+
+            if !command.trim().is_empty() {
+                commands.push(std::mem::take(&mut command));
+            }
+
+            command.clear();
+        }
+
+        commands
+    }
+}
+
+#[async_trait]
+impl Importer for PowerShell {
+    const NAME: &'static str = "powershell";
+
+    async fn new() -> Result<Self> {
+        let mut bytes = read_to_end(get_histpath(default_histpath)?)?;
+
+        // make sure the last line counts, even without a newline at the end
+        if !bytes.ends_with(b"\n") {
+            bytes.push(b'\n');
+        }
+
+        Ok(Self { bytes })
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(self.commands().len())
+    }
+
+    async fn load(self, h: &mut impl Loader) -> Result<()> {
+        let commands = self.commands();
+
+        // as with bash, keep the order by spacing the commands a millisecond apart, up to now
+        let timestamp_increment = Duration::milliseconds(1);
+        let mut timestamp = OffsetDateTime::now_utc() - timestamp_increment * commands.len() as i32;
+
+        for command in commands {
+            let imported = History::import().timestamp(timestamp).command(command);
+
+            h.push(imported.build().into()).await?;
+            timestamp += timestamp_increment;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::assert_equal;
+
+    use crate::import::{tests::TestLoader, Importer};
+
+    use super::PowerShell;
+
+    #[tokio::test]
+    async fn parse_file() {
+        let bytes = b"git status\r\ncd ~/src\r\nGet-ChildItem |`\r\n  Where-Object Length -gt 0\r\n\r\nls\n"
+            .to_vec();
+
+        let mut pwsh = PowerShell { bytes };
+        assert_eq!(pwsh.entries().await.unwrap(), 4);
+
+        let mut loader = TestLoader::default();
+        pwsh.load(&mut loader).await.unwrap();
+
+        assert_equal(
+            loader.buf.iter().map(|h| h.command.as_str()),
+            [
+                "git status",
+                "cd ~/src",
+                "Get-ChildItem |\n  Where-Object Length -gt 0",
+                "ls",
+            ],
+        );
+        assert!(loader
+            .buf
+            .windows(2)
+            .all(|w| w[0].timestamp < w[1].timestamp));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, ffi::OsStr, fs, path::PathBuf};
+
+use async_trait::async_trait;
+use directories::BaseDirs;
+use eyre::{eyre, Result};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use super::{Importer, Loader};
+use crate::history::History;
+
+/// xonsh's JSON history backend, which writes a file for each session
+#[derive(Debug)]
+pub struct Xonsh {
+    sessions: Vec<Vec<u8>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct XonshFile {
+    data: XonshSession,
+}
+
+#[derive(Deserialize, Debug)]
+struct XonshSession {
+    sessionid: String,
+    #[serde(default)]
+    cmds: Vec<XonshCmd>,
+    #[serde(default)]
+    env: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct XonshCmd {
+    inp: String,
+    /// These are missing if the session ended while the command was running
+    rtn: Option<i64>,
+    ts: (f64, Option<f64>),
+}
+
+// the whole seconds are kept apart, as an f64 isn't precise enough to hold nanoseconds since the
+// epoch
+#[allow(clippy::cast_possible_truncation)]
+fn unix_timestamp(secs: f64) -> Result<OffsetDateTime> {
+    let nanos =
+        secs.trunc() as i128 * 1_000_000_000 + (secs.fract() * 1_000_000_000_f64).round() as i128;
+
+    Ok(OffsetDateTime::from_unix_timestamp_nanos(nanos)?)
+}
+
+impl Xonsh {
+    pub fn histpath() -> Result<PathBuf> {
+        let data_dir = match std::env::var_os("XONSH_DATA_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => BaseDirs::new()
+                .ok_or_else(|| eyre!("could not determine data directory"))?
+                .data_dir()
+                .join("xonsh"),
+        };
+
+        // older versions of xonsh kept sessions in the data dir itself
+        let history_dir = data_dir.join("history_json");
+
+        if history_dir.is_dir() {
+            Ok(history_dir)
+        } else if data_dir.is_dir() {
+            Ok(data_dir)
+        } else {
+            Err(eyre!(
+                "Could not find xonsh history directory {history_dir:?}. Try setting $XONSH_DATA_DIR"
+            ))
+        }
+    }
+
+    fn history(&self) -> Vec<History> {
+        let mut history = Vec::new();
+
+        for bytes in &self.sessions {
+            // sessions that are being written, or aren't history at all, are skipped
+            let Ok(file) = serde_json::from_slice::<XonshFile>(bytes) else {
+                continue;
+            };
+            let session = file.data;
+
+            let session_id = session.sessionid.replace('-', "");
+            let cwd = match session.env.get("PWD") {
+                Some(serde_json::Value::String(cwd)) => cwd.clone(),
+                _ => String::from("unknown"),
+            };
+
+            for cmd in session.cmds {
+                let command = cmd.inp.trim_end();
+                let Ok(start) = unix_timestamp(cmd.ts.0) else {
+                    continue;
+                };
+
+                if command.is_empty() {
+                    continue;
+                }
+
+                #[allow(clippy::cast_possible_truncation)]
+                let duration = match cmd.ts.1 {
+                    Some(end) => ((end - cmd.ts.0) * 1_000_000_000_f64).round() as i64,
+                    None => -1,
+                };
+
+                let imported = History::import()
+                    .timestamp(start)
+                    .command(command)
+                    .cwd(cwd.clone())
+                    .exit(cmd.rtn.unwrap_or(-1))
+                    .duration(duration)
+                    .session(session_id.clone());
+
+                history.push(imported.build().into());
+            }
+        }
+
+        history.sort_by_key(|h: &History| h.timestamp);
+        history
+    }
+}
+
+#[async_trait]
+impl Importer for Xonsh {
+    const NAME: &'static str = "xonsh";
+
+    async fn new() -> Result<Self> {
+        let mut sessions = Vec::new();
+
+        for entry in fs::read_dir(Self::histpath()?)? {
+            let path = entry?.path();
+
+            if path.extension() == Some(OsStr::new("json")) {
+                sessions.push(fs::read(path)?);
+            }
+        }
+
+        Ok(Self { sessions })
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(self.history().len())
+    }
+
+    async fn load(self, h: &mut impl Loader) -> Result<()> {
+        for history in self.history() {
+            h.push(history).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::assert_equal;
+
+    use crate::import::{tests::TestLoader, Importer};
+
+    use super::Xonsh;
+
+    #[tokio::test]
+    async fn parse_sessions() {
+        let first = br#"{"locked": false, "ts": [1700000000.0, null], "data": {
+            "sessionid": "8b8c7ad6-2c40-4c9b-a07b-6f64d1a7a0c2",
+            "env": {"PWD": "/home/ellie", "SHELL": "xonsh"},
+            "cmds": [
+                {"inp": "cd src\n", "rtn": 0, "ts": [1700000010.5, 1700000010.75]},
+                {"inp": "for x in range(3):\n    print(x)\n", "rtn": 1, "ts": [1700000020.0, 1700000021.0]},
+                {"inp": "sleep 100\n", "ts": [1700000030.0, null]}
+            ],
+            "ts": [1700000000.0, 1700000040.0]
+        }}"#;
+        let second = br#"{"locked": false, "data": {
+            "sessionid": "0c4c0fb4-6c1b-4aa1-8f0c-90d6b3e0f6a1",
+            "cmds": [{"inp": "ls\n", "rtn": 0, "ts": [1600000000.0, 1600000000.0]}]
+        }}"#;
+
+        let mut xonsh = Xonsh {
+            sessions: vec![
+                first.to_vec(),
+                b"{\"locked\": true".to_vec(),
+                second.to_vec(),
+            ],
+        };
+        assert_eq!(xonsh.entries().await.unwrap(), 4);
+
+        let mut loader = TestLoader::default();
+        xonsh.load(&mut loader).await.unwrap();
+
+        assert_equal(
+            loader.buf.iter().map(|h| h.command.as_str()),
+            [
+                "ls",
+                "cd src",
+                "for x in range(3):\n    print(x)",
+                "sleep 100",
+            ],
+        );
+
+        let cd = &loader.buf[1];
+        assert_eq!(cd.cwd, "/home/ellie");
+        assert_eq!(cd.session, "8b8c7ad62c404c9ba07b6f64d1a7a0c2");
+        assert_eq!(cd.duration, 250_000_000);
+        assert_eq!(cd.exit, 0);
+        assert_eq!(cd.timestamp.unix_timestamp(), 1_700_000_010);
+
+        assert_eq!(loader.buf[0].cwd, "unknown");
+        assert_eq!(loader.buf[3].exit, -1);
+        assert_eq!(loader.buf[3].duration, -1);
+    }
+}
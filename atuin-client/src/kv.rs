@@ -2,16 +2,16 @@ use std::collections::BTreeMap;
 
 use atuin_common::record::{DecryptedData, Host, HostId};
 use eyre::{bail, ensure, eyre, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::record::encryption::PASETO_V4;
 use crate::record::store::Store;
 
-const KV_VERSION: &str = "v0";
-const KV_TAG: &str = "kv";
+pub(crate) const KV_VERSION: &str = "v0";
+pub(crate) const KV_TAG: &str = "kv";
 const KV_VAL_MAX_LEN: usize = 100 * 1024;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct KvRecord {
     pub namespace: String,
     pub key: String,
@@ -10,6 +10,7 @@ pub mod sync;
 
 pub mod database;
 pub mod encryption;
+pub mod export;
 pub mod history;
 pub mod import;
 pub mod kv;
@@ -1,5 +1,7 @@
 pub mod encryption;
+pub mod rotate;
 pub mod sqlite_store;
 pub mod store;
 #[cfg(feature = "sync")]
 pub mod sync;
+pub mod verify;
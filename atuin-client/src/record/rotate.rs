@@ -0,0 +1,134 @@
+// Rotate the key that records are encrypted with.
+// Every record is encrypted with its own random content encryption key (CEK), and only the CEK is
+// encrypted with the user's key. So rotating only needs to re-wrap each CEK with the new key, and
+// the data itself stays exactly as it is.
+
+use eyre::{ensure, Result};
+
+use atuin_common::record::{EncryptedData, Record};
+
+use super::{encryption::PASETO_V4, store::Store};
+
+const ROTATE_PAGE_SIZE: u64 = 1000;
+
+/// Re-wrap every record in the store with `new_key`, in one transaction. Records that are already
+/// wrapped with `new_key` are left alone, so an interrupted rotation can be run again.
+///
+/// Returns every record in the store, as it now is, ready to replace the copies on the server.
+pub async fn rotate(
+    store: &impl Store,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+) -> Result<Vec<Record<EncryptedData>>> {
+    let records = all(store).await?;
+
+    let mut rotated = Vec::with_capacity(records.len());
+    let mut failed = 0;
+
+    for record in records {
+        match record.clone().re_encrypt::<PASETO_V4>(old_key, new_key) {
+            Ok(record) => rotated.push(record),
+            Err(_) if record.clone().decrypt::<PASETO_V4>(new_key).is_ok() => rotated.push(record),
+            Err(_) => failed += 1,
+        }
+    }
+
+    ensure!(
+        failed == 0,
+        "{failed} records can't be decrypted with the current key. Run `atuin record verify --repair` first"
+    );
+
+    store.replace_batch(rotated.iter()).await?;
+
+    Ok(rotated)
+}
+
+async fn all(store: &impl Store) -> Result<Vec<Record<EncryptedData>>> {
+    let status = store.status().await?;
+    let mut records = Vec::new();
+
+    for (host, tags) in status.hosts {
+        for tag in tags.keys() {
+            let mut start = 0;
+
+            loop {
+                let page = store.next(host, tag, start, ROTATE_PAGE_SIZE).await?;
+
+                let Some(last) = page.last() else {
+                    break;
+                };
+                start = last.idx + 1;
+
+                let done = (page.len() as u64) < ROTATE_PAGE_SIZE;
+                records.extend(page);
+
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::record::{DecryptedData, Host, HostId, Record};
+    use atuin_common::utils::uuid_v7;
+
+    use super::rotate;
+    use crate::record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store};
+
+    const OLD: [u8; 32] = [1; 32];
+    const NEW: [u8; 32] = [2; 32];
+
+    async fn store(key: &[u8; 32]) -> SqliteStore {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let host = Host::new(HostId(uuid_v7()));
+
+        for idx in 0..5 {
+            let record = Record::builder()
+                .host(host.clone())
+                .version("v0".to_string())
+                .tag("test".to_string())
+                .idx(idx)
+                .data(DecryptedData(vec![idx as u8]))
+                .build()
+                .encrypt::<PASETO_V4>(key);
+
+            store.push(&record).await.unwrap();
+        }
+
+        store
+    }
+
+    #[tokio::test]
+    async fn rotate_store() {
+        let store = store(&OLD).await;
+
+        let rotated = rotate(&store, &OLD, &NEW).await.unwrap();
+        assert_eq!(rotated.len(), 5);
+
+        for record in rotated {
+            let stored = store.get(record.id).await.unwrap();
+            assert_eq!(stored, record);
+
+            // the data is the same, only the key has changed
+            let decrypted = stored.clone().decrypt::<PASETO_V4>(&NEW).unwrap();
+            assert_eq!(decrypted.data.0, vec![decrypted.idx as u8]);
+            assert!(stored.decrypt::<PASETO_V4>(&OLD).is_err());
+        }
+
+        // running it again does nothing
+        let again = rotate(&store, &OLD, &NEW).await.unwrap();
+        assert_eq!(again.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn rotate_wrong_key() {
+        let store = store(&[3; 32]).await;
+
+        assert!(rotate(&store, &OLD, &NEW).await.is_err());
+    }
+}
@@ -17,6 +17,7 @@ use sqlx::{
 use atuin_common::record::{
     EncryptedData, Host, HostId, Record, RecordId, RecordIdx, RecordStatus,
 };
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::store::Store;
@@ -122,6 +123,33 @@ impl Store for SqliteStore {
         Ok(())
     }
 
+    async fn replace_batch(
+        &self,
+        records: impl Iterator<Item = &Record<EncryptedData>> + Send + Sync,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for record in records {
+            let res = sqlx::query("update store set data = ?1, cek = ?2 where id = ?3")
+                .bind(record.data.data.as_str())
+                .bind(record.data.content_encryption_key.as_str())
+                .bind(record.id.0.as_hyphenated().to_string())
+                .execute(&mut *tx)
+                .await?;
+
+            if res.rows_affected() == 0 {
+                return Err(eyre!(
+                    "record {} is not in the store",
+                    record.id.0.as_hyphenated()
+                ));
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     async fn get(&self, id: RecordId) -> Result<Record<EncryptedData>> {
         let res = sqlx::query("select * from store where store.id = ?1")
             .bind(id.0.as_hyphenated().to_string())
@@ -170,7 +198,9 @@ impl Store for SqliteStore {
         limit: u64,
     ) -> Result<Vec<Record<EncryptedData>>> {
         let res =
-            sqlx::query("select * from store where idx >= ?1 and host = ?2 and tag = ?3 limit ?4")
+            sqlx::query(
+                "select * from store where idx >= ?1 and host = ?2 and tag = ?3 order by idx asc limit ?4",
+            )
                 .bind(idx as i64)
                 .bind(host.0.as_hyphenated().to_string())
                 .bind(tag)
@@ -227,6 +257,34 @@ impl Store for SqliteStore {
         Ok(status)
     }
 
+    async fn quarantine(&self, id: RecordId, reason: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let moved = sqlx::query(
+            "insert into quarantine(id, idx, host, tag, timestamp, version, data, cek, reason, quarantined_at)
+                select id, idx, host, tag, timestamp, version, data, cek, ?2, ?3 from store where id = ?1",
+        )
+        .bind(id.0.as_hyphenated().to_string())
+        .bind(reason)
+        .bind(OffsetDateTime::now_utc().unix_timestamp_nanos() as i64)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if moved == 0 {
+            return Err(eyre!("record {} is not in the store", id.0.as_hyphenated()));
+        }
+
+        sqlx::query("delete from store where id = ?1")
+            .bind(id.0.as_hyphenated().to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     async fn all_tagged(&self, tag: &str) -> Result<Vec<Record<EncryptedData>>> {
         let res = sqlx::query("select * from store where tag = ?1 order by timestamp asc")
             .bind(tag)
@@ -400,4 +458,20 @@ mod tests {
             "failed to insert 10k records"
         );
     }
+
+    #[tokio::test]
+    async fn quarantine() {
+        let db = SqliteStore::new(":memory:").await.unwrap();
+        let record = test_record();
+        db.push(&record).await.unwrap();
+
+        db.quarantine(record.id, "testing").await.unwrap();
+
+        assert!(db.get(record.id).await.is_err());
+        assert_eq!(
+            db.len(record.host.id, record.tag.as_str()).await.unwrap(),
+            0
+        );
+        assert!(db.quarantine(record.id, "testing").await.is_err());
+    }
 }
@@ -19,6 +19,12 @@ pub trait Store {
         records: impl Iterator<Item = &Record<EncryptedData>> + Send + Sync,
     ) -> Result<()>;
 
+    /// Replace the data of records already in the store, all in one transaction
+    async fn replace_batch(
+        &self,
+        records: impl Iterator<Item = &Record<EncryptedData>> + Send + Sync,
+    ) -> Result<()>;
+
     async fn get(&self, id: RecordId) -> Result<Record<EncryptedData>>;
     async fn len(&self, host: HostId, tag: &str) -> Result<u64>;
 
@@ -44,6 +50,10 @@ pub trait Store {
 
     async fn status(&self) -> Result<RecordStatus>;
 
+    /// Move a record out of the store and into quarantine, so that it no longer takes part in
+    /// sync. The record is kept, along with the reason, in case it is needed later
+    async fn quarantine(&self, id: RecordId, reason: &str) -> Result<()>;
+
     /// Get every start record for a given tag, regardless of host.
     /// Useful when actually operating on synchronized data, and will often have conflict
     /// resolution applied.
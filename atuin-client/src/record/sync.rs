@@ -4,10 +4,24 @@ use std::cmp::Ordering;
 use eyre::Result;
 use thiserror::Error;
 
-use super::store::Store;
-use crate::{api_client::Client, settings::Settings};
+use super::{encryption::PASETO_V4, store::Store};
+use crate::{
+    api_client::{Client, QuotaExceeded},
+    settings::{self, Settings},
+};
 
-use atuin_common::record::{Diff, HostId, RecordIdx, RecordStatus};
+use atuin_common::{
+    api::RecordRange,
+    record::{Diff, HostId, RecordIdx, RecordStatus},
+    stream,
+};
+
+/// Most records to ask for in one streamed download, so that each request finishes well within the
+/// network timeout
+const STREAM_REQUEST_RECORDS: u64 = 20_000;
+
+/// Once a streamed upload is this big, send it and start another
+const STREAM_REQUEST_BYTES: usize = 16 * 1024 * 1024;
 
 #[derive(Error, Debug)]
 pub enum SyncError {
@@ -22,6 +36,9 @@ pub enum SyncError {
 
     #[error("a request to the sync server failed")]
     RemoteRequestError,
+
+    #[error("{msg}")]
+    QuotaExceeded { msg: String },
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -138,6 +155,221 @@ pub async fn operations(
     Ok(operations)
 }
 
+fn remote_error(e: eyre::Report) -> SyncError {
+    error!("request to the sync server failed: {e:?}");
+
+    match e.downcast::<QuotaExceeded>() {
+        Ok(QuotaExceeded(msg)) => SyncError::QuotaExceeded { msg },
+        Err(_) => SyncError::RemoteRequestError,
+    }
+}
+
+/// Group ranges into requests of at most `max` records, splitting ranges between requests where
+/// they need to be
+fn split_ranges(ranges: Vec<RecordRange>, max: u64) -> Vec<Vec<RecordRange>> {
+    let mut requests = Vec::new();
+    let mut request = Vec::new();
+    let mut size = 0;
+
+    for range in ranges {
+        let mut start = range.start;
+
+        while start < range.end {
+            let take = (range.end - start).min(max - size);
+
+            request.push(RecordRange {
+                host: range.host,
+                tag: range.tag.clone(),
+                start,
+                end: start + take,
+            });
+
+            start += take;
+            size += take;
+
+            if size == max {
+                requests.push(std::mem::take(&mut request));
+                size = 0;
+            }
+        }
+    }
+
+    if !request.is_empty() {
+        requests.push(request);
+    }
+
+    requests
+}
+
+/// Sync with the streaming endpoints, which move the records for many (host, tag)s in each
+/// request. Returns None if the server is too old to have them
+async fn sync_stream(
+    operations: &[Operation],
+    store: &impl Store,
+    client: &Client<'_>,
+) -> Result<Option<(i64, i64)>, SyncError> {
+    let mut uploads = Vec::new();
+    let mut downloads = Vec::new();
+
+    for op in operations {
+        match op {
+            Operation::Upload {
+                local,
+                remote,
+                host,
+                tag,
+            } => uploads.push(RecordRange {
+                host: *host,
+                tag: tag.clone(),
+                start: remote.map_or(0, |remote| remote + 1),
+                end: local + 1,
+            }),
+
+            Operation::Download {
+                local,
+                remote,
+                host,
+                tag,
+            } => downloads.push(RecordRange {
+                host: *host,
+                tag: tag.clone(),
+                start: local.map_or(0, |local| local + 1),
+                end: remote + 1,
+            }),
+
+            Operation::Noop { .. } => continue,
+        }
+    }
+
+    if uploads.is_empty() && downloads.is_empty() {
+        return Ok(Some((0, 0)));
+    }
+
+    // Download first. Asking for records doesn't need us to read any, so it's a cheap way to find
+    // out whether the server can stream
+    let Some(downloaded) = stream_download(store, client, downloads).await? else {
+        return Ok(None);
+    };
+
+    let uploaded = stream_upload(store, client, uploads).await?;
+
+    Ok(Some((uploaded, downloaded)))
+}
+
+async fn stream_download(
+    store: &impl Store,
+    client: &Client<'_>,
+    ranges: Vec<RecordRange>,
+) -> Result<Option<i64>, SyncError> {
+    let expected: u64 = ranges.iter().map(|r| r.end - r.start).sum();
+    let requests = split_ranges(ranges, STREAM_REQUEST_RECORDS);
+
+    if requests.is_empty() {
+        let supported = client
+            .stream_download(&[])
+            .await
+            .map_err(remote_error)?
+            .is_some();
+
+        return Ok(supported.then_some(0));
+    }
+
+    println!("Downloading {expected} records");
+
+    let mut downloaded = 0;
+
+    for request in requests {
+        let Some(mut records) = client
+            .stream_download(&request)
+            .await
+            .map_err(remote_error)?
+        else {
+            return Ok(None);
+        };
+
+        while let Some(batch) = records.next_batch().await.map_err(remote_error)? {
+            store
+                .push_batch(batch.iter())
+                .await
+                .map_err(|_| SyncError::LocalStoreError)?;
+
+            downloaded += batch.len() as u64;
+        }
+
+        println!("downloaded {downloaded}/{expected} records from remote");
+    }
+
+    Ok(Some(downloaded as i64))
+}
+
+async fn stream_upload(
+    store: &impl Store,
+    client: &Client<'_>,
+    ranges: Vec<RecordRange>,
+) -> Result<i64, SyncError> {
+    if ranges.is_empty() {
+        return Ok(0);
+    }
+
+    let expected: u64 = ranges.iter().map(|r| r.end - r.start).sum();
+    println!("Uploading {expected} records");
+
+    let mut body = Vec::new();
+    let mut uploaded = 0;
+
+    for range in ranges {
+        let mut start = range.start;
+
+        while start < range.end {
+            let page = store
+                .next(
+                    range.host,
+                    &range.tag,
+                    start,
+                    (range.end - start).min(stream::BATCH_RECORDS as u64),
+                )
+                .await
+                .map_err(|e| {
+                    error!("failed to read upload page: {e:?}");
+
+                    SyncError::LocalStoreError
+                })?;
+
+            let Some(last) = page.last() else {
+                break;
+            };
+            start = last.idx + 1;
+
+            let page: Vec<_> = page.into_iter().filter(|r| r.idx < range.end).collect();
+
+            stream::encode_batches(&page, &mut body)
+                .map_err(|e| SyncError::SyncLogicError { msg: e.to_string() })?;
+
+            if body.len() >= STREAM_REQUEST_BYTES {
+                uploaded += send_stream(client, std::mem::take(&mut body)).await?;
+                println!("uploaded {uploaded}/{expected} records to remote");
+            }
+        }
+    }
+
+    if !body.is_empty() {
+        uploaded += send_stream(client, body).await?;
+        println!("uploaded {uploaded}/{expected} records to remote");
+    }
+
+    Ok(uploaded as i64)
+}
+
+async fn send_stream(client: &Client<'_>, body: Vec<u8>) -> Result<u64, SyncError> {
+    client
+        .stream_upload(body)
+        .await
+        .map_err(remote_error)?
+        .ok_or_else(|| SyncError::SyncLogicError {
+            msg: String::from("the server accepted a record stream, then refused one"),
+        })
+}
+
 async fn sync_upload(
     store: &impl Store,
     client: &Client<'_>,
@@ -169,11 +401,7 @@ async fn sync_upload(
                 SyncError::LocalStoreError
             })?;
 
-        client.post_records(&page).await.map_err(|e| {
-            error!("failed to post records: {e:?}");
-
-            SyncError::RemoteRequestError
-        })?;
+        client.post_records(&page).await.map_err(remote_error)?;
 
         println!(
             "uploaded {} to remote, progress {}/{}",
@@ -240,11 +468,82 @@ async fn sync_download(
     Ok(progress as i64)
 }
 
+/// Download the records from `start` up to, but not including, `end` for one (host, tag), to fill
+/// a gap in the local store. Records that can't be decrypted with `key` are not stored, so that
+/// we don't replace one bad record with another.
+pub async fn download_range(
+    settings: &Settings,
+    store: &impl Store,
+    key: &[u8; 32],
+    host: HostId,
+    tag: &str,
+    start: RecordIdx,
+    end: RecordIdx,
+) -> Result<u64, SyncError> {
+    let client = Client::new(
+        &settings.sync_address,
+        &settings.session_token,
+        settings.network_connect_timeout,
+        settings.network_timeout,
+    )
+    .map_err(|_| SyncError::RemoteRequestError)?;
+
+    let download_page_size = 100;
+    let mut next = start;
+    let mut downloaded = 0;
+
+    while next < end {
+        let page = client
+            .next_records(
+                host,
+                tag.to_string(),
+                next,
+                download_page_size.min(end - next),
+            )
+            .await
+            .map_err(|_| SyncError::RemoteRequestError)?;
+
+        let Some(last) = page.iter().map(|r| r.idx).max() else {
+            break;
+        };
+        next = last + 1;
+
+        let page = page
+            .into_iter()
+            .filter(|r| r.idx < end && r.clone().decrypt::<PASETO_V4>(key).is_ok())
+            .collect::<Vec<_>>();
+
+        store
+            .push_batch(page.iter())
+            .await
+            .map_err(|_| SyncError::LocalStoreError)?;
+
+        downloaded += page.len() as u64;
+    }
+
+    Ok(downloaded)
+}
+
+/// Drop the operations that the sync policy doesn't allow: tags that are turned off, and all
+/// uploads in receive-only mode
+pub fn apply_policy(operations: Vec<Operation>, policy: &settings::Sync) -> Vec<Operation> {
+    operations
+        .into_iter()
+        .filter(|op| match op {
+            Operation::Upload { tag, .. } => policy.uploads(tag),
+            Operation::Download { tag, .. } => policy.downloads(tag),
+            Operation::Noop { .. } => true,
+        })
+        .collect()
+}
+
 pub async fn sync_remote(
     operations: Vec<Operation>,
     local_store: &impl Store,
     settings: &Settings,
 ) -> Result<(i64, i64), SyncError> {
+    let operations = apply_policy(operations, &settings.sync);
+
     let client = Client::new(
         &settings.sync_address,
         &settings.session_token,
@@ -253,6 +552,11 @@ pub async fn sync_remote(
     )
     .expect("failed to create client");
 
+    if let Some(synced) = sync_stream(&operations, local_store, &client).await? {
+        return Ok(synced);
+    }
+
+    // the server is too old to stream records, so fall back to moving a page at a time
     let mut uploaded = 0;
     let mut downloaded = 0;
 
@@ -284,15 +588,19 @@ pub async fn sync_remote(
 
 #[cfg(test)]
 mod tests {
-    use atuin_common::record::{Diff, EncryptedData, HostId, Record};
+    use atuin_common::{
+        api::RecordRange,
+        record::{Diff, EncryptedData, HostId, Record},
+    };
     use pretty_assertions::assert_eq;
 
     use crate::record::{
         encryption::PASETO_V4,
         sqlite_store::SqliteStore,
         store::Store,
-        sync::{self, Operation},
+        sync::{self, split_ranges, Operation},
     };
+    use crate::settings::Sync;
 
     fn test_record() -> Record<EncryptedData> {
         Record::builder()
@@ -363,6 +671,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sync_policy() {
+        let host = HostId(atuin_common::utils::uuid_v7());
+        let upload = |tag: &str| Operation::Upload {
+            host,
+            tag: tag.to_string(),
+            local: 1,
+            remote: None,
+        };
+        let download = |tag: &str| Operation::Download {
+            host,
+            tag: tag.to_string(),
+            local: None,
+            remote: 1,
+        };
+
+        let mut policy = Sync::default();
+        policy.tags.insert("kv".to_string(), false);
+
+        assert_eq!(
+            sync::apply_policy(
+                vec![upload("history"), upload("kv"), download("kv")],
+                &policy
+            ),
+            vec![upload("history")]
+        );
+
+        policy.receive_only = true;
+
+        assert_eq!(
+            sync::apply_policy(
+                vec![upload("history"), download("history"), download("kv")],
+                &policy
+            ),
+            vec![download("history")]
+        );
+    }
+
     #[tokio::test]
     async fn build_two_way_diff() {
         // a diff where local is ahead of remote for one, and remote for
@@ -569,4 +915,28 @@ mod tests {
 
         assert_eq!(result_ops, operations);
     }
+
+    #[test]
+    fn split_stream_requests() {
+        let host = HostId(atuin_common::utils::uuid_v7());
+        let range = |tag: &str, start, end| RecordRange {
+            host,
+            tag: tag.to_string(),
+            start,
+            end,
+        };
+
+        let requests = split_ranges(vec![range("a", 0, 25), range("b", 5, 10)], 10);
+
+        assert_eq!(
+            requests,
+            vec![
+                vec![range("a", 0, 10)],
+                vec![range("a", 10, 20)],
+                vec![range("a", 20, 25), range("b", 5, 10)],
+            ]
+        );
+
+        assert!(split_ranges(vec![], 10).is_empty());
+    }
 }
@@ -0,0 +1,410 @@
+// Check that the record store is in a state that sync can work with.
+// Sync assumes that every (host, tag) is a contiguous chain of records, starting at idx 0. If that
+// isn't true, it can fail in ways that are hard to understand. This walks every chain and reports
+// anything that looks wrong.
+
+use std::fmt::{self, Display, Formatter};
+
+use eyre::Result;
+use time::{Duration, OffsetDateTime};
+
+use atuin_common::record::{EncryptedData, HostId, Record, RecordId, RecordIdx, RecordStatus};
+
+use super::{encryption::PASETO_V4, store::Store};
+use crate::{
+    history::{HISTORY_TAG, HISTORY_VERSION, HISTORY_VERSION_V0},
+    kv::{KV_TAG, KV_VERSION},
+};
+
+const VERIFY_PAGE_SIZE: u64 = 1000;
+
+/// How far in the future a record can be before we think its timestamp is wrong. Clocks drift.
+const MAX_CLOCK_SKEW: Duration = Duration::days(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub host: HostId,
+    pub tag: String,
+    pub kind: ProblemKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The records from `start` up to, but not including, `end` are missing
+    Gap { start: RecordIdx, end: RecordIdx },
+
+    /// Another record already has this idx
+    Duplicate { id: RecordId, idx: RecordIdx },
+
+    /// The record can't be decrypted with our key. Usually it was written with a different key
+    Undecryptable { id: RecordId, idx: RecordIdx },
+
+    /// We don't know how to read this version. It may have been written by a newer client
+    UnknownVersion {
+        id: RecordId,
+        idx: RecordIdx,
+        version: String,
+    },
+
+    /// The record was created in the future, according to our clock
+    FutureTimestamp {
+        id: RecordId,
+        idx: RecordIdx,
+        timestamp: OffsetDateTime,
+    },
+
+    /// The record was created before the one before it
+    OutOfOrder {
+        id: RecordId,
+        idx: RecordIdx,
+        timestamp: OffsetDateTime,
+        previous: OffsetDateTime,
+    },
+}
+
+impl Problem {
+    /// The record that has this problem, if it's something we can quarantine.
+    ///
+    /// Unknown versions and odd timestamps are left alone: the record may well be fine, and
+    /// removing it would only cause a gap.
+    pub fn quarantine(&self) -> Option<RecordId> {
+        match self.kind {
+            ProblemKind::Duplicate { id, .. } | ProblemKind::Undecryptable { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}: ", self.host.0.as_simple(), self.tag)?;
+
+        match &self.kind {
+            ProblemKind::Gap { start, end } if *end == start + 1 => {
+                write!(f, "record {start} is missing")
+            }
+            ProblemKind::Gap { start, end } => {
+                write!(f, "records {start} to {} are missing", end - 1)
+            }
+            ProblemKind::Duplicate { id, idx } => {
+                write!(f, "record {idx} ({}) is a duplicate", id.0.as_simple())
+            }
+            ProblemKind::Undecryptable { id, idx } => write!(
+                f,
+                "record {idx} ({}) can't be decrypted with this key",
+                id.0.as_simple()
+            ),
+            ProblemKind::UnknownVersion { id, idx, version } => write!(
+                f,
+                "record {idx} ({}) has unknown version {version:?}",
+                id.0.as_simple()
+            ),
+            ProblemKind::FutureTimestamp { id, idx, timestamp } => write!(
+                f,
+                "record {idx} ({}) was created in the future, at {timestamp}",
+                id.0.as_simple()
+            ),
+            ProblemKind::OutOfOrder {
+                id,
+                idx,
+                timestamp,
+                previous,
+            } => write!(
+                f,
+                "record {idx} ({}) was created at {timestamp}, before the previous record at {previous}",
+                id.0.as_simple()
+            ),
+        }
+    }
+}
+
+/// Whether we know how to read `version` for `tag`. None for tags we don't know about at all
+fn known_version(tag: &str, version: &str) -> Option<bool> {
+    match tag {
+        HISTORY_TAG => Some(matches!(version, HISTORY_VERSION_V0 | HISTORY_VERSION)),
+        KV_TAG => Some(version == KV_VERSION),
+        _ => None,
+    }
+}
+
+/// Walk every (host, tag) chain in the store, and report anything that would trip up sync
+pub async fn verify(store: &impl Store, key: &[u8; 32]) -> Result<Vec<Problem>> {
+    let now = OffsetDateTime::now_utc();
+    let status = store.status().await?;
+
+    let mut hosts = status.hosts.into_iter().collect::<Vec<_>>();
+    hosts.sort_by_key(|(host, _)| *host);
+
+    let mut problems = Vec::new();
+
+    for (host, tags) in hosts {
+        let mut tags = tags.into_keys().collect::<Vec<_>>();
+        tags.sort();
+
+        for tag in tags {
+            verify_chain(store, key, host, &tag, now, &mut problems).await?;
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Every gap in the store, including any at the end of a chain that is now shorter than it was
+/// in `before`. Quarantining the last records in a chain leaves a gap that `verify` can't see.
+pub async fn gaps(
+    store: &impl Store,
+    key: &[u8; 32],
+    before: &RecordStatus,
+) -> Result<Vec<Problem>> {
+    let mut gaps = verify(store, key)
+        .await?
+        .into_iter()
+        .filter(|p| matches!(p.kind, ProblemKind::Gap { .. }))
+        .collect::<Vec<_>>();
+
+    let mut tails = before
+        .hosts
+        .iter()
+        .flat_map(|(host, tags)| tags.iter().map(|(tag, idx)| (*host, tag.clone(), *idx)))
+        .collect::<Vec<_>>();
+    tails.sort();
+
+    for (host, tag, tail) in tails {
+        let start = store.len(host, &tag).await?;
+
+        if start <= tail {
+            gaps.push(Problem {
+                host,
+                tag,
+                kind: ProblemKind::Gap {
+                    start,
+                    end: tail + 1,
+                },
+            });
+        }
+    }
+
+    Ok(gaps)
+}
+
+async fn verify_chain(
+    store: &impl Store,
+    key: &[u8; 32],
+    host: HostId,
+    tag: &str,
+    now: OffsetDateTime,
+    problems: &mut Vec<Problem>,
+) -> Result<()> {
+    let mut problem = |kind| {
+        problems.push(Problem {
+            host,
+            tag: tag.to_string(),
+            kind,
+        });
+    };
+
+    // The idx and timestamp of the last good record we saw
+    let mut previous: Option<(RecordIdx, OffsetDateTime)> = None;
+    let mut start = 0;
+
+    loop {
+        let page = store.next(host, tag, start, VERIFY_PAGE_SIZE).await?;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+        start = last.idx + 1;
+
+        for record in &page {
+            let expected = previous.map_or(0, |(idx, _)| idx + 1);
+
+            if record.idx < expected {
+                problem(ProblemKind::Duplicate {
+                    id: record.id,
+                    idx: record.idx,
+                });
+                continue;
+            }
+
+            if record.idx > expected {
+                problem(ProblemKind::Gap {
+                    start: expected,
+                    end: record.idx,
+                });
+            }
+
+            for kind in verify_record(record, key, previous.map(|(_, t)| t), now) {
+                problem(kind);
+            }
+
+            let timestamp = timestamp(record);
+            previous = Some((record.idx, timestamp));
+        }
+
+        if (page.len() as u64) < VERIFY_PAGE_SIZE {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn verify_record(
+    record: &Record<EncryptedData>,
+    key: &[u8; 32],
+    previous: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> Vec<ProblemKind> {
+    let mut problems = Vec::new();
+    let (id, idx) = (record.id, record.idx);
+
+    if known_version(&record.tag, &record.version) == Some(false) {
+        problems.push(ProblemKind::UnknownVersion {
+            id,
+            idx,
+            version: record.version.clone(),
+        });
+    }
+
+    if record.clone().decrypt::<PASETO_V4>(key).is_err() {
+        problems.push(ProblemKind::Undecryptable { id, idx });
+    }
+
+    let timestamp = timestamp(record);
+
+    if timestamp > now + MAX_CLOCK_SKEW {
+        problems.push(ProblemKind::FutureTimestamp { id, idx, timestamp });
+    }
+
+    if let Some(previous) = previous.filter(|p| timestamp < *p) {
+        problems.push(ProblemKind::OutOfOrder {
+            id,
+            idx,
+            timestamp,
+            previous,
+        });
+    }
+
+    problems
+}
+
+fn timestamp(record: &Record<EncryptedData>) -> OffsetDateTime {
+    // timestamps are u64 nanoseconds, which always fit
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(record.timestamp))
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::record::{DecryptedData, Host, HostId, Record};
+    use atuin_common::utils::uuid_v7;
+    use time::{Duration, OffsetDateTime};
+
+    use super::{gaps, verify, Problem, ProblemKind};
+    use crate::{
+        history::{HISTORY_TAG, HISTORY_VERSION},
+        record::{encryption::PASETO_V4, sqlite_store::SqliteStore, store::Store},
+    };
+
+    const KEY: [u8; 32] = [1; 32];
+
+    fn record(host: HostId, idx: u64, timestamp: OffsetDateTime) -> Record<DecryptedData> {
+        Record::builder()
+            .host(Host::new(host))
+            .version(HISTORY_VERSION.to_string())
+            .tag(HISTORY_TAG.to_string())
+            .idx(idx)
+            .timestamp(timestamp.unix_timestamp_nanos() as u64)
+            .data(DecryptedData(vec![1, 2, 3]))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn healthy() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let host = HostId(uuid_v7());
+        let now = OffsetDateTime::now_utc();
+
+        for idx in 0..10 {
+            let r = record(host, idx, now + Duration::seconds(idx as i64));
+            store.push(&r.encrypt::<PASETO_V4>(&KEY)).await.unwrap();
+        }
+
+        assert_eq!(verify(&store, &KEY).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn problems() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let host = HostId(uuid_v7());
+        let now = OffsetDateTime::now_utc() - Duration::hours(1);
+
+        let mut records = (0..7)
+            .map(|idx| record(host, idx, now + Duration::seconds(idx as i64)))
+            .collect::<Vec<_>>();
+
+        records[3].version = "v99".to_string();
+        records[5].timestamp = records[4].timestamp - 1;
+        records[6].timestamp = (now + Duration::days(30)).unix_timestamp_nanos() as u64;
+
+        let ids = records.iter().map(|r| r.id).collect::<Vec<_>>();
+
+        for (idx, r) in records.into_iter().enumerate() {
+            // 1 and 2 are missing
+            if idx == 1 || idx == 2 {
+                continue;
+            }
+
+            let key = if idx == 4 { [2; 32] } else { KEY };
+            store.push(&r.encrypt::<PASETO_V4>(&key)).await.unwrap();
+        }
+
+        let problems = verify(&store, &KEY)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p: Problem| {
+                assert_eq!(p.host, host);
+                p.kind
+            })
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            problems.as_slice(),
+            [
+                ProblemKind::Gap { start: 1, end: 3 },
+                ProblemKind::UnknownVersion { idx: 3, .. },
+                ProblemKind::Undecryptable { idx: 4, .. },
+                ProblemKind::OutOfOrder { idx: 5, .. },
+                ProblemKind::FutureTimestamp { idx: 6, .. },
+            ]
+        ));
+
+        let problems = verify(&store, &KEY).await.unwrap();
+        let quarantine = problems
+            .iter()
+            .filter_map(Problem::quarantine)
+            .collect::<Vec<_>>();
+        assert_eq!(quarantine, vec![ids[4]]);
+
+        let before = store.status().await.unwrap();
+        store.quarantine(ids[4], "testing").await.unwrap();
+        store.quarantine(ids[6], "testing").await.unwrap();
+
+        let gaps = gaps(&store, &KEY, &before)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.kind)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            gaps,
+            vec![
+                ProblemKind::Gap { start: 1, end: 3 },
+                ProblemKind::Gap { start: 4, end: 5 },
+                ProblemKind::Gap { start: 6, end: 7 },
+            ]
+        );
+    }
+}
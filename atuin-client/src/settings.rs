@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     convert::TryFrom,
     io::prelude::*,
     path::{Path, PathBuf},
@@ -15,7 +16,7 @@ use fs_err::{create_dir_all, File};
 use parse_duration::parse;
 use regex::RegexSet;
 use semver::Version;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use uuid::Uuid;
 
@@ -40,6 +41,9 @@ pub enum SearchMode {
 
     #[serde(rename = "skim")]
     Skim,
+
+    #[serde(rename = "fts")]
+    Fts,
 }
 
 impl SearchMode {
@@ -49,6 +53,7 @@ impl SearchMode {
             SearchMode::FullText => "FULLTXT",
             SearchMode::Fuzzy => "FUZZY",
             SearchMode::Skim => "SKIM",
+            SearchMode::Fts => "FTS",
         }
     }
     pub fn next(&self, settings: &Settings) -> Self {
@@ -56,14 +61,16 @@ impl SearchMode {
             SearchMode::Prefix => SearchMode::FullText,
             // if the user is using skim, we go to skim
             SearchMode::FullText if settings.search_mode == SearchMode::Skim => SearchMode::Skim,
+            // same for the full text index
+            SearchMode::FullText if settings.search_mode == SearchMode::Fts => SearchMode::Fts,
             // otherwise fuzzy.
             SearchMode::FullText => SearchMode::Fuzzy,
-            SearchMode::Fuzzy | SearchMode::Skim => SearchMode::Prefix,
+            SearchMode::Fuzzy | SearchMode::Skim | SearchMode::Fts => SearchMode::Prefix,
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Copy, PartialEq, Eq, ValueEnum)]
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, ValueEnum)]
 pub enum FilterMode {
     #[serde(rename = "global")]
     Global = 0,
@@ -143,6 +150,40 @@ pub enum WordJumpMode {
     Subl,
 }
 
+#[derive(Clone, Debug, Deserialize, Copy, Default, PartialEq, Eq)]
+pub enum KeymapStyle {
+    #[default]
+    #[serde(rename = "emacs")]
+    Emacs,
+
+    #[serde(rename = "vim")]
+    Vim,
+}
+
+/// Key bindings for interactive search. Each map is from a key chord, such as `ctrl-r`, to the
+/// name of an action, and is applied on top of the defaults for the style.
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct Keys {
+    #[serde(default)]
+    pub style: KeymapStyle,
+
+    /// Bindings while typing a query. With the vim style, these are the insert mode bindings
+    #[serde(default)]
+    pub insert: HashMap<String, String>,
+
+    /// Bindings for vim normal mode
+    #[serde(default)]
+    pub normal: HashMap<String, String>,
+
+    /// Bindings while inspecting a command
+    #[serde(default)]
+    pub inspector: HashMap<String, String>,
+
+    /// Bindings while looking at the timeline of a session
+    #[serde(default)]
+    pub timeline: HashMap<String, String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Stats {
     #[serde(default = "Stats::common_prefix_default")]
@@ -176,6 +217,132 @@ impl Default for Stats {
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct Sync {
     pub records: bool,
+
+    /// Record tags to sync, or not. Tags that aren't listed are synced
+    #[serde(default)]
+    pub tags: HashMap<String, bool>,
+
+    /// History run in these directories is saved and searchable here, but never uploaded
+    #[serde(default)]
+    pub local_only_cwd: Globs,
+
+    /// History from these hosts is saved and searchable here, but never uploaded
+    #[serde(default)]
+    pub local_only_hosts: Vec<String>,
+
+    /// Download from the server, but never upload to it
+    #[serde(default)]
+    pub receive_only: bool,
+}
+
+impl Sync {
+    pub fn syncs_tag(&self, tag: &str) -> bool {
+        self.tags.get(tag).copied().unwrap_or(true)
+    }
+
+    /// Whether records with this tag may be uploaded
+    pub fn uploads(&self, tag: &str) -> bool {
+        !self.receive_only && self.syncs_tag(tag)
+    }
+
+    /// Whether records with this tag may be downloaded
+    pub fn downloads(&self, tag: &str) -> bool {
+        self.syncs_tag(tag)
+    }
+}
+
+/// A set of path globs. `*` and `?` match within a single directory, `**` matches any number of
+/// directories, and a leading `~` is the home directory. A glob that matches a directory also
+/// matches everything inside it.
+#[derive(Clone, Debug)]
+pub struct Globs(RegexSet);
+
+impl Globs {
+    pub fn new<I, S>(globs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let regexes = globs.into_iter().map(|g| glob_regex(g.as_ref()));
+
+        Ok(Self(RegexSet::new(regexes)?))
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.0.is_match(path)
+    }
+}
+
+impl Default for Globs {
+    fn default() -> Self {
+        Self(RegexSet::empty())
+    }
+}
+
+impl<'de> Deserialize<'de> for Globs {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let globs = Vec::<String>::deserialize(deserializer)?;
+
+        Self::new(globs).map_err(serde::de::Error::custom)
+    }
+}
+
+fn glob_regex(glob: &str) -> String {
+    let glob = shellexpand::tilde(glob);
+    let glob = glob.trim_end_matches('/');
+
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                // `a/**/b` matches `a/b` too
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push_str("(/.*)?$");
+    regex
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Daemon {
+    /// Where `atuin daemon` listens, and where `history start` and `end` look for it
+    #[serde(default = "Daemon::socket_path_default")]
+    pub socket_path: String,
+}
+
+impl Daemon {
+    fn socket_path_default() -> String {
+        // the runtime dir is local to the machine, unlike data dirs on network filesystems
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map_or_else(atuin_common::utils::data_dir, PathBuf::from);
+
+        dir.join("atuin.sock").to_string_lossy().to_string()
+    }
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            socket_path: Self::socket_path_default(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -200,6 +367,7 @@ pub struct Settings {
     pub show_preview: bool,
     pub max_preview_height: u16,
     pub show_help: bool,
+    pub theme: String,
     pub exit_mode: ExitMode,
     pub word_jump_mode: WordJumpMode,
     pub word_chars: String,
@@ -212,6 +380,7 @@ pub struct Settings {
     pub cwd_filter: RegexSet,
 
     pub secrets_filter: bool,
+    pub history_tag_env: Vec<String>,
     pub workspaces: bool,
     pub ctrl_n_shortcuts: bool,
 
@@ -225,6 +394,12 @@ pub struct Settings {
     #[serde(default)]
     pub sync: Sync,
 
+    #[serde(default)]
+    pub daemon: Daemon,
+
+    #[serde(default)]
+    pub keys: Keys,
+
     // This is automatically loaded when settings is created. Do not set in
     // config! Keep secrets and settings apart.
     #[serde(skip)]
@@ -314,8 +489,12 @@ impl Settings {
         Some(HostId(uuid))
     }
 
+    pub fn logged_in(&self) -> bool {
+        PathBuf::from(self.session_path.as_str()).exists()
+    }
+
     pub fn should_sync(&self) -> Result<bool> {
-        if !self.auto_sync || !PathBuf::from(self.session_path.as_str()).exists() {
+        if !self.auto_sync || !self.logged_in() {
             return Ok(false);
         }
 
@@ -328,6 +507,15 @@ impl Settings {
         }
     }
 
+    /// The tag to record with new history, taken from the first variable in `history_tag_env`
+    /// that is set and non-empty.
+    pub fn history_tag(&self) -> Option<String> {
+        self.history_tag_env
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|tag| !tag.is_empty())
+    }
+
     fn needs_update_check(&self) -> Result<bool> {
         let last_check = Settings::last_version_check()?;
         let diff = OffsetDateTime::now_utc() - last_check;
@@ -414,6 +602,7 @@ impl Settings {
             .set_default("show_preview", false)?
             .set_default("max_preview_height", 4)?
             .set_default("show_help", true)?
+            .set_default("theme", "default")?
             .set_default("invert", false)?
             .set_default("exit_mode", "return-original")?
             .set_default("word_jump_mode", "emacs")?
@@ -427,6 +616,7 @@ impl Settings {
             .set_default("workspaces", false)?
             .set_default("ctrl_n_shortcuts", false)?
             .set_default("secrets_filter", true)?
+            .set_default("history_tag_env", vec!["ATUIN_HISTORY_TAG"])?
             .set_default("network_connect_timeout", 5)?
             .set_default("network_timeout", 30)?
             // enter_accept defaults to false here, but true in the default config file. The dissonance is
@@ -443,6 +633,12 @@ impl Settings {
             ))
     }
 
+    /// The directory holding config.toml, and any themes
+    pub fn config_dir() -> PathBuf {
+        std::env::var("ATUIN_CONFIG_DIR")
+            .map_or_else(|_| atuin_common::utils::config_dir(), PathBuf::from)
+    }
+
     pub fn new() -> Result<Self> {
         let config_dir = atuin_common::utils::config_dir();
         let data_dir = atuin_common::utils::data_dir();
@@ -452,15 +648,7 @@ impl Settings {
 
         create_dir_all(&data_dir).wrap_err_with(|| format!("could not create dir {data_dir:?}"))?;
 
-        let mut config_file = if let Ok(p) = std::env::var("ATUIN_CONFIG_DIR") {
-            PathBuf::from(p)
-        } else {
-            let mut config_file = PathBuf::new();
-            config_file.push(config_dir);
-            config_file
-        };
-
-        config_file.push("config.toml");
+        let config_file = Self::config_dir().join("config.toml");
 
         let mut config_builder = Self::builder()?;
 
@@ -495,6 +683,9 @@ impl Settings {
         let session_path = shellexpand::full(&session_path)?;
         settings.session_path = session_path.to_string();
 
+        let socket_path = shellexpand::full(&settings.daemon.socket_path)?;
+        settings.daemon.socket_path = socket_path.to_string();
+
         // Finally, set the auth token
         if Path::new(session_path.to_string().as_str()).exists() {
             let token = fs_err::read_to_string(session_path.to_string())?;
@@ -525,3 +716,38 @@ impl Default for Settings {
             .expect("Could not deserialize config")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Globs;
+
+    #[test]
+    fn glob_matching() {
+        let globs =
+            Globs::new(["/work/clients", "/src/*/secret", "/srv/**/keys", "/tmp/a?c"]).unwrap();
+
+        assert!(globs.is_match("/work/clients"));
+        assert!(globs.is_match("/work/clients/acme/app"));
+        assert!(!globs.is_match("/work/clientsx"));
+        assert!(!globs.is_match("/home/work/clients"));
+
+        assert!(globs.is_match("/src/foo/secret"));
+        assert!(globs.is_match("/src/foo/secret/deep"));
+        assert!(!globs.is_match("/src/foo/bar/secret"));
+
+        assert!(globs.is_match("/srv/keys"));
+        assert!(globs.is_match("/srv/a/b/keys"));
+
+        assert!(globs.is_match("/tmp/abc"));
+        assert!(!globs.is_match("/tmp/a/c"));
+    }
+
+    #[test]
+    fn glob_home() {
+        let home = shellexpand::tilde("~").to_string();
+        let globs = Globs::new(["~/clients/"]).unwrap();
+
+        assert!(globs.is_match(&format!("{home}/clients/acme")));
+        assert!(!globs.is_match("~/clients/acme"));
+    }
+}
@@ -60,6 +60,7 @@ async fn sync_download(
     let mut last_timestamp = OffsetDateTime::UNIX_EPOCH;
 
     let host = if force { Some(String::from("")) } else { None };
+    let mut undecryptable = 0;
 
     while remote_count > local_count {
         let page = client
@@ -70,8 +71,17 @@ async fn sync_download(
             .history
             .iter()
             // TODO: handle deletion earlier in this chain
-            .map(|h| serde_json::from_str(h).expect("invalid base64"))
-            .map(|h| decrypt(h, key).expect("failed to decrypt history! check your key"))
+            // history encrypted with another key can't be read, so it's skipped rather than
+            // failing the whole sync
+            .filter_map(|h| {
+                let h = serde_json::from_str(h)
+                    .ok()
+                    .and_then(|h| decrypt(h, key).ok());
+                if h.is_none() {
+                    undecryptable += 1;
+                }
+                h
+            })
             .map(|mut h| {
                 if remote_deleted.contains(h.id.0.as_str()) {
                     h.deleted_at = Some(time::OffsetDateTime::now_utc());
@@ -86,14 +96,14 @@ async fn sync_download(
 
         local_count = db.history_count(true).await?;
 
-        if history.len() < remote_status.page_size.try_into().unwrap() {
+        if page.history.len() < remote_status.page_size.try_into().unwrap() {
             break;
         }
 
-        let page_last = history
-            .last()
-            .expect("could not get last element of page")
-            .timestamp;
+        // with nothing readable on this page, there's no timestamp to carry on from
+        let Some(page_last) = history.last().map(|h| h.timestamp) else {
+            break;
+        };
 
         // in the case of a small sync frequency, it's possible for history to
         // be "lost" between syncs. In this case we need to rewind the sync
@@ -106,6 +116,12 @@ async fn sync_download(
         }
     }
 
+    if undecryptable > 0 {
+        warn!(
+            "skipped {undecryptable} history entries that could not be decrypted. check your key"
+        );
+    }
+
     for i in remote_status.deleted {
         // we will update the stored history to have this data
         // pretty much everything can be nullified
@@ -124,6 +140,7 @@ async fn sync_download(
 
 // Check if we have things remote doesn't, and if so, upload them
 async fn sync_upload(
+    settings: &Settings,
     key: &Key,
     _force: bool,
     client: &api_client::Client<'_>,
@@ -144,15 +161,24 @@ async fn sync_upload(
     // first just try the most recent set
     let mut cursor = OffsetDateTime::now_utc();
 
-    while local_count > remote_count {
+    // history we've seen that stays on this machine, so remote will never have it
+    let mut local_only = 0;
+
+    while local_count > remote_count + local_only {
         let last = db.before(cursor, remote_status.page_size).await?;
         let mut buffer = Vec::new();
 
-        if last.is_empty() {
+        let Some(oldest) = last.last() else {
             break;
-        }
+        };
+        cursor = oldest.timestamp;
 
         for i in last {
+            if i.is_local_only(settings) {
+                local_only += 1;
+                continue;
+            }
+
             let data = encrypt(&i, key)?;
             let data = serde_json::to_string(&data)?;
 
@@ -167,9 +193,10 @@ async fn sync_upload(
         }
 
         // anything left over outside of the 100 block size
-        client.post_history(&buffer).await?;
-        cursor = buffer.last().unwrap().timestamp;
-        remote_count = client.count().await?;
+        if !buffer.is_empty() {
+            client.post_history(&buffer).await?;
+            remote_count = client.count().await?;
+        }
 
         debug!("upload cursor: {:?}", cursor);
     }
@@ -177,7 +204,7 @@ async fn sync_upload(
     let deleted = db.deleted().await?;
 
     for i in deleted {
-        if remote_deleted.contains(&i.id.to_string()) {
+        if remote_deleted.contains(&i.id.to_string()) || i.is_local_only(settings) {
             continue;
         }
 
@@ -198,7 +225,9 @@ pub async fn sync(settings: &Settings, force: bool, db: &(impl Database + Send))
 
     let key = load_key(settings)?; // encryption key
 
-    sync_upload(&key, force, &client, db).await?;
+    if !settings.sync.receive_only {
+        sync_upload(settings, &key, force, &client, db).await?;
+    }
 
     let download = sync_download(&key, force, &client, db).await?;
 
@@ -1,9 +1,11 @@
 use lazy_static::lazy_static;
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 use time::OffsetDateTime;
 
+use crate::record::{HostId, RecordIdx};
+
 // the usage of X- has been deprecated for quite along time, it turns out
 pub static ATUIN_HEADER_VERSION: &str = "Atuin-Version";
 pub static ATUIN_CARGO_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -23,6 +25,9 @@ pub struct RegisterRequest {
     pub email: String,
     pub username: String,
     pub password: String,
+    /// Names the session this creates, usually the hostname
+    #[serde(default)]
+    pub device: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,6 +42,9 @@ pub struct DeleteUserResponse {}
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// Names the session this creates, usually the hostname
+    #[serde(default)]
+    pub device: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +52,50 @@ pub struct LoginResponse {
     pub session: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordResponse {
+    /// Changing the password ends every existing session, so this replaces the session used to
+    /// change it
+    pub session: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeEmailRequest {
+    pub password: String,
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeEmailResponse {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: i64,
+    pub device: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    /// Whether this is the session that asked for the list
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListSessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeSessionResponse {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddHistoryRequest {
     pub id: String,
@@ -106,3 +158,60 @@ pub struct DeleteHistoryRequest {
 pub struct MessageResponse {
     pub message: String,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplaceRecordsResponse {
+    /// How many of the records sent were found and replaced
+    pub replaced: u64,
+}
+
+/// The records from `start` up to, but not including, `end` for one (host, tag)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordRange {
+    pub host: HostId,
+    pub tag: String,
+    pub start: RecordIdx,
+    pub end: RecordIdx,
+}
+
+/// Sent to listening clients when records are uploaded to a (host, tag)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordEvent {
+    pub host: HostId,
+    pub tag: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamDownloadRequest {
+    pub ranges: Vec<RecordRange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamUploadResponse {
+    /// How many records the server received
+    pub uploaded: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub used: u64,
+    /// None if there is no limit
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub count: QuotaUsage,
+    /// Bytes of encrypted data
+    pub bytes: QuotaUsage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageResponse {
+    /// All records together
+    pub records: StorageUsage,
+    /// Records by tag
+    pub tags: BTreeMap<String, StorageUsage>,
+    /// History uploaded without record sync
+    pub history: StorageUsage,
+}
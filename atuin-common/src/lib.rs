@@ -54,4 +54,5 @@ macro_rules! new_uuid {
 
 pub mod api;
 pub mod record;
+pub mod stream;
 pub mod utils;
@@ -0,0 +1,305 @@
+// A compact format for moving many records in one request.
+// A stream is a series of frames, each a batch of records: a big-endian u32 length, followed by
+// that many bytes of msgpack. The msgpack is an array of records, each an array of their fields,
+// with IDs as 16 byte binaries rather than strings. Frames can be decoded as they arrive, so
+// neither end has to hold the whole stream in memory.
+
+use eyre::{bail, eyre, Result};
+use rmp::decode::{self, Bytes};
+use rmp::encode;
+use uuid::Uuid;
+
+use crate::record::{EncryptedData, Host, HostId, Record, RecordId};
+
+pub const CONTENT_TYPE: &str = "application/vnd.atuin.record-stream";
+
+/// Frames hold at most this many records
+pub const BATCH_RECORDS: usize = 1000;
+
+/// Frames hold at most this many bytes, unless a single record is bigger
+pub const BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+// INFO: ensure this is updated when adding new fields
+const RECORD_FIELDS: u32 = 9;
+
+fn error_report<E: std::fmt::Debug>(err: E) -> eyre::Report {
+    eyre!("{err:?}")
+}
+
+fn encode_record(record: &Record<EncryptedData>, output: &mut Vec<u8>) -> Result<()> {
+    encode::write_array_len(output, RECORD_FIELDS)?;
+
+    encode::write_bin(output, record.id.0.as_bytes())?;
+    encode::write_u64(output, record.idx)?;
+    encode::write_bin(output, record.host.id.0.as_bytes())?;
+    encode::write_str(output, &record.host.name)?;
+    encode::write_u64(output, record.timestamp)?;
+    encode::write_str(output, &record.version)?;
+    encode::write_str(output, &record.tag)?;
+    encode::write_str(output, &record.data.data)?;
+    encode::write_str(output, &record.data.content_encryption_key)?;
+
+    Ok(())
+}
+
+fn write_frame(records: &[Vec<u8>], output: &mut Vec<u8>) -> Result<()> {
+    let mut frame = vec![];
+    encode::write_array_len(&mut frame, records.len() as u32)?;
+
+    for record in records {
+        frame.extend_from_slice(record);
+    }
+
+    let len = u32::try_from(frame.len()).map_err(|_| eyre!("record batch is too large"))?;
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(&frame);
+
+    Ok(())
+}
+
+/// Append `records` to `output`, split into as many frames as they need
+pub fn encode_batches(records: &[Record<EncryptedData>], output: &mut Vec<u8>) -> Result<()> {
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+
+    for record in records {
+        let mut encoded = vec![];
+        encode_record(record, &mut encoded)?;
+
+        if !batch.is_empty()
+            && (batch.len() >= BATCH_RECORDS || batch_bytes + encoded.len() > BATCH_BYTES)
+        {
+            write_frame(&batch, output)?;
+            batch.clear();
+            batch_bytes = 0;
+        }
+
+        batch_bytes += encoded.len();
+        batch.push(encoded);
+    }
+
+    if !batch.is_empty() {
+        write_frame(&batch, output)?;
+    }
+
+    Ok(())
+}
+
+fn read_uuid(bytes: &[u8]) -> Result<(Uuid, &[u8])> {
+    let mut reader = Bytes::new(bytes);
+    let len = decode::read_bin_len(&mut reader).map_err(error_report)? as usize;
+    let bytes = reader.remaining_slice();
+
+    if bytes.len() < len {
+        bail!("record stream ended in the middle of an ID");
+    }
+
+    let id = Uuid::from_slice(&bytes[..len])?;
+
+    Ok((id, &bytes[len..]))
+}
+
+fn read_u64(bytes: &[u8]) -> Result<(u64, &[u8])> {
+    let mut reader = Bytes::new(bytes);
+    let value = decode::read_u64(&mut reader).map_err(error_report)?;
+
+    Ok((value, reader.remaining_slice()))
+}
+
+fn read_string(bytes: &[u8]) -> Result<(String, &[u8])> {
+    let (value, bytes) = decode::read_str_from_slice(bytes).map_err(error_report)?;
+
+    Ok((value.to_owned(), bytes))
+}
+
+fn decode_record(bytes: &[u8]) -> Result<(Record<EncryptedData>, &[u8])> {
+    let mut reader = Bytes::new(bytes);
+    let nfields = decode::read_array_len(&mut reader).map_err(error_report)?;
+
+    if nfields != RECORD_FIELDS {
+        bail!("cannot decode records from a different version of Atuin");
+    }
+
+    let bytes = reader.remaining_slice();
+    let (id, bytes) = read_uuid(bytes)?;
+    let (idx, bytes) = read_u64(bytes)?;
+    let (host, bytes) = read_uuid(bytes)?;
+    let (name, bytes) = read_string(bytes)?;
+    let (timestamp, bytes) = read_u64(bytes)?;
+    let (version, bytes) = read_string(bytes)?;
+    let (tag, bytes) = read_string(bytes)?;
+    let (data, bytes) = read_string(bytes)?;
+    let (content_encryption_key, bytes) = read_string(bytes)?;
+
+    let record = Record {
+        id: RecordId(id),
+        idx,
+        host: Host {
+            id: HostId(host),
+            name,
+        },
+        timestamp,
+        version,
+        tag,
+        data: EncryptedData {
+            data,
+            content_encryption_key,
+        },
+    };
+
+    Ok((record, bytes))
+}
+
+fn decode_frame(bytes: &[u8]) -> Result<Vec<Record<EncryptedData>>> {
+    let mut reader = Bytes::new(bytes);
+    let len = decode::read_array_len(&mut reader).map_err(error_report)?;
+
+    let mut bytes = reader.remaining_slice();
+    let mut records = Vec::with_capacity(len as usize);
+
+    for _ in 0..len {
+        let (record, rest) = decode_record(bytes)?;
+        records.push(record);
+        bytes = rest;
+    }
+
+    if !bytes.is_empty() {
+        bail!("trailing bytes in record batch. malformed");
+    }
+
+    Ok(records)
+}
+
+/// Decodes a stream of frames, as it arrives in chunks
+#[derive(Debug)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    max_frame: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new(u32::MAX as usize)
+    }
+}
+
+impl Decoder {
+    /// Frames longer than `max_frame` bytes are rejected, without waiting for the rest of them
+    pub fn new(max_frame: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max_frame,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The next whole batch of records, if one has arrived
+    pub fn next_batch(&mut self) -> Result<Option<Vec<Record<EncryptedData>>>> {
+        let Some(header) = self.buf.get(..4) else {
+            return Ok(None);
+        };
+
+        let len = u32::from_be_bytes(header.try_into()?) as usize;
+
+        if len > self.max_frame {
+            bail!(
+                "record batch of {len} bytes is larger than the limit of {}",
+                self.max_frame
+            );
+        }
+
+        if self.buf.len() < 4 + len {
+            return Ok(None);
+        }
+
+        let records = decode_frame(&self.buf[4..4 + len])?;
+        self.buf.drain(..4 + len);
+
+        Ok(Some(records))
+    }
+
+    /// Check the stream didn't stop partway through a frame
+    pub fn finish(&self) -> Result<()> {
+        if !self.buf.is_empty() {
+            bail!("record stream ended partway through a batch");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{encode_batches, Decoder, BATCH_RECORDS};
+    use crate::record::{EncryptedData, Host, HostId, Record};
+    use crate::utils::uuid_v7;
+
+    fn records(count: usize) -> Vec<Record<EncryptedData>> {
+        let host = Host {
+            id: HostId(uuid_v7()),
+            name: "laptop".to_string(),
+        };
+
+        (0..count)
+            .map(|idx| {
+                Record::builder()
+                    .host(host.clone())
+                    .version("v0".to_string())
+                    .tag("history".to_string())
+                    .idx(idx as u64)
+                    .data(EncryptedData {
+                        data: format!("data {idx}"),
+                        content_encryption_key: format!("cek {idx}"),
+                    })
+                    .build()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_decode() {
+        let records = records(BATCH_RECORDS * 2 + 5);
+
+        let mut stream = vec![];
+        encode_batches(&records, &mut stream).unwrap();
+
+        // feed it through in awkwardly sized chunks, as it would arrive over the network
+        let mut decoder = Decoder::default();
+        let mut batches = vec![];
+
+        for chunk in stream.chunks(1000) {
+            decoder.push(chunk);
+
+            while let Some(batch) = decoder.next_batch().unwrap() {
+                batches.push(batch);
+            }
+        }
+        decoder.finish().unwrap();
+
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches.concat(), records);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut stream = vec![];
+        encode_batches(&records(10), &mut stream).unwrap();
+        stream.pop();
+
+        let mut decoder = Decoder::default();
+        decoder.push(&stream);
+
+        assert!(decoder.next_batch().unwrap().is_none());
+        assert!(decoder.finish().is_err());
+
+        // and frames over the limit are refused before they arrive
+        let mut decoder = Decoder::new(10);
+        decoder.push(&stream);
+        assert!(decoder.next_batch().is_err());
+    }
+}
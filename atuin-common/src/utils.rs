@@ -1,5 +1,5 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use rand::RngCore;
 use uuid::Uuid;
@@ -45,6 +45,59 @@ pub fn in_git_repo(path: &str) -> Option<PathBuf> {
     None
 }
 
+/// Read the current branch and commit of a git repository, without shelling out to git.
+///
+/// Returns `(branch, commit)`. The branch is `None` when HEAD is detached, and the commit is
+/// `None` on a branch with no commits yet.
+pub fn git_head(git_root: &Path) -> (Option<String>, Option<String>) {
+    let mut git_dir = git_root.join(".git");
+
+    // worktrees and submodules have a .git file pointing at the real git dir
+    if git_dir.is_file() {
+        let Ok(contents) = std::fs::read_to_string(&git_dir) else {
+            return (None, None);
+        };
+
+        match contents.trim().strip_prefix("gitdir: ") {
+            Some(dir) => git_dir = git_root.join(dir),
+            None => return (None, None),
+        }
+    }
+
+    let Ok(head) = std::fs::read_to_string(git_dir.join("HEAD")) else {
+        return (None, None);
+    };
+    let head = head.trim();
+
+    let Some(reference) = head.strip_prefix("ref: ") else {
+        // detached
+        return (None, Some(head.to_string()));
+    };
+
+    let branch = reference
+        .strip_prefix("refs/heads/")
+        .unwrap_or(reference)
+        .to_string();
+
+    // worktrees keep their refs in the main repository
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map_or_else(|_| git_dir.clone(), |dir| git_dir.join(dir.trim()));
+
+    let commit = std::fs::read_to_string(common_dir.join(reference))
+        .ok()
+        .map(|c| c.trim().to_string())
+        .or_else(|| {
+            let packed = std::fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+
+            packed.lines().find_map(|line| {
+                let (commit, name) = line.split_once(' ')?;
+                (name == reference).then(|| commit.to_string())
+            })
+        });
+
+    (Some(branch), commit)
+}
+
 // TODO: more reliable, more tested
 // I don't want to use ProjectDirs, it puts config in awkward places on
 // mac. Data too. Seems to be more intended for GUI apps.
@@ -149,6 +202,42 @@ mod tests {
         env::remove_var("HOME");
     }
 
+    #[test]
+    fn git_head_from_repo() {
+        let root = env::temp_dir().join(format!("atuin-git-{}", uuid_v4()));
+        let git_dir = root.join(".git");
+        std::fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+
+        // a new repository, with no commits
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert_eq!(git_head(&root), (Some("main".to_string()), None));
+
+        // a loose ref
+        std::fs::write(git_dir.join("refs/heads/main"), "abc123\n").unwrap();
+        assert_eq!(
+            git_head(&root),
+            (Some("main".to_string()), Some("abc123".to_string()))
+        );
+
+        // a packed ref
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        std::fs::write(
+            git_dir.join("packed-refs"),
+            "# pack-refs with: peeled fully-peeled sorted\ndef456 refs/heads/feature/x\n",
+        )
+        .unwrap();
+        assert_eq!(
+            git_head(&root),
+            (Some("feature/x".to_string()), Some("def456".to_string()))
+        );
+
+        // detached
+        std::fs::write(git_dir.join("HEAD"), "789abc\n").unwrap();
+        assert_eq!(git_head(&root), (None, Some("789abc".to_string())));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
     #[test]
     fn days_from_month() {
         assert_eq!(time::util::days_in_year_month(2023, Month::January), 31);
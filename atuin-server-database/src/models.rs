@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use time::OffsetDateTime;
 
 pub struct History {
@@ -32,12 +34,20 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Disabled users can't log in, and their sessions are rejected
+    pub disabled: bool,
 }
 
 pub struct Session {
     pub id: i64,
     pub user_id: i64,
     pub token: String,
+    /// The device the session was created for, as the client named it
+    pub device: String,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+    /// When the session stops working. Sessions without one last until they are revoked
+    pub expires_at: Option<OffsetDateTime>,
 }
 
 pub struct NewUser {
@@ -49,4 +59,39 @@ pub struct NewUser {
 pub struct NewSession {
     pub user_id: i64,
     pub token: String,
+    pub device: String,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// How many of something a user is storing, and how big they are
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stored {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl std::ops::Add for Stored {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            count: self.count + other.count,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+/// Everything a user is storing. Sizes are of the encrypted data
+#[derive(Debug, Default)]
+pub struct Usage {
+    /// Records, by tag
+    pub records: HashMap<String, Stored>,
+    /// History that hasn't been deleted
+    pub history: Stored,
+}
+
+impl Usage {
+    pub fn total_records(&self) -> Stored {
+        self.records.values().fold(Stored::default(), |a, b| a + *b)
+    }
 }
@@ -0,0 +1,4 @@
+alter table sessions add column device text not null default '';
+alter table sessions add column created_at timestamp not null default now();
+alter table sessions add column last_used_at timestamp;
+alter table sessions add column expires_at timestamp;
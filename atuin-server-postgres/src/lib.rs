@@ -2,10 +2,11 @@ use std::ops::Range;
 
 use async_trait::async_trait;
 use atuin_common::record::{EncryptedData, HostId, Record, RecordIdx, RecordStatus};
-use atuin_server_database::models::{History, NewHistory, NewSession, NewUser, Session, User};
-use atuin_server_database::{Database, DbError, DbResult};
+use atuin_server_database::models::{
+    History, NewHistory, NewSession, NewUser, Session, Stored, Usage, User,
+};
+use atuin_server_database::{Database, DbError, DbResult, DbSettings};
 use futures_util::TryStreamExt;
-use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::Row;
 
@@ -16,16 +17,15 @@ use wrappers::{DbHistory, DbRecord, DbSession, DbUser};
 
 mod wrappers;
 
+/// The settings used to be specific to Postgres, before the server could use SQLite too
+#[deprecated(note = "use atuin_server_database::DbSettings")]
+pub type PostgresSettings = DbSettings;
+
 #[derive(Clone)]
 pub struct Postgres {
     pool: sqlx::Pool<sqlx::postgres::Postgres>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct PostgresSettings {
-    pub db_uri: String,
-}
-
 fn fix_error(error: sqlx::Error) -> DbError {
     match error {
         sqlx::Error::RowNotFound => DbError::NotFound,
@@ -35,8 +35,8 @@ fn fix_error(error: sqlx::Error) -> DbError {
 
 #[async_trait]
 impl Database for Postgres {
-    type Settings = PostgresSettings;
-    async fn new(settings: &PostgresSettings) -> DbResult<Self> {
+    type Settings = DbSettings;
+    async fn new(settings: &DbSettings) -> DbResult<Self> {
         let pool = PgPoolOptions::new()
             .max_connections(100)
             .connect(settings.db_uri.as_str())
@@ -53,33 +53,42 @@ impl Database for Postgres {
 
     #[instrument(skip_all)]
     async fn get_session(&self, token: &str) -> DbResult<Session> {
-        sqlx::query_as("select id, user_id, token from sessions where token = $1")
-            .bind(token)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(fix_error)
-            .map(|DbSession(session)| session)
+        sqlx::query_as(
+            "select id, user_id, token, device, created_at, last_used_at, expires_at from sessions
+            where token = $1
+            and (expires_at is null or expires_at > $2)",
+        )
+        .bind(token)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbSession(session)| session)
     }
 
     #[instrument(skip_all)]
     async fn get_user(&self, username: &str) -> DbResult<User> {
-        sqlx::query_as("select id, username, email, password from users where username = $1")
-            .bind(username)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(fix_error)
-            .map(|DbUser(user)| user)
+        sqlx::query_as(
+            "select id, username, email, password, disabled from users where username = $1",
+        )
+        .bind(username)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbUser(user)| user)
     }
 
     #[instrument(skip_all)]
     async fn get_session_user(&self, token: &str) -> DbResult<User> {
         sqlx::query_as(
-            "select users.id, users.username, users.email, users.password from users 
-            inner join sessions 
-            on users.id = sessions.user_id 
-            and sessions.token = $1",
+            "select users.id, users.username, users.email, users.password, users.disabled from users
+            inner join sessions
+            on users.id = sessions.user_id
+            and sessions.token = $1
+            where sessions.expires_at is null or sessions.expires_at > $2",
         )
         .bind(token)
+        .bind(into_utc(OffsetDateTime::now_utc()))
         .fetch_one(&self.pool)
         .await
         .map_err(fix_error)
@@ -286,9 +295,129 @@ impl Database for Postgres {
             .await
             .map_err(fix_error)?;
 
+        sqlx::query("delete from store where user_id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_users(&self) -> DbResult<Vec<User>> {
+        sqlx::query_as("select id, username, email, password, disabled from users order by id")
+            .fetch(&self.pool)
+            .map_ok(|DbUser(user)| user)
+            .try_collect()
+            .await
+            .map_err(fix_error)
+    }
+
+    #[instrument(skip_all)]
+    async fn disable_user(&self, u: &User) -> DbResult<()> {
+        sqlx::query("update users set disabled = true where id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn enable_user(&self, u: &User) -> DbResult<()> {
+        sqlx::query("update users set disabled = false where id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn update_password(&self, u: &User, password: &str) -> DbResult<()> {
+        sqlx::query("update users set password = $2 where id = $1")
+            .bind(u.id)
+            .bind(password)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn update_email(&self, u: &User, email: &str) -> DbResult<()> {
+        sqlx::query("update users set email = $2 where id = $1")
+            .bind(u.id)
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_user_sessions(&self, u: &User) -> DbResult<()> {
+        sqlx::query("delete from sessions where user_id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
         Ok(())
     }
 
+    #[instrument(skip_all)]
+    async fn count_records(&self, user: &User) -> DbResult<i64> {
+        let res: (i64,) = sqlx::query_as("select count(1) from store where user_id = $1")
+            .bind(user.id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn usage(&self, user: &User) -> DbResult<Usage> {
+        let records: Vec<(String, i64, i64)> = sqlx::query_as(
+            "select tag, count(1), coalesce(sum(octet_length(data)), 0) from store
+            where user_id = $1
+            group by tag",
+        )
+        .bind(user.id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        let history: (i64, i64) = sqlx::query_as(
+            "select count(1), coalesce(sum(octet_length(data)), 0) from history
+            where user_id = $1
+            and deleted_at is null",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        let stored = |count: i64, bytes: i64| Stored {
+            count: count as u64,
+            bytes: bytes as u64,
+        };
+
+        Ok(Usage {
+            records: records
+                .into_iter()
+                .map(|(tag, count, bytes)| (tag, stored(count, bytes)))
+                .collect(),
+            history: stored(history.0, history.1),
+        })
+    }
+
     #[instrument(skip_all)]
     async fn add_user(&self, user: &NewUser) -> DbResult<i64> {
         let email: &str = &user.email;
@@ -314,14 +443,39 @@ impl Database for Postgres {
     #[instrument(skip_all)]
     async fn add_session(&self, session: &NewSession) -> DbResult<()> {
         let token: &str = &session.token;
+        let device: &str = &session.device;
 
         sqlx::query(
             "insert into sessions
-                (user_id, token)
-            values($1, $2)",
+                (user_id, token, device, created_at, expires_at)
+            values($1, $2, $3, $4, $5)",
         )
         .bind(session.user_id)
         .bind(token)
+        .bind(device)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .bind(session.expires_at.map(into_utc))
+        .execute(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn touch_session(&self, token: &str) -> DbResult<()> {
+        let now = OffsetDateTime::now_utc();
+
+        // only write once a minute, rather than on every request
+        sqlx::query(
+            "update sessions
+            set last_used_at = $2
+            where token = $1
+            and (last_used_at is null or last_used_at < $3)",
+        )
+        .bind(token)
+        .bind(into_utc(now))
+        .bind(into_utc(now - time::Duration::minutes(1)))
         .execute(&self.pool)
         .await
         .map_err(fix_error)?;
@@ -330,13 +484,36 @@ impl Database for Postgres {
     }
 
     #[instrument(skip_all)]
-    async fn get_user_session(&self, u: &User) -> DbResult<Session> {
-        sqlx::query_as("select id, user_id, token from sessions where user_id = $1")
+    async fn list_sessions(&self, u: &User) -> DbResult<Vec<Session>> {
+        sqlx::query_as(
+            "select id, user_id, token, device, created_at, last_used_at, expires_at from sessions
+            where user_id = $1
+            and (expires_at is null or expires_at > $2)
+            order by id",
+        )
+        .bind(u.id)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .fetch(&self.pool)
+        .map_ok(|DbSession(session)| session)
+        .try_collect()
+        .await
+        .map_err(fix_error)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_session(&self, u: &User, id: i64) -> DbResult<()> {
+        let res = sqlx::query("delete from sessions where user_id = $1 and id = $2")
             .bind(u.id)
-            .fetch_one(&self.pool)
+            .bind(id)
+            .execute(&self.pool)
             .await
-            .map_err(fix_error)
-            .map(|DbSession(session)| session)
+            .map_err(fix_error)?;
+
+        if res.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(())
     }
 
     #[instrument(skip_all)]
@@ -388,6 +565,38 @@ impl Database for Postgres {
         Ok(())
     }
 
+    #[instrument(skip_all)]
+    async fn replace_records(
+        &self,
+        user: &User,
+        records: &[Record<EncryptedData>],
+    ) -> DbResult<u64> {
+        let mut tx = self.pool.begin().await.map_err(fix_error)?;
+        let mut replaced = 0;
+
+        for i in records {
+            replaced += sqlx::query(
+                "update store set data = $1, cek = $2
+                where user_id = $3 and client_id = $4 and host = $5 and tag = $6 and idx = $7",
+            )
+            .bind(&i.data.data)
+            .bind(&i.data.content_encryption_key)
+            .bind(user.id)
+            .bind(i.id)
+            .bind(i.host.id)
+            .bind(&i.tag)
+            .bind(i.idx as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(fix_error)?
+            .rows_affected();
+        }
+
+        tx.commit().await.map_err(fix_error)?;
+
+        Ok(replaced)
+    }
+
     #[instrument(skip_all)]
     async fn next_records(
         &self,
@@ -16,6 +16,7 @@ impl<'a> FromRow<'a, PgRow> for DbUser {
             username: row.try_get("username")?,
             email: row.try_get("email")?,
             password: row.try_get("password")?,
+            disabled: row.try_get("disabled")?,
         }))
     }
 }
@@ -26,6 +27,16 @@ impl<'a> ::sqlx::FromRow<'a, PgRow> for DbSession {
             id: row.try_get("id")?,
             user_id: row.try_get("user_id")?,
             token: row.try_get("token")?,
+            device: row.try_get("device")?,
+            created_at: row
+                .try_get::<PrimitiveDateTime, _>("created_at")?
+                .assume_utc(),
+            last_used_at: row
+                .try_get::<Option<PrimitiveDateTime>, _>("last_used_at")?
+                .map(PrimitiveDateTime::assume_utc),
+            expires_at: row
+                .try_get::<Option<PrimitiveDateTime>, _>("expires_at")?
+                .map(PrimitiveDateTime::assume_utc),
         }))
     }
 }
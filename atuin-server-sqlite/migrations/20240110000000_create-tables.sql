@@ -0,0 +1,67 @@
+-- The sqlite backend starts out with the same schema the postgres backend has
+-- ended up with, rather than replaying its whole migration history.
+
+create table users (
+	id integer primary key autoincrement,
+	username text not null unique,
+	email text not null unique,
+	password text not null unique,
+	created_at timestamp not null default current_timestamp
+);
+
+create unique index email_unique_idx on users (lower(email));
+create unique index username_unique_idx on users (lower(username));
+
+create table sessions (
+	id integer primary key autoincrement,
+	user_id integer not null,
+	token text unique not null
+);
+
+create table history (
+	id integer primary key autoincrement,
+	client_id text not null unique, -- the client-generated ID
+	user_id integer not null,
+	hostname text not null,
+	timestamp timestamp not null,
+
+	data text not null,             -- encrypted, as always
+
+	created_at timestamp not null default current_timestamp,
+	deleted_at timestamp
+);
+
+create index history_deleted_index on history(client_id, user_id, deleted_at);
+
+create table total_history_count_user(
+	id integer primary key autoincrement,
+	user_id integer not null unique,
+	total integer not null
+);
+
+-- Maintain the cached count on insert only, matching postgres. History is only
+-- ever deleted when the user is.
+create trigger tg_user_history_count
+	after insert on history
+	for each row
+begin
+	insert or ignore into total_history_count_user(user_id, total) values (new.user_id, 0);
+	update total_history_count_user set total = total + 1 where user_id = new.user_id;
+end;
+
+create table store (
+	id blob primary key,
+	client_id blob not null,
+	host blob not null,
+	idx integer not null,           -- the index of the record in this store, identified by (host, tag)
+	timestamp integer not null,
+	version text not null,
+	tag text not null,
+	data text not null,
+	cek text not null,
+
+	user_id integer not null,
+	created_at timestamp not null default current_timestamp
+);
+
+create unique index record_uniq on store(user_id, host, tag, idx);
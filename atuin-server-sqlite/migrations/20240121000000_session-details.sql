@@ -0,0 +1,7 @@
+-- sqlite can't add a column with a default of current_timestamp, so fill it in afterwards
+alter table sessions add column device text not null default '';
+alter table sessions add column created_at timestamp not null default '1970-01-01 00:00:00';
+alter table sessions add column last_used_at timestamp;
+alter table sessions add column expires_at timestamp;
+
+update sessions set created_at = current_timestamp;
@@ -0,0 +1,645 @@
+use std::ops::Range;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use atuin_common::record::{EncryptedData, HostId, Record, RecordIdx, RecordStatus};
+use atuin_server_database::models::{
+    History, NewHistory, NewSession, NewUser, Session, Stored, Usage, User,
+};
+use atuin_server_database::{Database, DbError, DbResult, DbSettings};
+use futures_util::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::Row;
+
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use tracing::instrument;
+use uuid::Uuid;
+use wrappers::{DbHistory, DbRecord, DbSession, DbUser};
+
+mod wrappers;
+
+#[derive(Clone)]
+pub struct Sqlite {
+    pool: sqlx::Pool<sqlx::sqlite::Sqlite>,
+}
+
+fn fix_error(error: sqlx::Error) -> DbError {
+    match error {
+        sqlx::Error::RowNotFound => DbError::NotFound,
+        error => DbError::Other(error.into()),
+    }
+}
+
+#[async_trait]
+impl Database for Sqlite {
+    type Settings = DbSettings;
+    async fn new(settings: &DbSettings) -> DbResult<Self> {
+        let opts = SqliteConnectOptions::from_str(settings.db_uri.as_str())
+            .map_err(fix_error)?
+            .journal_mode(SqliteJournalMode::Wal)
+            .create_if_missing(true);
+
+        let pool = SqlitePoolOptions::new()
+            .connect_with(opts)
+            .await
+            .map_err(fix_error)?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .map_err(|error| DbError::Other(error.into()))?;
+
+        Ok(Self { pool })
+    }
+
+    #[instrument(skip_all)]
+    async fn get_session(&self, token: &str) -> DbResult<Session> {
+        sqlx::query_as(
+            "select id, user_id, token, device, created_at, last_used_at, expires_at from sessions
+            where token = ?1
+            and (expires_at is null or expires_at > ?2)",
+        )
+        .bind(token)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbSession(session)| session)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_user(&self, username: &str) -> DbResult<User> {
+        sqlx::query_as(
+            "select id, username, email, password, disabled from users where username = ?1",
+        )
+        .bind(username)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbUser(user)| user)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_session_user(&self, token: &str) -> DbResult<User> {
+        sqlx::query_as(
+            "select users.id, users.username, users.email, users.password, users.disabled from users
+            inner join sessions
+            on users.id = sessions.user_id
+            and sessions.token = ?1
+            where sessions.expires_at is null or sessions.expires_at > ?2",
+        )
+        .bind(token)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbUser(user)| user)
+    }
+
+    #[instrument(skip_all)]
+    async fn count_history(&self, user: &User) -> DbResult<i64> {
+        let res: (i64,) = sqlx::query_as(
+            "select count(1) from history
+            where user_id = ?1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn total_history(&self) -> DbResult<i64> {
+        let res: (i64,) =
+            sqlx::query_as("select coalesce(sum(total), 0) from total_history_count_user")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn count_history_cached(&self, user: &User) -> DbResult<i64> {
+        let res: (i64,) = sqlx::query_as(
+            "select total from total_history_count_user
+            where user_id = ?1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    async fn delete_history(&self, user: &User, id: String) -> DbResult<()> {
+        sqlx::query(
+            "update history
+            set deleted_at = ?3
+            where user_id = ?1
+            and client_id = ?2
+            and deleted_at is null", // don't just keep setting it
+        )
+        .bind(user.id)
+        .bind(id)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .execute(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn deleted_history(&self, user: &User) -> DbResult<Vec<String>> {
+        let res = sqlx::query(
+            "select client_id from history
+            where user_id = ?1
+            and deleted_at is not null",
+        )
+        .bind(user.id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        let res = res
+            .iter()
+            .map(|row| row.get::<String, _>("client_id"))
+            .collect();
+
+        Ok(res)
+    }
+
+    #[instrument(skip_all)]
+    async fn count_history_range(
+        &self,
+        user: &User,
+        range: Range<OffsetDateTime>,
+    ) -> DbResult<i64> {
+        let res: (i64,) = sqlx::query_as(
+            "select count(1) from history
+            where user_id = ?1
+            and timestamp >= ?2
+            and timestamp < ?3",
+        )
+        .bind(user.id)
+        .bind(into_utc(range.start))
+        .bind(into_utc(range.end))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_history(
+        &self,
+        user: &User,
+        created_after: OffsetDateTime,
+        since: OffsetDateTime,
+        host: &str,
+        page_size: i64,
+    ) -> DbResult<Vec<History>> {
+        let res = sqlx::query_as(
+            "select id, client_id, user_id, hostname, timestamp, data, created_at from history
+            where user_id = ?1
+            and hostname != ?2
+            and created_at >= ?3
+            and timestamp >= ?4
+            order by timestamp asc
+            limit ?5",
+        )
+        .bind(user.id)
+        .bind(host)
+        .bind(into_utc(created_after))
+        .bind(into_utc(since))
+        .bind(page_size)
+        .fetch(&self.pool)
+        .map_ok(|DbHistory(h)| h)
+        .try_collect()
+        .await
+        .map_err(fix_error)?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip_all)]
+    async fn add_history(&self, history: &[NewHistory]) -> DbResult<()> {
+        let mut tx = self.pool.begin().await.map_err(fix_error)?;
+
+        for i in history {
+            let client_id: &str = &i.client_id;
+            let hostname: &str = &i.hostname;
+            let data: &str = &i.data;
+
+            sqlx::query(
+                "insert into history
+                    (client_id, user_id, hostname, timestamp, data)
+                values (?1, ?2, ?3, ?4, ?5)
+                on conflict do nothing
+                ",
+            )
+            .bind(client_id)
+            .bind(i.user_id)
+            .bind(hostname)
+            .bind(into_utc(i.timestamp))
+            .bind(data)
+            .execute(&mut *tx)
+            .await
+            .map_err(fix_error)?;
+        }
+
+        tx.commit().await.map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_user(&self, u: &User) -> DbResult<()> {
+        sqlx::query("delete from sessions where user_id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        sqlx::query("delete from users where id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        sqlx::query("delete from history where user_id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        sqlx::query("delete from total_history_count_user where user_id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        sqlx::query("delete from store where user_id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_users(&self) -> DbResult<Vec<User>> {
+        sqlx::query_as("select id, username, email, password, disabled from users order by id")
+            .fetch(&self.pool)
+            .map_ok(|DbUser(user)| user)
+            .try_collect()
+            .await
+            .map_err(fix_error)
+    }
+
+    #[instrument(skip_all)]
+    async fn disable_user(&self, u: &User) -> DbResult<()> {
+        sqlx::query("update users set disabled = true where id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn enable_user(&self, u: &User) -> DbResult<()> {
+        sqlx::query("update users set disabled = false where id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn update_password(&self, u: &User, password: &str) -> DbResult<()> {
+        sqlx::query("update users set password = ?2 where id = ?1")
+            .bind(u.id)
+            .bind(password)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn update_email(&self, u: &User, email: &str) -> DbResult<()> {
+        sqlx::query("update users set email = ?2 where id = ?1")
+            .bind(u.id)
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_user_sessions(&self, u: &User) -> DbResult<()> {
+        sqlx::query("delete from sessions where user_id = ?1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn count_records(&self, user: &User) -> DbResult<i64> {
+        let res: (i64,) = sqlx::query_as("select count(1) from store where user_id = ?1")
+            .bind(user.id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn usage(&self, user: &User) -> DbResult<Usage> {
+        let records: Vec<(String, i64, i64)> = sqlx::query_as(
+            "select tag, count(1), coalesce(sum(length(data)), 0) from store
+            where user_id = ?1
+            group by tag",
+        )
+        .bind(user.id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        let history: (i64, i64) = sqlx::query_as(
+            "select count(1), coalesce(sum(length(data)), 0) from history
+            where user_id = ?1
+            and deleted_at is null",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        let stored = |count: i64, bytes: i64| Stored {
+            count: count as u64,
+            bytes: bytes as u64,
+        };
+
+        Ok(Usage {
+            records: records
+                .into_iter()
+                .map(|(tag, count, bytes)| (tag, stored(count, bytes)))
+                .collect(),
+            history: stored(history.0, history.1),
+        })
+    }
+
+    #[instrument(skip_all)]
+    async fn add_user(&self, user: &NewUser) -> DbResult<i64> {
+        let email: &str = &user.email;
+        let username: &str = &user.username;
+        let password: &str = &user.password;
+
+        let res: (i64,) = sqlx::query_as(
+            "insert into users
+                (username, email, password)
+            values(?1, ?2, ?3)
+            returning id",
+        )
+        .bind(username)
+        .bind(email)
+        .bind(password)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn add_session(&self, session: &NewSession) -> DbResult<()> {
+        let token: &str = &session.token;
+        let device: &str = &session.device;
+
+        sqlx::query(
+            "insert into sessions
+                (user_id, token, device, created_at, expires_at)
+            values(?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(session.user_id)
+        .bind(token)
+        .bind(device)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .bind(session.expires_at.map(into_utc))
+        .execute(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn touch_session(&self, token: &str) -> DbResult<()> {
+        let now = OffsetDateTime::now_utc();
+
+        // only write once a minute, rather than on every request
+        sqlx::query(
+            "update sessions
+            set last_used_at = ?2
+            where token = ?1
+            and (last_used_at is null or last_used_at < ?3)",
+        )
+        .bind(token)
+        .bind(into_utc(now))
+        .bind(into_utc(now - time::Duration::minutes(1)))
+        .execute(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_sessions(&self, u: &User) -> DbResult<Vec<Session>> {
+        sqlx::query_as(
+            "select id, user_id, token, device, created_at, last_used_at, expires_at from sessions
+            where user_id = ?1
+            and (expires_at is null or expires_at > ?2)
+            order by id",
+        )
+        .bind(u.id)
+        .bind(into_utc(OffsetDateTime::now_utc()))
+        .fetch(&self.pool)
+        .map_ok(|DbSession(session)| session)
+        .try_collect()
+        .await
+        .map_err(fix_error)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_session(&self, u: &User, id: i64) -> DbResult<()> {
+        let res = sqlx::query("delete from sessions where user_id = ?1 and id = ?2")
+            .bind(u.id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        if res.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn oldest_history(&self, user: &User) -> DbResult<History> {
+        sqlx::query_as(
+            "select id, client_id, user_id, hostname, timestamp, data, created_at from history
+            where user_id = ?1
+            order by timestamp asc
+            limit 1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(fix_error)
+        .map(|DbHistory(h)| h)
+    }
+
+    #[instrument(skip_all)]
+    async fn add_records(&self, user: &User, records: &[Record<EncryptedData>]) -> DbResult<()> {
+        let mut tx = self.pool.begin().await.map_err(fix_error)?;
+
+        for i in records {
+            let id = atuin_common::utils::uuid_v7();
+
+            sqlx::query(
+                "insert into store
+                    (id, client_id, host, idx, timestamp, version, tag, data, cek, user_id)
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                on conflict do nothing
+                ",
+            )
+            .bind(id)
+            .bind(i.id)
+            .bind(i.host.id)
+            .bind(i.idx as i64)
+            .bind(i.timestamp as i64) // throwing away some data, but i64 is still big in terms of time
+            .bind(&i.version)
+            .bind(&i.tag)
+            .bind(&i.data.data)
+            .bind(&i.data.content_encryption_key)
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(fix_error)?;
+        }
+
+        tx.commit().await.map_err(fix_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn replace_records(
+        &self,
+        user: &User,
+        records: &[Record<EncryptedData>],
+    ) -> DbResult<u64> {
+        let mut tx = self.pool.begin().await.map_err(fix_error)?;
+        let mut replaced = 0;
+
+        for i in records {
+            replaced += sqlx::query(
+                "update store set data = ?1, cek = ?2
+                where user_id = ?3 and client_id = ?4 and host = ?5 and tag = ?6 and idx = ?7",
+            )
+            .bind(&i.data.data)
+            .bind(&i.data.content_encryption_key)
+            .bind(user.id)
+            .bind(i.id)
+            .bind(i.host.id)
+            .bind(&i.tag)
+            .bind(i.idx as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(fix_error)?
+            .rows_affected();
+        }
+
+        tx.commit().await.map_err(fix_error)?;
+
+        Ok(replaced)
+    }
+
+    #[instrument(skip_all)]
+    async fn next_records(
+        &self,
+        user: &User,
+        host: HostId,
+        tag: String,
+        start: Option<RecordIdx>,
+        count: u64,
+    ) -> DbResult<Vec<Record<EncryptedData>>> {
+        tracing::debug!("{:?} - {:?} - {:?}", host, tag, start);
+        let start = start.unwrap_or(0);
+
+        let records: Vec<DbRecord> = sqlx::query_as(
+            "select client_id, host, idx, timestamp, version, tag, data, cek from store
+                    where user_id = ?1
+                    and tag = ?2
+                    and host = ?3
+                    and idx >= ?4
+                    order by idx asc
+                    limit ?5",
+        )
+        .bind(user.id)
+        .bind(tag)
+        .bind(host)
+        .bind(start as i64)
+        .bind(count as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(fix_error)?;
+
+        Ok(records.into_iter().map(Into::into).collect())
+    }
+
+    async fn status(&self, user: &User) -> DbResult<RecordStatus> {
+        const STATUS_SQL: &str =
+            "select host, tag, max(idx) from store where user_id = ?1 group by host, tag";
+
+        let res: Vec<(Uuid, String, i64)> = sqlx::query_as(STATUS_SQL)
+            .bind(user.id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(fix_error)?;
+
+        let mut status = RecordStatus::new();
+
+        for i in res {
+            status.set_raw(HostId(i.0), i.1, i.2 as u64);
+        }
+
+        Ok(status)
+    }
+}
+
+fn into_utc(x: OffsetDateTime) -> PrimitiveDateTime {
+    let x = x.to_offset(UtcOffset::UTC);
+    PrimitiveDateTime::new(x.date(), x.time())
+}
@@ -129,7 +129,9 @@ pub async fn add<DB: Database>(
     state: State<AppState<DB>>,
     Json(req): Json<Vec<AddHistoryRequest>>,
 ) -> Result<(), ErrorResponseStatus<'static>> {
-    let State(AppState { database, settings }) = state;
+    let State(AppState {
+        database, settings, ..
+    }) = state;
 
     debug!("request to add {} history items", req.len());
     counter!("atuin_history_uploaded", req.len() as u64);
@@ -164,6 +166,24 @@ pub async fn add<DB: Database>(
         keep
     });
 
+    if settings.quota.limits_history() {
+        let usage = match database.usage(&user).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                error!("failed to get usage: {}", e);
+
+                return Err(ErrorResponse::reply("failed to add history")
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        };
+
+        if let Err(e) = settings.quota.check_history(&usage, &history) {
+            counter!("atuin_history_over_quota", 1);
+
+            return Err(e.into());
+        }
+    }
+
     if let Err(e) = database.add_history(&history).await {
         error!("failed to add history: {}", e);
 
@@ -12,11 +12,13 @@ use axum::{
 };
 use http::StatusCode;
 use rand::rngs::OsRng;
+use time::OffsetDateTime;
 use tracing::{debug, error, info, instrument};
 use uuid::Uuid;
 
 use super::{ErrorResponse, ErrorResponseStatus, RespExt};
-use crate::router::{AppState, UserAuth};
+use crate::router::{AppState, SessionAuth, UserAuth};
+use crate::settings::Settings;
 use atuin_server_database::{
     models::{NewSession, NewUser},
     Database, DbError,
@@ -94,16 +96,11 @@ pub async fn register<DB: Database>(
         );
     }
 
-    for c in register.username.chars() {
-        match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => {}
-            _ => {
-                return Err(ErrorResponse::reply(
-                    "Only alphanumeric and hyphens (-) are allowed in usernames",
-                )
-                .with_status(StatusCode::BAD_REQUEST))
-            }
-        }
+    if !valid_username(&register.username) {
+        return Err(ErrorResponse::reply(
+            "Only alphanumeric and hyphens (-) are allowed in usernames",
+        )
+        .with_status(StatusCode::BAD_REQUEST));
     }
 
     let hashed = hash_secret(&register.password);
@@ -130,6 +127,8 @@ pub async fn register<DB: Database>(
     let new_session = NewSession {
         user_id,
         token: (&token).into(),
+        device: register.device.clone(),
+        expires_at: session_expiry(&state.settings),
     };
 
     if let Some(url) = &state.settings.register_webhook_url {
@@ -188,18 +187,15 @@ pub async fn login<DB: Database>(
         }
     };
 
-    let session = match db.get_user_session(&user).await {
-        Ok(u) => u,
-        Err(DbError::NotFound) => {
-            debug!("user session not found for user id={}", user.id);
-            return Err(ErrorResponse::reply("user not found").with_status(StatusCode::NOT_FOUND));
+    // only users that exist get a bucket, so made up names can't fill the limiter
+    if let Some(limit) = &state.username_limit {
+        if limit.check(&user.id.to_string()).is_err() {
+            return Err(ErrorResponse::reply(
+                "too many login attempts for this user, try again later",
+            )
+            .with_status(StatusCode::TOO_MANY_REQUESTS));
         }
-        Err(DbError::Other(err)) => {
-            error!("database error for user {}: {}", login.username, err);
-            return Err(ErrorResponse::reply("database error")
-                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
+    }
 
     let verified = verify_str(user.password.as_str(), login.password.borrow());
 
@@ -209,12 +205,162 @@ pub async fn login<DB: Database>(
         );
     }
 
-    Ok(Json(LoginResponse {
-        session: session.token,
-    }))
+    if user.disabled {
+        return Err(ErrorResponse::reply("this account has been disabled")
+            .with_status(StatusCode::FORBIDDEN));
+    }
+
+    // every login gets its own session, so it can be revoked on its own
+    let token = Uuid::new_v4().as_simple().to_string();
+
+    let new_session = NewSession {
+        user_id: user.id,
+        token: (&token).into(),
+        device: login.device.clone(),
+        expires_at: session_expiry(&state.settings),
+    };
+
+    if let Err(e) = db.add_session(&new_session).await {
+        error!("failed to add session: {}", e);
+        return Err(
+            ErrorResponse::reply("failed to log in").with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        );
+    }
+
+    Ok(Json(LoginResponse { session: token }))
+}
+
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn change_password<DB: Database>(
+    SessionAuth(user, session): SessionAuth,
+    state: State<AppState<DB>>,
+    Json(change): Json<ChangePasswordRequest>,
+) -> Result<Json<ChangePasswordResponse>, ErrorResponseStatus<'static>> {
+    if !verify_str(&user.password, &change.current_password) {
+        return Err(
+            ErrorResponse::reply("password is not correct").with_status(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    if change.new_password.is_empty() {
+        return Err(ErrorResponse::reply("the new password can't be empty")
+            .with_status(StatusCode::BAD_REQUEST));
+    }
+
+    let db = &state.0.database;
+    let db_error = |e: DbError| {
+        error!("failed to change password: {}", e);
+        ErrorResponse::reply("failed to change password")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    };
+
+    db.update_password(&user, &hash_secret(&change.new_password))
+        .await
+        .map_err(db_error)?;
+
+    // Anyone holding a session may have had the old password, so end them all, and give the
+    // caller a new one
+    db.delete_user_sessions(&user).await.map_err(db_error)?;
+
+    let token = Uuid::new_v4().as_simple().to_string();
+    db.add_session(&NewSession {
+        user_id: user.id,
+        token: (&token).into(),
+        device: session.device,
+        expires_at: session_expiry(&state.settings),
+    })
+    .await
+    .map_err(db_error)?;
+
+    Ok(Json(ChangePasswordResponse { session: token }))
+}
+
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn change_email<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    Json(change): Json<ChangeEmailRequest>,
+) -> Result<Json<ChangeEmailResponse>, ErrorResponseStatus<'static>> {
+    if !verify_str(&user.password, &change.password) {
+        return Err(
+            ErrorResponse::reply("password is not correct").with_status(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    let db = &state.0.database;
+    if let Err(e) = db.update_email(&user, &change.email).await {
+        // most likely, the email is already in use
+        error!("failed to change email: {}", e);
+        return Err(
+            ErrorResponse::reply("failed to change email").with_status(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    Ok(Json(ChangeEmailResponse {}))
+}
+
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn sessions<DB: Database>(
+    SessionAuth(user, current): SessionAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<ListSessionsResponse>, ErrorResponseStatus<'static>> {
+    let sessions = match state.database.list_sessions(&user).await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            error!("failed to list sessions: {}", e);
+            return Err(ErrorResponse::reply("failed to list sessions")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            id: session.id,
+            current: session.id == current.id,
+            device: session.device,
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+
+    Ok(Json(ListSessionsResponse { sessions }))
+}
+
+#[instrument(skip_all, fields(user.id = user.id, session.id = id))]
+pub async fn revoke_session<DB: Database>(
+    Path(id): Path<i64>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<RevokeSessionResponse>, ErrorResponseStatus<'static>> {
+    match state.database.delete_session(&user, id).await {
+        Ok(()) => Ok(Json(RevokeSessionResponse {})),
+        Err(DbError::NotFound) => {
+            Err(ErrorResponse::reply("session not found").with_status(StatusCode::NOT_FOUND))
+        }
+        Err(DbError::Other(e)) => {
+            error!("failed to revoke session: {}", e);
+            Err(ErrorResponse::reply("failed to revoke session")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+/// When a session created now should expire, if ever
+fn session_expiry<DbSettings>(settings: &Settings<DbSettings>) -> Option<OffsetDateTime> {
+    (settings.session_expiry_days > 0).then(|| {
+        OffsetDateTime::now_utc() + time::Duration::days(settings.session_expiry_days as i64)
+    })
+}
+
+pub fn valid_username(username: &str) -> bool {
+    username
+        .chars()
+        .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-'))
 }
 
-fn hash_secret(password: &str) -> String {
+pub fn hash_secret(password: &str) -> String {
     let arg2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default());
     let salt = SaltString::generate(&mut OsRng);
     let hash = arg2.hash_password(password.as_bytes(), &salt).unwrap();
@@ -0,0 +1,34 @@
+use axum::{extract::State, Json};
+use http::StatusCode;
+use tracing::{error, instrument};
+
+use crate::{
+    handlers::{ErrorResponse, ErrorResponseStatus, RespExt},
+    router::{AppState, UserAuth},
+};
+use atuin_server_database::Database;
+
+use atuin_common::api::UsageResponse;
+
+/// How much the user is storing, and their quota
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn usage<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<UsageResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database, settings, ..
+    }) = state;
+
+    let usage = match database.usage(&user).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("failed to get usage: {}", e);
+
+            return Err(ErrorResponse::reply("failed to get usage")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    Ok(Json(settings.quota.usage(&usage)))
+}
@@ -1 +1,2 @@
+pub(crate) mod me;
 pub(crate) mod record;
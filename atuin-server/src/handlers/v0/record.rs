@@ -1,16 +1,37 @@
-use axum::{extract::Query, extract::State, Json};
-use http::StatusCode;
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{BodyStream, Query, State},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures_util::StreamExt;
+use http::{header::CONTENT_TYPE, StatusCode};
 use metrics::counter;
 use serde::Deserialize;
+use std::{collections::HashMap, io, time::Duration};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
 use tracing::{error, instrument};
 
 use crate::{
     handlers::{ErrorResponse, ErrorResponseStatus, RespExt},
+    notify::Notifier,
+    quota::{self, QuotaExceeded},
     router::{AppState, UserAuth},
+    settings::Settings,
 };
-use atuin_server_database::Database;
+use atuin_server_database::{models::User, Database, DbResult};
 
-use atuin_common::record::{EncryptedData, HostId, Record, RecordIdx, RecordStatus};
+use atuin_common::{
+    api::{
+        RecordEvent, RecordRange, ReplaceRecordsResponse, StreamDownloadRequest,
+        StreamUploadResponse,
+    },
+    record::{EncryptedData, HostId, Record, RecordIdx, RecordStatus},
+    stream::{self, Decoder},
+};
 
 #[instrument(skip_all, fields(user.id = user.id))]
 pub async fn post<DB: Database>(
@@ -18,7 +39,12 @@ pub async fn post<DB: Database>(
     state: State<AppState<DB>>,
     Json(records): Json<Vec<Record<EncryptedData>>>,
 ) -> Result<(), ErrorResponseStatus<'static>> {
-    let State(AppState { database, settings }) = state;
+    let State(AppState {
+        database,
+        settings,
+        notifier,
+        ..
+    }) = state;
 
     tracing::debug!(
         count = records.len(),
@@ -41,6 +67,24 @@ pub async fn post<DB: Database>(
         );
     }
 
+    if settings.quota.limits_records() {
+        let usage = match database.usage(&user).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                error!("failed to get usage: {}", e);
+
+                return Err(ErrorResponse::reply("failed to add record")
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        };
+
+        if let Err(e) = settings.quota.check_records(&usage, &records) {
+            counter!("atuin_record_over_quota", 1);
+
+            return Err(e.into());
+        }
+    }
+
     if let Err(e) = database.add_records(&user, &records).await {
         error!("failed to add record: {}", e);
 
@@ -48,19 +92,114 @@ pub async fn post<DB: Database>(
             .with_status(StatusCode::INTERNAL_SERVER_ERROR));
     };
 
+    notifier.notify(user.id, &records);
+
     Ok(())
 }
 
+/// Replace records the user already has, for example after they rotate their key
 #[instrument(skip_all, fields(user.id = user.id))]
-pub async fn index<DB: Database>(
+pub async fn replace<DB: Database>(
     UserAuth(user): UserAuth,
     state: State<AppState<DB>>,
-) -> Result<Json<RecordStatus>, ErrorResponseStatus<'static>> {
+    Json(records): Json<Vec<Record<EncryptedData>>>,
+) -> Result<Json<ReplaceRecordsResponse>, ErrorResponseStatus<'static>> {
     let State(AppState {
-        database,
-        settings: _,
+        database, settings, ..
     }) = state;
 
+    tracing::debug!(
+        count = records.len(),
+        user = user.username,
+        "request to replace records"
+    );
+
+    let too_big = records
+        .iter()
+        .any(|r| r.data.data.len() >= settings.max_record_size || settings.max_record_size == 0);
+
+    if too_big {
+        counter!("atuin_record_too_large", 1);
+
+        return Err(
+            ErrorResponse::reply("could not replace records; record too large")
+                .with_status(StatusCode::BAD_REQUEST),
+        );
+    }
+
+    if settings.quota.limits_records() {
+        let (usage, stored) = match tokio::try_join!(
+            database.usage(&user),
+            stored_records(&database, &user, &records)
+        ) {
+            Ok(found) => found,
+            Err(e) => {
+                error!("failed to get usage: {}", e);
+
+                return Err(ErrorResponse::reply("failed to replace records")
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        };
+
+        if let Err(e) = settings.quota.check_replace(&usage, &stored, &records) {
+            counter!("atuin_record_over_quota", 1);
+
+            return Err(e.into());
+        }
+    }
+
+    let replaced = match database.replace_records(&user, &records).await {
+        Ok(replaced) => replaced,
+        Err(e) => {
+            error!("failed to replace records: {}", e);
+
+            return Err(ErrorResponse::reply("failed to replace records")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    counter!("atuin_record_replaced", replaced);
+
+    Ok(Json(ReplaceRecordsResponse { replaced }))
+}
+
+/// The user's copies of `records`, as they are now. Records are fetched a (host, tag) at a time,
+/// over the range of indexes being replaced
+async fn stored_records<DB: Database>(
+    database: &DB,
+    user: &User,
+    records: &[Record<EncryptedData>],
+) -> DbResult<Vec<Record<EncryptedData>>> {
+    let mut ranges: HashMap<(HostId, &str), (RecordIdx, RecordIdx)> = HashMap::new();
+
+    for record in records {
+        let range = ranges
+            .entry((record.host.id, record.tag.as_str()))
+            .or_insert((record.idx, record.idx));
+        range.0 = range.0.min(record.idx);
+        range.1 = range.1.max(record.idx);
+    }
+
+    let mut stored = Vec::new();
+
+    for ((host, tag), (start, end)) in ranges {
+        stored.extend(
+            database
+                .next_records(user, host, tag.to_string(), Some(start), end - start + 1)
+                .await?,
+        );
+    }
+
+    Ok(stored)
+}
+
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn index<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<RecordStatus>, ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
     let record_index = match database.status(&user).await {
         Ok(index) => index,
         Err(e) => {
@@ -88,10 +227,7 @@ pub async fn next<DB: Database>(
     UserAuth(user): UserAuth,
     state: State<AppState<DB>>,
 ) -> Result<Json<Vec<Record<EncryptedData>>>, ErrorResponseStatus<'static>> {
-    let State(AppState {
-        database,
-        settings: _,
-    }) = state;
+    let State(AppState { database, .. }) = state;
     let params = params.0;
 
     let records = match database
@@ -109,3 +245,229 @@ pub async fn next<DB: Database>(
 
     Ok(Json(records))
 }
+
+/// Add records sent as a record stream, for any number of (host, tag)s at once. Records are stored
+/// a batch at a time, so if a batch is refused the ones before it are kept
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn stream_upload<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    mut body: BodyStream,
+) -> Result<Json<StreamUploadResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        settings,
+        notifier,
+        ..
+    }) = state;
+
+    match add_stream(&database, &settings, &notifier, &user, &mut body).await {
+        Ok(uploaded) => Ok(Json(StreamUploadResponse { uploaded })),
+        Err(e) => {
+            // read the rest of the request, so the client sees our response rather than the
+            // connection closing while it is still sending
+            while body.next().await.is_some() {}
+
+            Err(e)
+        }
+    }
+}
+
+async fn add_stream<DB: Database>(
+    database: &DB,
+    settings: &Settings<DB::Settings>,
+    notifier: &Notifier,
+    user: &User,
+    body: &mut BodyStream,
+) -> Result<u64, ErrorResponseStatus<'static>> {
+    let invalid = |e: eyre::Report| {
+        ErrorResponse {
+            reason: format!("invalid record stream: {e}").into(),
+        }
+        .with_status(StatusCode::BAD_REQUEST)
+    };
+
+    let mut usage = if settings.quota.limits_records() {
+        match database.usage(user).await {
+            Ok(usage) => Some(usage),
+            Err(e) => {
+                error!("failed to get usage: {}", e);
+
+                return Err(ErrorResponse::reply("failed to add record")
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut decoder = Decoder::new(settings.max_record_size.saturating_add(stream::BATCH_BYTES));
+    let mut uploaded = 0;
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| {
+            error!("failed to read record stream: {}", e);
+
+            ErrorResponse::reply("failed to read record stream")
+                .with_status(StatusCode::BAD_REQUEST)
+        })?;
+
+        decoder.push(&chunk);
+
+        while let Some(records) = decoder.next_batch().map_err(invalid)? {
+            counter!("atuin_record_uploaded", records.len() as u64);
+
+            let too_big = records.iter().any(|r| {
+                r.data.data.len() >= settings.max_record_size || settings.max_record_size == 0
+            });
+
+            if too_big {
+                counter!("atuin_record_too_large", 1);
+
+                return Err(
+                    ErrorResponse::reply("could not add records; record too large")
+                        .with_status(StatusCode::BAD_REQUEST),
+                );
+            }
+
+            // earlier batches have already been stored, so say how many, for the client to
+            // carry on from there once it has room
+            if let Some(usage) = &mut usage {
+                if let Err(e) = settings.quota.check_records(usage, &records) {
+                    counter!("atuin_record_over_quota", 1);
+
+                    return Err(QuotaExceeded(format!(
+                        "{}. {uploaded} records from this upload were stored",
+                        e.0
+                    ))
+                    .into());
+                }
+
+                quota::add_usage(usage, &records);
+            }
+
+            if let Err(e) = database.add_records(user, &records).await {
+                error!("failed to add record: {}", e);
+
+                return Err(ErrorResponse::reply("failed to add record")
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+
+            notifier.notify(user.id, &records);
+            uploaded += records.len() as u64;
+        }
+    }
+
+    decoder.finish().map_err(invalid)?;
+
+    Ok(uploaded)
+}
+
+/// Send the records in each of the requested ranges, as a record stream
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn stream_download<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    Json(req): Json<StreamDownloadRequest>,
+) -> Response {
+    let State(AppState { database, .. }) = state;
+    let (sender, receiver) = mpsc::channel(4);
+
+    tokio::spawn(async move {
+        for range in req.ranges {
+            match send_range(&database, &user, range, &sender).await {
+                Ok(true) => continue,
+                // the client has gone away
+                Ok(false) => return,
+                Err(e) => {
+                    error!("failed to stream records: {}", e);
+
+                    // so the client sees an error, rather than a stream that ends early
+                    let _ = sender
+                        .send(Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            "failed to stream records",
+                        )))
+                        .await;
+                    return;
+                }
+            }
+        }
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|frames| (frames, receiver))
+    });
+
+    (
+        [(CONTENT_TYPE, stream::CONTENT_TYPE)],
+        StreamBody::new(body),
+    )
+        .into_response()
+}
+
+/// Returns false if the client stopped listening
+async fn send_range<DB: Database>(
+    database: &DB,
+    user: &User,
+    range: RecordRange,
+    sender: &mpsc::Sender<io::Result<Bytes>>,
+) -> eyre::Result<bool> {
+    let mut start = range.start;
+
+    while start < range.end {
+        let count = (range.end - start).min(stream::BATCH_RECORDS as u64);
+
+        let records = database
+            .next_records(user, range.host, range.tag.clone(), Some(start), count)
+            .await?;
+
+        let Some(last) = records.last() else {
+            break;
+        };
+        start = last.idx + 1;
+
+        let records: Vec<_> = records.into_iter().filter(|r| r.idx < range.end).collect();
+
+        let mut frames = vec![];
+        stream::encode_batches(&records, &mut frames)?;
+
+        if sender.send(Ok(frames.into())).await.is_err() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Server-sent events, telling a client whenever records are uploaded to the user's account, so it
+/// can sync straight away
+#[instrument(skip_all, fields(user.id = user.id))]
+pub async fn events<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Sse<impl futures_util::Stream<Item = Result<Event, axum::Error>>> {
+    let receiver = state.notifier.subscribe(user.id);
+
+    let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let event: RecordEvent = event;
+                    let event = Event::default()
+                        .event("records")
+                        .json_data(event)
+                        .map_err(axum::Error::new);
+
+                    return Some((event, receiver));
+                }
+                // the events that are left are enough to make the client sync
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    // also lets clients notice a dead connection
+    Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
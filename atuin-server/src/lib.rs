@@ -9,12 +9,17 @@ use axum::Router;
 use axum::Server;
 use axum_server::Handle;
 use eyre::{Context, Result};
+use notify::Notifier;
 
 mod handlers;
 mod metrics;
+mod notify;
+pub(crate) mod quota;
+mod rate_limit;
 mod router;
 mod utils;
 
+pub use handlers::user::{hash_secret, valid_username};
 use rustls::ServerConfig;
 pub use settings::example_config;
 pub use settings::Settings;
@@ -67,12 +72,13 @@ pub async fn launch_with_tcp_listener<Db: Database>(
     listener: TcpListener,
     shutdown: impl Future<Output = ()>,
 ) -> Result<()> {
-    let r = make_router::<Db>(settings).await?;
+    let notifier = Arc::new(Notifier::default());
+    let r = make_router::<Db>(settings, notifier.clone()).await?;
 
     Server::from_tcp(listener)
         .context("could not launch server")?
-        .serve(r.into_make_service())
-        .with_graceful_shutdown(shutdown)
+        .serve(r.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(close_events(shutdown, notifier))
         .await?;
 
     Ok(())
@@ -94,17 +100,18 @@ async fn launch_with_tls<Db: Database>(
     let server_config = Arc::new(server_config);
     let rustls_config = axum_server::tls_rustls::RustlsConfig::from_config(server_config);
 
-    let r = make_router::<Db>(settings).await?;
+    let notifier = Arc::new(Notifier::default());
+    let r = make_router::<Db>(settings, notifier.clone()).await?;
 
     let handle = Handle::new();
 
     let server = axum_server::bind_rustls(addr, rustls_config)
         .handle(handle.clone())
-        .serve(r.into_make_service());
+        .serve(r.into_make_service_with_connect_info::<SocketAddr>());
 
     tokio::select! {
         _ = server => {}
-        _ = shutdown => {
+        _ = close_events(shutdown, notifier) => {
             handle.graceful_shutdown(None);
         }
     }
@@ -133,12 +140,20 @@ pub async fn launch_metrics_server(host: String, port: u16) -> Result<()> {
     Ok(())
 }
 
+/// End event streams when shutting down, as they would otherwise stay open forever and the server
+/// would never finish shutting down
+async fn close_events(shutdown: impl Future<Output = ()>, notifier: Arc<Notifier>) {
+    shutdown.await;
+    notifier.close();
+}
+
 async fn make_router<Db: Database>(
     settings: Settings<<Db as Database>::Settings>,
+    notifier: Arc<Notifier>,
 ) -> Result<Router, eyre::Error> {
     let db = Db::new(&settings.db_settings)
         .await
         .wrap_err_with(|| format!("failed to connect to db: {:?}", settings.db_settings))?;
-    let r = router::router(db, settings);
+    let r = router::router(db, settings, notifier);
     Ok(r)
 }
@@ -50,3 +50,8 @@ pub async fn track_metrics<B>(req: Request<B>, next: Next<B>) -> impl IntoRespon
 
     response
 }
+
+/// Count a request turned away by the rate limiter called `limit`
+pub fn rate_limited(limit: &'static str) {
+    metrics::increment_counter!("http_requests_rate_limited_total", "limit" => limit);
+}
@@ -0,0 +1,107 @@
+// Tells clients when records arrive for their account, so they can sync straight away rather than
+// waiting to poll. Each user with a client listening has a broadcast channel. Channels live in
+// memory, so a client only hears about uploads to the same server process it is connected to.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use atuin_common::{
+    api::RecordEvent,
+    record::{EncryptedData, Record},
+};
+use tokio::sync::broadcast;
+
+/// How many events a slow client can fall behind by. Any more are dropped, which is fine, as one
+/// event is enough to make a client sync everything
+const CHANNEL_SIZE: usize = 16;
+
+#[derive(Default)]
+pub struct Notifier {
+    channels: Mutex<HashMap<i64, broadcast::Sender<RecordEvent>>>,
+}
+
+impl Notifier {
+    pub fn subscribe(&self, user_id: i64) -> broadcast::Receiver<RecordEvent> {
+        let mut channels = self.channels.lock().expect("notifier lock poisoned");
+
+        // forget users whose clients have all gone
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+
+        channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_SIZE).0)
+            .subscribe()
+    }
+
+    /// Stop every client's event stream
+    pub fn close(&self) {
+        self.channels
+            .lock()
+            .expect("notifier lock poisoned")
+            .clear();
+    }
+
+    /// Tell the user's clients about records they just uploaded, once per (host, tag)
+    pub fn notify(&self, user_id: i64, records: &[Record<EncryptedData>]) {
+        let channels = self.channels.lock().expect("notifier lock poisoned");
+
+        let Some(sender) = channels.get(&user_id) else {
+            return;
+        };
+
+        let stores: HashSet<_> = records.iter().map(|r| (r.host.id, &r.tag)).collect();
+
+        for (host, tag) in stores {
+            // fails if nobody is listening, which is fine
+            let _ = sender.send(RecordEvent {
+                host,
+                tag: tag.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::record::{EncryptedData, Host, HostId, Record};
+    use atuin_common::utils::uuid_v7;
+
+    use super::Notifier;
+
+    #[test]
+    fn notify_subscribers() {
+        let notifier = Notifier::default();
+        let host = Host::new(HostId(uuid_v7()));
+
+        let records: Vec<_> = (0..3)
+            .map(|idx| {
+                Record::builder()
+                    .host(host.clone())
+                    .version("v0".to_string())
+                    .tag("history".to_string())
+                    .idx(idx)
+                    .data(EncryptedData {
+                        data: String::new(),
+                        content_encryption_key: String::new(),
+                    })
+                    .build()
+            })
+            .collect();
+
+        let mut mine = notifier.subscribe(1);
+        let mut theirs = notifier.subscribe(2);
+
+        notifier.notify(1, &records);
+
+        // one event for the whole batch
+        let event = mine.try_recv().unwrap();
+        assert_eq!(event.host, host.id);
+        assert_eq!(event.tag, "history");
+        assert!(mine.try_recv().is_err());
+
+        // and nothing for other users
+        assert!(theirs.try_recv().is_err());
+    }
+}
@@ -0,0 +1,335 @@
+// Storage quotas, limiting how much each user can keep on the server.
+// Uploads that would take a user past a limit are refused with a 507 Insufficient Storage, so
+// clients can tell them apart from other failures and stop retrying. Record streams are checked a
+// batch at a time, so the batches before the one that goes over are still stored.
+
+use std::collections::{BTreeMap, HashMap};
+
+use atuin_common::{
+    api::{ErrorResponse, QuotaUsage, StorageUsage, UsageResponse},
+    record::{EncryptedData, Record},
+};
+use atuin_server_database::models::{NewHistory, Stored, Usage};
+use http::StatusCode;
+
+use crate::{
+    handlers::ErrorResponseStatus,
+    settings::{Quota, QuotaLimit},
+};
+
+impl QuotaLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.count == 0 && self.bytes == 0
+    }
+
+    fn check(&self, what: &str, used: Stored, adding: Stored) -> Result<(), QuotaExceeded> {
+        // adding nothing is fine, even if the user is already over a limit that was lowered
+        let over =
+            |limit: u64, used: u64, adding: u64| limit != 0 && adding != 0 && used + adding > limit;
+
+        if over(self.count, used.count, adding.count) {
+            return Err(QuotaExceeded(format!(
+                "storage quota exceeded: you can store {} {what}, and have {}, so can't add {} more",
+                self.count, used.count, adding.count
+            )));
+        }
+
+        if over(self.bytes, used.bytes, adding.bytes) {
+            return Err(QuotaExceeded(format!(
+                "storage quota exceeded: you can store {} bytes of {what}, and have {}, so can't add {} more",
+                self.bytes, used.bytes, adding.bytes
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn usage(&self, used: Stored) -> StorageUsage {
+        let limit = |limit: u64| (limit != 0).then_some(limit);
+
+        StorageUsage {
+            count: QuotaUsage {
+                used: used.count,
+                limit: limit(self.count),
+            },
+            bytes: QuotaUsage {
+                used: used.bytes,
+                limit: limit(self.bytes),
+            },
+        }
+    }
+}
+
+impl Quota {
+    /// Whether there are any limits on records, so it's worth looking up usage before adding them
+    pub fn limits_records(&self) -> bool {
+        !self.records.is_unlimited() || self.tags.values().any(|l| !l.is_unlimited())
+    }
+
+    pub fn limits_history(&self) -> bool {
+        !self.history.is_unlimited()
+    }
+
+    pub fn check_records(
+        &self,
+        usage: &Usage,
+        records: &[Record<EncryptedData>],
+    ) -> Result<(), QuotaExceeded> {
+        self.check_adding(usage, by_tag(records))
+    }
+
+    /// Replacing records doesn't change how many there are, only how big they are. `stored` holds
+    /// the user's copies of `records`, as they are before being replaced. Records the user doesn't
+    /// have aren't replaced, so don't count
+    pub fn check_replace(
+        &self,
+        usage: &Usage,
+        stored: &[Record<EncryptedData>],
+        records: &[Record<EncryptedData>],
+    ) -> Result<(), QuotaExceeded> {
+        let key = |r: &Record<EncryptedData>| (r.id, r.host.id, r.tag.clone(), r.idx);
+        let sizes: HashMap<_, u64> = stored
+            .iter()
+            .map(|r| (key(r), r.data.data.len() as u64))
+            .collect();
+
+        // (bytes added, bytes removed) for each tag
+        let mut changes: HashMap<&str, (u64, u64)> = HashMap::new();
+
+        for record in records {
+            if let Some(&size) = sizes.get(&key(record)) {
+                let change = changes.entry(record.tag.as_str()).or_default();
+                change.0 += record.data.data.len() as u64;
+                change.1 += size;
+            }
+        }
+
+        let adding = changes
+            .into_iter()
+            .map(|(tag, (added, removed))| {
+                let bytes = added.saturating_sub(removed);
+                (tag, Stored { count: 0, bytes })
+            })
+            .collect();
+
+        self.check_adding(usage, adding)
+    }
+
+    fn check_adding(
+        &self,
+        usage: &Usage,
+        adding: HashMap<&str, Stored>,
+    ) -> Result<(), QuotaExceeded> {
+        let total = adding.values().fold(Stored::default(), |a, b| a + *b);
+        self.records
+            .check("records", usage.total_records(), total)?;
+
+        for (tag, adding) in adding {
+            if let Some(limit) = self.tags.get(tag) {
+                let used = usage.records.get(tag).copied().unwrap_or_default();
+                limit.check(&format!("{tag} records"), used, adding)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_history(
+        &self,
+        usage: &Usage,
+        history: &[NewHistory],
+    ) -> Result<(), QuotaExceeded> {
+        let adding = Stored {
+            count: history.len() as u64,
+            bytes: history.iter().map(|h| h.data.len() as u64).sum(),
+        };
+
+        self.history.check("history items", usage.history, adding)
+    }
+
+    /// Usage alongside the limits. Tags are listed if they are used or have a limit
+    pub fn usage(&self, usage: &Usage) -> UsageResponse {
+        let mut tags: BTreeMap<String, StorageUsage> = usage
+            .records
+            .iter()
+            .map(|(tag, used)| {
+                let limit = self.tags.get(tag).copied().unwrap_or_default();
+                (tag.clone(), limit.usage(*used))
+            })
+            .collect();
+
+        for (tag, limit) in &self.tags {
+            tags.entry(tag.clone())
+                .or_insert_with(|| limit.usage(Stored::default()));
+        }
+
+        UsageResponse {
+            records: self.records.usage(usage.total_records()),
+            tags,
+            history: self.history.usage(usage.history),
+        }
+    }
+}
+
+fn by_tag(records: &[Record<EncryptedData>]) -> HashMap<&str, Stored> {
+    let mut stored: HashMap<&str, Stored> = HashMap::new();
+
+    for record in records {
+        let tag = stored.entry(record.tag.as_str()).or_default();
+        tag.count += 1;
+        tag.bytes += record.data.data.len() as u64;
+    }
+
+    stored
+}
+
+/// Count `records` in `usage`, once they have been added
+pub fn add_usage(usage: &mut Usage, records: &[Record<EncryptedData>]) {
+    for (tag, adding) in by_tag(records) {
+        let used = usage.records.entry(tag.to_string()).or_default();
+        *used = *used + adding;
+    }
+}
+
+/// An upload would take the user over their quota. Holds a message saying which limit
+#[derive(Debug)]
+pub struct QuotaExceeded(pub String);
+
+impl From<QuotaExceeded> for ErrorResponseStatus<'static> {
+    fn from(e: QuotaExceeded) -> Self {
+        ErrorResponseStatus {
+            error: ErrorResponse { reason: e.0.into() },
+            status: StatusCode::INSUFFICIENT_STORAGE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use atuin_common::record::{EncryptedData, Host, HostId, Record};
+    use atuin_common::utils::uuid_v7;
+    use atuin_server_database::models::{Stored, Usage};
+
+    use crate::settings::{Quota, QuotaLimit};
+
+    fn records(tag: &str, count: usize, size: usize) -> Vec<Record<EncryptedData>> {
+        let host = Host::new(HostId(uuid_v7()));
+
+        (0..count)
+            .map(|idx| {
+                Record::builder()
+                    .host(host.clone())
+                    .version("v0".to_string())
+                    .tag(tag.to_string())
+                    .idx(idx as u64)
+                    .data(EncryptedData {
+                        data: "x".repeat(size),
+                        content_encryption_key: String::new(),
+                    })
+                    .build()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_records() {
+        let quota = Quota {
+            records: QuotaLimit {
+                count: 10,
+                bytes: 0,
+            },
+            tags: HashMap::from([(
+                "kv".to_string(),
+                QuotaLimit {
+                    count: 0,
+                    bytes: 100,
+                },
+            )]),
+            history: QuotaLimit::default(),
+        };
+        assert!(quota.limits_records());
+        assert!(!quota.limits_history());
+
+        let usage = Usage {
+            records: HashMap::from([
+                (
+                    "history".to_string(),
+                    Stored {
+                        count: 5,
+                        bytes: 500,
+                    },
+                ),
+                (
+                    "kv".to_string(),
+                    Stored {
+                        count: 2,
+                        bytes: 60,
+                    },
+                ),
+            ]),
+            history: Stored::default(),
+        };
+
+        // up to the limit is fine
+        assert!(quota
+            .check_records(&usage, &records("history", 3, 100))
+            .is_ok());
+        assert!(quota
+            .check_records(&usage, &records("history", 4, 1))
+            .is_err());
+
+        // as are other tags
+        assert!(quota.check_records(&usage, &records("kv", 2, 20)).is_ok());
+        assert!(quota.check_records(&usage, &records("kv", 2, 21)).is_err());
+
+        let response = quota.usage(&usage);
+        assert_eq!(response.records.count.used, 7);
+        assert_eq!(response.records.count.limit, Some(10));
+        assert_eq!(response.records.bytes.limit, None);
+        assert_eq!(response.tags["kv"].bytes.limit, Some(100));
+        assert_eq!(response.tags["history"].bytes.used, 500);
+    }
+
+    #[test]
+    fn check_replace() {
+        let quota = Quota {
+            records: QuotaLimit {
+                count: 2,
+                bytes: 250,
+            },
+            tags: HashMap::new(),
+            history: QuotaLimit::default(),
+        };
+
+        let stored = records("history", 2, 100);
+        let usage = Usage {
+            records: HashMap::from([(
+                "history".to_string(),
+                Stored {
+                    count: 2,
+                    bytes: 200,
+                },
+            )]),
+            history: Stored::default(),
+        };
+
+        let resized = |size: usize| {
+            let mut records = stored.clone();
+            for r in &mut records {
+                r.data.data = "x".repeat(size);
+            }
+            records
+        };
+
+        // at the count limit, but replacing doesn't add any, and only the growth counts
+        assert!(quota.check_replace(&usage, &stored, &resized(125)).is_ok());
+        assert!(quota.check_replace(&usage, &stored, &resized(126)).is_err());
+        assert!(quota.check_replace(&usage, &stored, &resized(10)).is_ok());
+
+        // records the user doesn't have aren't replaced
+        let missing = records("history", 2, 1000);
+        assert!(quota.check_replace(&usage, &stored, &missing).is_ok());
+    }
+}
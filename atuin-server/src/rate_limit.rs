@@ -0,0 +1,334 @@
+// Rate limiting, to slow down credential stuffing and clients that hammer the server.
+// Every client gets a token bucket, keyed by IP address or username. Each request takes a token,
+// and tokens refill at a steady rate up to the burst size. Buckets live in memory, so limits are per
+// server process, and are forgotten on restart.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use atuin_common::api::ErrorResponse;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::RETRY_AFTER, StatusCode};
+
+use crate::{
+    handlers::{ErrorResponseStatus, RespExt},
+    metrics,
+    settings::Limit,
+};
+
+/// The most buckets to keep, as anyone can make new ones. Once there are this many, the ones that
+/// have refilled are dropped, as they are the same as no bucket at all, and then the least recently
+/// used, until only half are left
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    /// Used to label metrics
+    name: &'static str,
+    burst: f64,
+    /// Tokens added per second
+    rate: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, limit: &Limit) -> Self {
+        Self {
+            name,
+            burst: f64::from(limit.burst.max(1)),
+            rate: f64::from(limit.per_minute) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `key`. If there are none left, returns how long until there will be
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let res = self.check_at(key, Instant::now());
+
+        if res.is_err() {
+            metrics::rate_limited(self.name);
+        }
+
+        res
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().expect("rate limit lock poisoned");
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            self.prune(&mut buckets, now);
+        }
+
+        let tokens = buckets
+            .get(key)
+            .map_or(self.burst, |bucket| self.refill(*bucket, now));
+
+        let (tokens, res) = if tokens >= 1.0 {
+            (tokens - 1.0, Ok(()))
+        } else if self.rate > 0.0 {
+            (
+                tokens,
+                Err(Duration::from_secs_f64((1.0 - tokens) / self.rate)),
+            )
+        } else {
+            (tokens, Err(Duration::MAX))
+        };
+
+        buckets.insert(
+            key.to_string(),
+            Bucket {
+                tokens,
+                updated: now,
+            },
+        );
+
+        res
+    }
+
+    fn prune(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| self.refill(*bucket, now) < self.burst);
+
+        let excess = buckets.len().saturating_sub(MAX_BUCKETS / 2);
+        if excess == 0 {
+            return;
+        }
+
+        let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+        let (_, &mut cutoff, _) = updated.select_nth_unstable(excess - 1);
+
+        buckets.retain(|_, bucket| bucket.updated > cutoff);
+    }
+
+    fn refill(&self, bucket: Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+}
+
+/// Limits requests by the address they come from
+#[derive(Clone)]
+pub struct IpRateLimit {
+    pub limiter: Arc<RateLimiter>,
+    /// Read the client's address from this header, rather than the connection, for servers
+    /// behind a proxy
+    pub ip_header: Option<String>,
+    /// How many proxies in front of the server add to `ip_header`
+    pub trusted_proxies: usize,
+}
+
+impl IpRateLimit {
+    fn client_ip<B>(&self, req: &Request<B>) -> Option<String> {
+        self.forwarded_ip(req).or_else(|| {
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+    }
+
+    /// The client's address from `ip_header`. Each proxy appends the address it got the request
+    /// from, so only the last `trusted_proxies` entries are theirs. Anything before those was sent
+    /// by the client, and could be made up
+    fn forwarded_ip<B>(&self, req: &Request<B>) -> Option<String> {
+        let header = self.ip_header.as_ref()?;
+
+        let entries: Vec<&str> = req
+            .headers()
+            .get_all(header)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+
+        let ip = entries
+            .iter()
+            .rev()
+            .nth(self.trusted_proxies.checked_sub(1)?)?;
+
+        ip.trim().parse::<IpAddr>().ok().map(|ip| ip.to_string())
+    }
+}
+
+/// Middleware applying an `IpRateLimit`
+pub async fn limit_ip<B>(
+    State(limit): State<IpRateLimit>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    // if we can't tell who the client is, there's nothing to limit them by
+    if let Some(ip) = limit.client_ip(&req) {
+        if let Err(wait) = limit.limiter.check(&ip) {
+            return TooManyRequests(wait).into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+/// A `429 Too Many Requests` response, saying when to try again
+pub struct TooManyRequests(pub Duration);
+
+impl IntoResponse for TooManyRequests {
+    fn into_response(self) -> Response {
+        let seconds = self.0.as_secs().saturating_add(1);
+
+        let mut response = ErrorResponseStatus {
+            error: ErrorResponse::reply("too many requests, try again later"),
+            status: StatusCode::TOO_MANY_REQUESTS,
+        }
+        .into_response();
+
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, seconds.to_string().parse().unwrap());
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use axum::{extract::ConnectInfo, http::Request};
+
+    use super::{IpRateLimit, RateLimiter, MAX_BUCKETS};
+    use crate::settings::Limit;
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new(
+            "test",
+            &Limit {
+                burst: 3,
+                per_minute: 6,
+            },
+        );
+        let start = Instant::now();
+
+        // the burst is allowed, then nothing more
+        for _ in 0..3 {
+            assert!(limiter.check_at("a", start).is_ok());
+        }
+        let wait = limiter.check_at("a", start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(10));
+
+        // other keys have their own bucket
+        assert!(limiter.check_at("b", start).is_ok());
+
+        // one token every 10 seconds
+        let later = start + Duration::from_secs(10);
+        assert!(limiter.check_at("a", later).is_ok());
+        assert!(limiter.check_at("a", later).is_err());
+
+        // and never more than the burst
+        let much_later = start + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert!(limiter.check_at("a", much_later).is_ok());
+        }
+        assert!(limiter.check_at("a", much_later).is_err());
+    }
+
+    #[test]
+    fn bucket_limit() {
+        let limiter = RateLimiter::new(
+            "test",
+            &Limit {
+                burst: 1,
+                per_minute: 1,
+            },
+        );
+        let start = Instant::now();
+
+        for i in 0..MAX_BUCKETS {
+            let now = start + Duration::from_millis(i as u64);
+            assert!(limiter.check_at(&i.to_string(), now).is_ok());
+        }
+        assert!(limiter.check_at("0", start).is_err());
+
+        // a new key makes room by dropping the least recently used half
+        let now = start + Duration::from_secs(20);
+        assert!(limiter.check_at("new", now).is_ok());
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), MAX_BUCKETS / 2 + 1);
+        assert!(!buckets.contains_key("1"));
+        assert!(buckets.contains_key(&(MAX_BUCKETS - 1).to_string()));
+    }
+
+    #[test]
+    fn forwarded_ip() {
+        let limit = |ip_header: Option<&str>, trusted_proxies| IpRateLimit {
+            limiter: Arc::new(RateLimiter::new(
+                "test",
+                &Limit {
+                    burst: 1,
+                    per_minute: 1,
+                },
+            )),
+            ip_header: ip_header.map(String::from),
+            trusted_proxies,
+        };
+        let request = |forwarded: Option<&str>| {
+            let mut req = Request::builder()
+                .extension(ConnectInfo("10.0.0.2:443".parse::<SocketAddr>().unwrap()));
+            if let Some(forwarded) = forwarded {
+                req = req.header("X-Forwarded-For", forwarded);
+            }
+            req.body(()).unwrap()
+        };
+
+        // the client can put anything at the start, but only the proxy's entry counts
+        let spoofed = request(Some("1.1.1.1, 2.2.2.2, 203.0.113.7"));
+        let ip = |ip_header, trusted_proxies, req: &Request<()>| {
+            limit(ip_header, trusted_proxies).client_ip(req)
+        };
+
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 1, &spoofed).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 2, &spoofed).as_deref(),
+            Some("2.2.2.2")
+        );
+
+        // without a trusted proxy, the header is ignored
+        assert_eq!(ip(None, 1, &spoofed).as_deref(), Some("10.0.0.2"));
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 0, &spoofed).as_deref(),
+            Some("10.0.0.2")
+        );
+
+        // and so are headers the proxies can't have set
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 4, &spoofed).as_deref(),
+            Some("10.0.0.2")
+        );
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 1, &request(None)).as_deref(),
+            Some("10.0.0.2")
+        );
+        assert_eq!(
+            ip(Some("X-Forwarded-For"), 1, &request(Some("nonsense"))).as_deref(),
+            Some("10.0.0.2")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use atuin_common::api::{ErrorResponse, ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION};
 use axum::{
@@ -5,7 +7,7 @@ use axum::{
     http::Request,
     middleware::Next,
     response::{IntoResponse, Response},
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use eyre::Result;
@@ -17,12 +19,60 @@ use super::handlers;
 use crate::{
     handlers::{ErrorResponseStatus, RespExt},
     metrics,
+    notify::Notifier,
+    rate_limit::{self, IpRateLimit, RateLimiter},
     settings::Settings,
 };
-use atuin_server_database::{models::User, Database, DbError};
+use atuin_server_database::{
+    models::{Session, User},
+    Database, DbError,
+};
 
 pub struct UserAuth(pub User);
 
+/// Like `UserAuth`, for handlers that also need the session making the request
+pub struct SessionAuth(pub User, pub Session);
+
+fn auth_token(req: &Parts) -> Result<&str, ErrorResponseStatus<'static>> {
+    let auth_header = req
+        .headers
+        .get(http::header::AUTHORIZATION)
+        .ok_or_else(|| {
+            ErrorResponse::reply("missing authorization header")
+                .with_status(http::StatusCode::BAD_REQUEST)
+        })?;
+    let auth_header = auth_header.to_str().map_err(|_| {
+        ErrorResponse::reply("invalid authorization header encoding")
+            .with_status(http::StatusCode::BAD_REQUEST)
+    })?;
+    let (typ, token) = auth_header.split_once(' ').ok_or_else(|| {
+        ErrorResponse::reply("invalid authorization header encoding")
+            .with_status(http::StatusCode::BAD_REQUEST)
+    })?;
+
+    if typ != "Token" {
+        return Err(
+            ErrorResponse::reply("invalid authorization header encoding")
+                .with_status(http::StatusCode::BAD_REQUEST),
+        );
+    }
+
+    Ok(token)
+}
+
+fn session_error(e: DbError) -> ErrorResponseStatus<'static> {
+    match e {
+        DbError::NotFound => {
+            ErrorResponse::reply("session not found").with_status(http::StatusCode::FORBIDDEN)
+        }
+        DbError::Other(e) => {
+            tracing::error!(error = ?e, "could not query user session");
+            ErrorResponse::reply("could not query user session")
+                .with_status(http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 #[async_trait]
 impl<DB: Send + Sync> FromRequestParts<AppState<DB>> for UserAuth
 where
@@ -34,47 +84,51 @@ where
         req: &mut Parts,
         state: &AppState<DB>,
     ) -> Result<Self, Self::Rejection> {
-        let auth_header = req
-            .headers
-            .get(http::header::AUTHORIZATION)
-            .ok_or_else(|| {
-                ErrorResponse::reply("missing authorization header")
-                    .with_status(http::StatusCode::BAD_REQUEST)
-            })?;
-        let auth_header = auth_header.to_str().map_err(|_| {
-            ErrorResponse::reply("invalid authorization header encoding")
-                .with_status(http::StatusCode::BAD_REQUEST)
-        })?;
-        let (typ, token) = auth_header.split_once(' ').ok_or_else(|| {
-            ErrorResponse::reply("invalid authorization header encoding")
-                .with_status(http::StatusCode::BAD_REQUEST)
-        })?;
-
-        if typ != "Token" {
-            return Err(
-                ErrorResponse::reply("invalid authorization header encoding")
-                    .with_status(http::StatusCode::BAD_REQUEST),
-            );
-        }
+        let token = auth_token(req)?;
 
         let user = state
             .database
             .get_session_user(token)
             .await
-            .map_err(|e| match e {
-                DbError::NotFound => ErrorResponse::reply("session not found")
-                    .with_status(http::StatusCode::FORBIDDEN),
-                DbError::Other(e) => {
-                    tracing::error!(error = ?e, "could not query user session");
-                    ErrorResponse::reply("could not query user session")
-                        .with_status(http::StatusCode::INTERNAL_SERVER_ERROR)
-                }
-            })?;
+            .map_err(session_error)?;
+
+        if user.disabled {
+            return Err(ErrorResponse::reply("this account has been disabled")
+                .with_status(http::StatusCode::FORBIDDEN));
+        }
+
+        // not being able to record when the session was last used shouldn't stop it being used
+        if let Err(e) = state.database.touch_session(token).await {
+            tracing::error!(error = ?e, "could not update session");
+        }
 
         Ok(UserAuth(user))
     }
 }
 
+#[async_trait]
+impl<DB: Send + Sync> FromRequestParts<AppState<DB>> for SessionAuth
+where
+    DB: Database,
+{
+    type Rejection = ErrorResponseStatus<'static>;
+
+    async fn from_request_parts(
+        req: &mut Parts,
+        state: &AppState<DB>,
+    ) -> Result<Self, Self::Rejection> {
+        let UserAuth(user) = UserAuth::from_request_parts(req, state).await?;
+
+        let session = state
+            .database
+            .get_session(auth_token(req)?)
+            .await
+            .map_err(session_error)?;
+
+        Ok(SessionAuth(user, session))
+    }
+}
+
 async fn teapot() -> impl IntoResponse {
     // This used to return 418: 🫖
     // Much as it was fun, it wasn't as useful or informative as it should be
@@ -107,27 +161,87 @@ async fn semver<B>(request: Request<B>, next: Next<B>) -> Response {
 pub struct AppState<DB: Database> {
     pub database: DB,
     pub settings: Settings<DB::Settings>,
+    /// Limits login attempts per username, if rate limiting is enabled
+    pub username_limit: Option<Arc<RateLimiter>>,
+    pub notifier: Arc<Notifier>,
 }
 
-pub fn router<DB: Database>(database: DB, settings: Settings<DB::Settings>) -> Router {
-    let routes = Router::new()
-        .route("/", get(handlers::index))
+pub fn router<DB: Database>(
+    database: DB,
+    settings: Settings<DB::Settings>,
+    notifier: Arc<Notifier>,
+) -> Router {
+    // Routes that check a password, or give away whether a user exists
+    let auth = Router::new()
+        .route("/user/:username", get(handlers::user::get))
+        .route("/register", post(handlers::user::register))
+        .route("/login", post(handlers::user::login))
+        .route("/account/password", patch(handlers::user::change_password))
+        .route("/account/email", patch(handlers::user::change_email));
+
+    let sync = Router::new()
         .route("/sync/count", get(handlers::history::count))
         .route("/sync/history", get(handlers::history::list))
         .route("/sync/calendar/:focus", get(handlers::history::calendar))
         .route("/sync/status", get(handlers::status::status))
         .route("/history", post(handlers::history::add))
         .route("/history", delete(handlers::history::delete))
-        .route("/user/:username", get(handlers::user::get))
         .route("/account", delete(handlers::user::delete))
-        .route("/register", post(handlers::user::register))
-        .route("/login", post(handlers::user::login))
+        .route("/account/sessions", get(handlers::user::sessions))
+        .route(
+            "/account/sessions/:id",
+            delete(handlers::user::revoke_session),
+        )
         .route("/record", post(handlers::record::post::<DB>))
         .route("/record", get(handlers::record::index::<DB>))
         .route("/record/next", get(handlers::record::next))
         .route("/api/v0/record", post(handlers::v0::record::post))
+        .route("/api/v0/record", put(handlers::v0::record::replace))
         .route("/api/v0/record", get(handlers::v0::record::index))
-        .route("/api/v0/record/next", get(handlers::v0::record::next));
+        .route("/api/v0/record/next", get(handlers::v0::record::next))
+        .route("/api/v0/record/events", get(handlers::v0::record::events))
+        .route(
+            "/api/v0/record/stream/upload",
+            post(handlers::v0::record::stream_upload),
+        )
+        .route(
+            "/api/v0/record/stream/download",
+            post(handlers::v0::record::stream_download),
+        )
+        .route("/api/v0/me/usage", get(handlers::v0::me::usage));
+
+    let limits = &settings.rate_limit;
+    let ip_limit = |name, limit| IpRateLimit {
+        limiter: Arc::new(RateLimiter::new(name, limit)),
+        ip_header: limits.ip_header.clone(),
+        trusted_proxies: limits.trusted_proxies,
+    };
+
+    let (auth, sync, username_limit) = if limits.enable {
+        let auth = auth.route_layer(axum::middleware::from_fn_with_state(
+            ip_limit("auth", &limits.auth),
+            rate_limit::limit_ip,
+        ));
+
+        let sync = match &limits.sync {
+            Some(limit) => sync.route_layer(axum::middleware::from_fn_with_state(
+                ip_limit("sync", limit),
+                rate_limit::limit_ip,
+            )),
+            None => sync,
+        };
+
+        let username_limit = Arc::new(RateLimiter::new("username", &limits.auth));
+
+        (auth, sync, Some(username_limit))
+    } else {
+        (auth, sync, None)
+    };
+
+    let routes = Router::new()
+        .route("/", get(handlers::index))
+        .merge(auth)
+        .merge(sync);
 
     let path = settings.path.as_str();
     if path.is_empty() {
@@ -136,7 +250,12 @@ pub fn router<DB: Database>(database: DB, settings: Settings<DB::Settings>) -> R
         Router::new().nest(path, routes)
     }
     .fallback(teapot)
-    .with_state(AppState { database, settings })
+    .with_state(AppState {
+        database,
+        settings,
+        username_limit,
+        notifier,
+    })
     .layer(
         ServiceBuilder::new()
             .layer(axum::middleware::from_fn(clacks_overhead))
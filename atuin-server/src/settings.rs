@@ -1,4 +1,4 @@
-use std::{io::prelude::*, path::PathBuf};
+use std::{collections::HashMap, io::prelude::*, path::PathBuf};
 
 use config::{Config, Environment, File as ConfigFile, FileFormat};
 use eyre::{bail, eyre, Context, Result};
@@ -24,6 +24,64 @@ impl Default for Metrics {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Limit {
+    /// How many requests can be made at once
+    pub burst: u32,
+    /// How quickly more requests are allowed after the burst is used up
+    pub per_minute: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub enable: bool,
+    /// Take the client's address from this header, such as `X-Forwarded-For`, rather than from
+    /// the connection. Only set this behind a proxy that sets the header, or clients can pick their
+    /// own address
+    pub ip_header: Option<String>,
+    /// How many proxies in front of the server add to `ip_header`. The client's address is the
+    /// entry added by the first of them, as the ones before it come from the client
+    pub trusted_proxies: usize,
+    /// Limits logging in, registering and changing account details, per address and per username
+    pub auth: Limit,
+    /// Limits all the other authenticated routes per address, if set
+    pub sync: Option<Limit>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            ip_header: None,
+            trusted_proxies: 1,
+            auth: Limit {
+                burst: 10,
+                per_minute: 10,
+            },
+            sync: None,
+        }
+    }
+}
+
+/// How much a user can store. 0 means no limit
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QuotaLimit {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Quota {
+    /// Limits all of a user's records together
+    pub records: QuotaLimit,
+    /// Limits a user's records with each tag, such as `history`
+    pub tags: HashMap<String, QuotaLimit>,
+    /// Limits history uploaded by clients that don't use record sync
+    pub history: QuotaLimit,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Settings<DbSettings> {
     pub host: String,
@@ -33,10 +91,15 @@ pub struct Settings<DbSettings> {
     pub max_history_length: usize,
     pub max_record_size: usize,
     pub page_size: i64,
+    /// How many days a session lasts for, after logging in. 0 means until it is revoked
+    pub session_expiry_days: u64,
     pub register_webhook_url: Option<String>,
     pub register_webhook_username: String,
     pub metrics: Metrics,
     pub tls: Tls,
+    pub rate_limit: RateLimit,
+    #[serde(default)]
+    pub quota: Quota,
 
     #[serde(flatten)]
     pub db_settings: DbSettings,
@@ -65,12 +128,17 @@ impl<DbSettings: DeserializeOwned> Settings<DbSettings> {
             .set_default("path", "")?
             .set_default("register_webhook_username", "")?
             .set_default("page_size", 1100)?
+            .set_default("session_expiry_days", 0)?
             .set_default("metrics.enable", false)?
             .set_default("metrics.host", "127.0.0.1")?
             .set_default("metrics.port", 9001)?
             .set_default("tls.enable", false)?
             .set_default("tls.cert_path", "")?
             .set_default("tls.pkey_path", "")?
+            .set_default("rate_limit.enable", false)?
+            .set_default("rate_limit.trusted_proxies", 1)?
+            .set_default("rate_limit.auth.burst", 10)?
+            .set_default("rate_limit.auth.per_minute", 10)?
             .add_source(
                 Environment::with_prefix("atuin")
                     .prefix_separator("_")
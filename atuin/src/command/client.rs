@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use eyre::{Result, WrapErr};
+use serde::Serialize;
 
 use atuin_client::{database::Sqlite, record::sqlite_store::SqliteStore, settings::Settings};
 use env_logger::Builder;
@@ -13,6 +14,7 @@ mod sync;
 mod account;
 
 mod config;
+mod daemon;
 mod history;
 mod import;
 mod kv;
@@ -20,6 +22,20 @@ mod record;
 mod search;
 mod stats;
 
+/// How a command prints what it found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Text, for people to read
+    Human,
+    /// JSON, for scripts
+    Json,
+}
+
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
 #[derive(Subcommand, Debug)]
 #[command(infer_subcommands = true)]
 pub enum Cmd {
@@ -44,6 +60,9 @@ pub enum Cmd {
     #[cfg(feature = "sync")]
     Account(account::Cmd),
 
+    /// Run in the background, saving and syncing history for the shell hooks
+    Daemon(daemon::Cmd),
+
     #[command(subcommand)]
     Kv(kv::Cmd),
 
@@ -79,6 +98,15 @@ impl Cmd {
 
         let mut settings = Settings::new().wrap_err("could not load client settings")?;
 
+        // history start and end run for every command, so leave the databases to the daemon if
+        // it's running
+        #[cfg(unix)]
+        if let Self::History(history) = &self {
+            if history.run_with_daemon(&settings).await {
+                return Ok(());
+            }
+        }
+
         let db_path = PathBuf::from(settings.db_path.as_str());
         let record_store_path = PathBuf::from(settings.record_store_path.as_str());
 
@@ -89,14 +117,16 @@ impl Cmd {
             Self::History(history) => history.run(&settings, &db, store).await,
             Self::Import(import) => import.run(&db).await,
             Self::Stats(stats) => stats.run(&db, &settings).await,
-            Self::Search(search) => search.run(db, &mut settings).await,
+            Self::Search(search) => search.run(db, &mut settings, store).await,
 
             #[cfg(feature = "sync")]
-            Self::Sync(sync) => sync.run(settings, &db, &store).await,
+            Self::Sync(sync) => sync.run(settings, &db, store).await,
 
             #[cfg(feature = "sync")]
             Self::Account(account) => account.run(settings).await,
 
+            Self::Daemon(daemon) => daemon.run(&settings, &db, store).await,
+
             Self::Kv(kv) => kv.run(&settings, &store).await,
 
             Self::Record(record) => record.run(&settings, &store).await,
@@ -3,10 +3,13 @@ use eyre::Result;
 
 use atuin_client::settings::Settings;
 
+pub mod change_email;
+pub mod change_password;
 pub mod delete;
 pub mod login;
 pub mod logout;
 pub mod register;
+pub mod sessions;
 
 #[derive(Args, Debug)]
 pub struct Cmd {
@@ -27,6 +30,15 @@ pub enum Commands {
 
     // Delete your account, and all synced data
     Delete,
+
+    /// Change your password. This logs out all of your other machines
+    ChangePassword(change_password::Cmd),
+
+    /// Change the email address of your account
+    ChangeEmail(change_email::Cmd),
+
+    /// List the machines logged in to your account, or log one out
+    Sessions(sessions::Cmd),
 }
 
 impl Cmd {
@@ -36,6 +48,9 @@ impl Cmd {
             Commands::Register(r) => r.run(&settings).await,
             Commands::Logout => logout::run(&settings),
             Commands::Delete => delete::run(&settings).await,
+            Commands::ChangePassword(c) => c.run(&settings).await,
+            Commands::ChangeEmail(c) => c.run(&settings).await,
+            Commands::Sessions(c) => c.run(&settings).await,
         }
     }
 }
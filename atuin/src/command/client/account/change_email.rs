@@ -0,0 +1,39 @@
+use clap::Parser;
+use eyre::{bail, Result};
+
+use atuin_client::{api_client, settings::Settings};
+
+use super::login::{or_user_input, read_user_password};
+
+#[derive(Parser, Debug)]
+pub struct Cmd {
+    #[clap(long, short)]
+    pub email: Option<String>,
+
+    #[clap(long, short)]
+    pub password: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        if !settings.logged_in() {
+            bail!("You are not logged in");
+        }
+
+        let email = or_user_input(&self.email, "new email");
+        let password = self.password.unwrap_or_else(read_user_password);
+
+        let client = api_client::Client::new(
+            &settings.sync_address,
+            &settings.session_token,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+        )?;
+
+        client.change_email(password, email).await?;
+
+        println!("Your email has been changed");
+
+        Ok(())
+    }
+}
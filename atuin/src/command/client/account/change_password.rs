@@ -0,0 +1,60 @@
+use clap::Parser;
+use eyre::{bail, Result};
+use rpassword::prompt_password;
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use atuin_client::{api_client, settings::Settings};
+
+#[derive(Parser, Debug)]
+pub struct Cmd {
+    #[clap(long, short)]
+    pub current_password: Option<String>,
+
+    #[clap(long, short)]
+    pub new_password: Option<String>,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        if !settings.logged_in() {
+            bail!("You are not logged in");
+        }
+
+        let current_password = self
+            .current_password
+            .map_or_else(|| prompt_password("Please enter current password: "), Ok)?;
+
+        let new_password = if let Some(password) = self.new_password {
+            password
+        } else {
+            let password = prompt_password("Please enter new password: ")?;
+            if prompt_password("Please repeat new password: ")? != password {
+                bail!("the new passwords do not match");
+            }
+            password
+        };
+
+        if new_password.is_empty() {
+            bail!("please provide a new password");
+        }
+
+        let client = api_client::Client::new(
+            &settings.sync_address,
+            &settings.session_token,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+        )?;
+
+        let session = client
+            .change_password(current_password, new_password)
+            .await?;
+
+        // the old session has been ended
+        let mut file = File::create(settings.session_path.as_str()).await?;
+        file.write_all(session.session.as_bytes()).await?;
+
+        println!("Your password has been changed. Log in again on your other machines");
+
+        Ok(())
+    }
+}
@@ -6,7 +6,7 @@ use tokio::{fs::File, io::AsyncWriteExt};
 
 use atuin_client::{
     api_client,
-    encryption::{decode_key, encode_key, new_key, Key},
+    encryption::{decode_key, encode_key, new_key, protect::is_protected, Key},
     settings::Settings,
 };
 use atuin_common::api::LoginRequest;
@@ -52,7 +52,7 @@ impl Cmd {
             if PathBuf::from(key_path).exists() {
                 let bytes = fs_err::read_to_string(key_path)
                     .context("existing key file couldn't be read")?;
-                if decode_key(bytes).is_err() {
+                if !is_protected(&bytes) && decode_key(bytes).is_err() {
                     bail!("the key in existing key file was invalid");
                 }
             } else {
@@ -60,33 +60,7 @@ impl Cmd {
                 let _key = new_key(settings)?;
             }
         } else {
-            // try parse the key as a mnemonic...
-            let key = match bip39::Mnemonic::from_phrase(&key, bip39::Language::English) {
-                Ok(mnemonic) => encode_key(Key::from_slice(mnemonic.entropy()))?,
-                Err(err) => {
-                    if let Some(err) = err.downcast_ref::<bip39::ErrorKind>() {
-                        match err {
-                            // assume they copied in the base64 key
-                            bip39::ErrorKind::InvalidWord => key,
-                            bip39::ErrorKind::InvalidChecksum => {
-                                bail!("key mnemonic was not valid")
-                            }
-                            bip39::ErrorKind::InvalidKeysize(_)
-                            | bip39::ErrorKind::InvalidWordLength(_)
-                            | bip39::ErrorKind::InvalidEntropyLength(_, _) => {
-                                bail!("key was not the correct length")
-                            }
-                        }
-                    } else {
-                        // unknown error. assume they copied the base64 key
-                        key
-                    }
-                }
-            };
-
-            if decode_key(key.clone()).is_err() {
-                bail!("the specified key was invalid");
-            }
+            let key = parse_key(key)?;
 
             let mut file = File::create(key_path).await?;
             file.write_all(key.as_bytes()).await?;
@@ -94,7 +68,11 @@ impl Cmd {
 
         let session = api_client::login(
             settings.sync_address.as_str(),
-            LoginRequest { username, password },
+            LoginRequest {
+                username,
+                password,
+                device: whoami::hostname(),
+            },
         )
         .await?;
 
@@ -108,6 +86,39 @@ impl Cmd {
     }
 }
 
+/// Parse a key given as either a mnemonic or base64, as printed by `atuin key`, into base64
+pub fn parse_key(key: String) -> Result<String> {
+    // try parse the key as a mnemonic...
+    let key = match bip39::Mnemonic::from_phrase(&key, bip39::Language::English) {
+        Ok(mnemonic) => encode_key(Key::from_slice(mnemonic.entropy()))?,
+        Err(err) => {
+            if let Some(err) = err.downcast_ref::<bip39::ErrorKind>() {
+                match err {
+                    // assume they copied in the base64 key
+                    bip39::ErrorKind::InvalidWord => key,
+                    bip39::ErrorKind::InvalidChecksum => {
+                        bail!("key mnemonic was not valid")
+                    }
+                    bip39::ErrorKind::InvalidKeysize(_)
+                    | bip39::ErrorKind::InvalidWordLength(_)
+                    | bip39::ErrorKind::InvalidEntropyLength(_, _) => {
+                        bail!("key was not the correct length")
+                    }
+                }
+            } else {
+                // unknown error. assume they copied the base64 key
+                key
+            }
+        }
+    };
+
+    if decode_key(key.clone()).is_err() {
+        bail!("the specified key was invalid");
+    }
+
+    Ok(key)
+}
+
 pub(super) fn or_user_input(value: &'_ Option<String>, name: &'static str) -> String {
     value.clone().unwrap_or_else(|| read_user_input(name))
 }
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use eyre::{bail, Result};
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
+
+use atuin_client::{api_client, settings::Settings};
+
+static TIME_FMT: &[time::format_description::FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour repr:24]:[minute]:[second]");
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cmd {
+    #[command(subcommand)]
+    cmd: Option<Subcmd>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Subcmd {
+    /// Revoke a session, logging out the machine using it. Use the id from `atuin account sessions`
+    Revoke { id: i64 },
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        if !settings.logged_in() {
+            bail!("You are not logged in");
+        }
+
+        let client = api_client::Client::new(
+            &settings.sync_address,
+            &settings.session_token,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+        )?;
+
+        match self.cmd {
+            None => list(settings, &client).await,
+            Some(Subcmd::Revoke { id }) => revoke(settings, &client, id).await,
+        }
+    }
+}
+
+async fn list(settings: &Settings, client: &api_client::Client<'_>) -> Result<()> {
+    let sessions = client.sessions().await?;
+
+    let tz = settings.local_tz.unwrap_or(UtcOffset::UTC);
+    let format = |time: Option<OffsetDateTime>| -> Result<String> {
+        match time {
+            Some(time) => Ok(time.to_offset(tz).format(TIME_FMT)?),
+            None => Ok("-".to_string()),
+        }
+    };
+
+    let device_width = sessions
+        .iter()
+        .map(|s| s.device.len())
+        .max()
+        .unwrap_or_default()
+        .max("DEVICE".len());
+
+    println!(
+        "  {:>6}  {:<device_width$}  {:<19}  {:<19}  EXPIRES",
+        "ID", "DEVICE", "CREATED", "LAST USED"
+    );
+
+    for session in sessions {
+        // the session this machine is using
+        let marker = if session.current { "*" } else { " " };
+
+        println!(
+            "{marker} {:>6}  {:<device_width$}  {:<19}  {:<19}  {}",
+            session.id,
+            session.device,
+            format(Some(session.created_at))?,
+            format(session.last_used_at)?,
+            format(session.expires_at)?,
+        );
+    }
+
+    Ok(())
+}
+
+async fn revoke(settings: &Settings, client: &api_client::Client<'_>, id: i64) -> Result<()> {
+    let current = client
+        .sessions()
+        .await?
+        .into_iter()
+        .any(|s| s.id == id && s.current);
+
+    client.revoke_session(id).await?;
+
+    println!("Revoked session {id}");
+
+    if current {
+        // the session file is no use now
+        let session_path = PathBuf::from(&settings.session_path);
+        if session_path.exists() {
+            fs_err::remove_file(session_path)?;
+        }
+
+        println!("That was this machine's session, so you have been logged out");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+// A long running process that takes work off the shell hooks. `history start` and `end` hand
+// their entries to it over a Unix socket, rather than opening the databases for every command, and
+// it writes them in batches. It also syncs as soon as there is something to sync, and keeps history
+// in memory for searching.
+//
+// The protocol is JSON, one request and one response per line.
+
+use std::sync::Arc;
+
+use clap::Parser;
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use atuin_client::{
+    database::{Context, Database},
+    encryption,
+    export::HistoryJson,
+    history::store::HistoryStore,
+    record::sqlite_store::SqliteStore,
+    settings::{FilterMode, Settings},
+};
+
+#[cfg(unix)]
+pub mod client;
+#[cfg(unix)]
+mod server;
+#[cfg(feature = "sync")]
+mod sync;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// A command has started
+    Start { history: HistoryJson },
+
+    /// A command has finished
+    End {
+        id: String,
+        exit: i64,
+        duration: Option<u64>,
+    },
+
+    /// Fuzzy search history, as `search_mode = "skim"` does
+    Search {
+        query: String,
+        filter_mode: FilterMode,
+        context: Context,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    History { history: Vec<HistoryJson> },
+    Error { message: String },
+}
+
+/// Run in the background, saving and syncing history for the shell hooks
+#[derive(Parser, Debug)]
+pub struct Cmd {}
+
+impl Cmd {
+    pub async fn run(
+        self,
+        settings: &Settings,
+        db: &impl Database,
+        store: SqliteStore,
+    ) -> Result<()> {
+        let host_id = Settings::host_id().expect("failed to get host_id");
+        let encryption_key: [u8; 32] = encryption::load_key(settings)
+            .context("could not load encryption key")?
+            .into();
+
+        // the writer says when there's new history to upload, and sync when it downloaded some
+        let written = Arc::new(Notify::new());
+        let downloaded = Arc::new(Notify::new());
+
+        #[cfg(unix)]
+        let serve = server::serve(
+            settings,
+            db,
+            HistoryStore::new(store.clone(), host_id, encryption_key),
+            written.clone(),
+            downloaded.clone(),
+        );
+
+        // there are no Unix sockets to listen on, so all the daemon can do is sync
+        #[cfg(not(unix))]
+        let serve = std::future::pending::<Result<()>>();
+
+        #[cfg(feature = "sync")]
+        let sync = sync::run(
+            settings,
+            db,
+            HistoryStore::new(store.clone(), host_id, encryption_key),
+            store,
+            written,
+            downloaded,
+        );
+
+        #[cfg(not(feature = "sync"))]
+        let sync = std::future::pending::<Result<()>>();
+
+        // syncing never stops, but serving does, when asked to shut down. Serve first, so a
+        // daemon that's already running is found before anything else starts
+        tokio::select! {
+            biased;
+
+            res = serve => res,
+            res = sync => res,
+        }
+    }
+}
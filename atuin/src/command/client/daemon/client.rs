@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use eyre::{bail, eyre, Result};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    time::timeout,
+};
+
+use atuin_client::{database::Context, history::History, settings::FilterMode};
+
+use super::{Request, Response};
+
+/// Shell hooks wait this long at most, so a stuck daemon can't hang the shell
+const HOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Searching all of history takes longer than saving an entry
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Client {
+    stream: BufReader<UnixStream>,
+}
+
+impl Client {
+    /// Connect to the daemon listening on `socket_path`, or None if it isn't running
+    pub async fn connect(socket_path: &str) -> Option<Self> {
+        let stream = UnixStream::connect(socket_path).await.ok()?;
+
+        Some(Self {
+            stream: BufReader::new(stream),
+        })
+    }
+
+    async fn send(&mut self, request: &Request, wait: Duration) -> Result<Response> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+
+        let response = timeout(wait, async {
+            self.stream.get_mut().write_all(line.as_bytes()).await?;
+
+            let mut response = String::new();
+            if self.stream.read_line(&mut response).await? == 0 {
+                bail!("the daemon closed the connection");
+            }
+
+            Ok(response)
+        })
+        .await
+        .map_err(|_| eyre!("the daemon didn't respond within {wait:?}"))??;
+
+        match serde_json::from_str(&response)? {
+            Response::Error { message } => bail!("daemon error: {message}"),
+            response => Ok(response),
+        }
+    }
+
+    pub async fn start(&mut self, history: &History) -> Result<()> {
+        let request = Request::Start {
+            history: history.into(),
+        };
+
+        self.send(&request, HOOK_TIMEOUT).await?;
+
+        Ok(())
+    }
+
+    pub async fn end(&mut self, id: &str, exit: i64, duration: Option<u64>) -> Result<()> {
+        let request = Request::End {
+            id: id.to_string(),
+            exit,
+            duration,
+        };
+
+        self.send(&request, HOOK_TIMEOUT).await?;
+
+        Ok(())
+    }
+
+    pub async fn search(
+        &mut self,
+        query: &str,
+        filter_mode: FilterMode,
+        context: &Context,
+    ) -> Result<Vec<History>> {
+        let request = Request::Search {
+            query: query.to_string(),
+            filter_mode,
+            context: context.clone(),
+        };
+
+        match self.send(&request, SEARCH_TIMEOUT).await? {
+            Response::History { history } => Ok(history.into_iter().map(History::from).collect()),
+            response => bail!("unexpected response from the daemon: {response:?}"),
+        }
+    }
+}
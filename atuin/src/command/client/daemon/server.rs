@@ -0,0 +1,588 @@
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eyre::{bail, eyre, Result, WrapErr};
+use fs_err as fs;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use log::{debug, warn};
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc, oneshot, Notify},
+    time::{interval, MissedTickBehavior},
+};
+
+use atuin_client::{
+    database::{Context, Database},
+    export::HistoryJson,
+    history::{store::HistoryStore, History},
+    settings::{FilterMode, SearchMode, Settings},
+};
+
+use super::{Request, Response};
+use crate::command::client::{
+    history::finish,
+    search::engines::{skim::fuzzy_search, SearchState},
+};
+
+/// Save waiting history at least this often
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Or as soon as this much is waiting
+const BATCH_SIZE: usize = 100;
+
+/// Reload the search index this often, to pick up changes made without the daemon, like deletions
+const INDEX_REFRESH: Duration = Duration::from_secs(5 * 60);
+
+/// Commands that haven't finished after this long are forgotten, though they're still in the
+/// database if they do finish
+const RUNNING_EXPIRY: time::Duration = time::Duration::DAY;
+
+type Message = (Request, oneshot::Sender<Response>);
+
+/// Every command and how many times it was run, as `all_with_count` returns. Shared between the
+/// writer, which adds new history to it, and the searcher. Only loaded once something searches it
+type Index = Arc<Mutex<Option<Arc<Vec<(History, i32)>>>>>;
+
+/// Listen on the socket, until asked to shut down
+pub async fn serve(
+    settings: &Settings,
+    db: &impl Database,
+    history_store: HistoryStore,
+    written: Arc<Notify>,
+    downloaded: Arc<Notify>,
+) -> Result<()> {
+    let path = Path::new(&settings.daemon.socket_path);
+    let listener = bind(path).await?;
+    let (to_writer, write_receiver) = mpsc::channel(64);
+    let (to_searcher, search_receiver) = mpsc::channel(64);
+    let index = Index::default();
+
+    let mut writer = Writer {
+        settings,
+        db,
+        history_store,
+        written,
+        running: HashMap::new(),
+        started: Vec::new(),
+        finished: VecDeque::new(),
+        index: index.clone(),
+    };
+
+    let searcher = Searcher {
+        db,
+        downloaded,
+        index,
+        engine: SkimMatcherV2::default(),
+    };
+
+    if settings.search_mode == SearchMode::Skim {
+        searcher.load_index().await?;
+    }
+
+    // searches, and reloading the index for them, are slow enough to hold up the shell hooks, so
+    // they're kept apart from the writer
+    let res = tokio::select! {
+        res = accept(listener, to_writer, to_searcher) => res,
+        res = writer.run(write_receiver) => res,
+        res = searcher.run(search_receiver) => res,
+        res = shutdown_signal() => res,
+    };
+
+    // new history goes straight to the database from now on, so save anything still waiting
+    fs::remove_file(path)?;
+    writer.flush().await?;
+
+    res
+}
+
+async fn bind(path: &Path) -> Result<UnixListener> {
+    // a socket left behind by a daemon that didn't shut down cleanly can be replaced, but not one
+    // that's still in use
+    if UnixStream::connect(path).await.is_ok() {
+        bail!("the daemon is already running, on {}", path.display());
+    }
+
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let listener = UnixListener::bind(path)
+        .wrap_err_with(|| format!("could not listen on {}", path.display()))?;
+
+    // only this user should be able to add to their history
+    fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+async fn shutdown_signal() -> Result<()> {
+    let mut term = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = term.recv() => {},
+        _ = interrupt.recv() => {},
+    };
+
+    Ok(())
+}
+
+async fn accept(
+    listener: UnixListener,
+    to_writer: mpsc::Sender<Message>,
+    to_searcher: mpsc::Sender<Message>,
+) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let to_writer = to_writer.clone();
+        let to_searcher = to_searcher.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, to_writer, to_searcher).await {
+                debug!("daemon connection failed: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    to_writer: mpsc::Sender<Message>,
+    to_searcher: mpsc::Sender<Message>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => {
+                let (reply, response) = oneshot::channel();
+                let sender = match request {
+                    Request::Search { .. } => &to_searcher,
+                    _ => &to_writer,
+                };
+
+                sender
+                    .send((request, reply))
+                    .await
+                    .map_err(|_| eyre!("the daemon is shutting down"))?;
+
+                response.await?
+            }
+            Err(e) => Response::Error {
+                message: format!("invalid request: {e}"),
+            },
+        };
+
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Owns the history waiting to be saved
+struct Writer<'a, DB: Database> {
+    settings: &'a Settings,
+    db: &'a DB,
+    history_store: HistoryStore,
+    written: Arc<Notify>,
+    /// Commands that have started, but not finished, by ID
+    running: HashMap<String, History>,
+    /// Commands that have started, waiting to be saved
+    started: Vec<History>,
+    /// Commands that have finished, waiting to be saved
+    finished: VecDeque<History>,
+    index: Index,
+}
+
+impl<DB: Database> Writer<'_, DB> {
+    async fn run(&mut self, mut receiver: mpsc::Receiver<Message>) -> Result<()> {
+        let mut flush = interval(FLUSH_INTERVAL);
+        flush.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                Some((request, reply)) = receiver.recv() => {
+                    let response = self.handle(request).await.unwrap_or_else(|e| Response::Error {
+                        message: e.to_string(),
+                    });
+
+                    // the client may have given up waiting
+                    let _ = reply.send(response);
+
+                    if self.started.len() + self.finished.len() >= BATCH_SIZE {
+                        self.try_flush().await;
+                    }
+                }
+
+                _ = flush.tick() => self.try_flush().await,
+            }
+        }
+    }
+
+    async fn handle(&mut self, request: Request) -> Result<Response> {
+        match request {
+            Request::Start { history } => {
+                let history = History::from(history);
+
+                self.running.insert(history.id.0.clone(), history.clone());
+                self.started.push(history);
+            }
+
+            Request::End { id, exit, duration } => self.end(&id, exit, duration).await?,
+
+            Request::Search { .. } => bail!("searches are handled by the searcher"),
+        }
+
+        Ok(Response::Ok)
+    }
+
+    async fn end(&mut self, id: &str, exit: i64, duration: Option<u64>) -> Result<()> {
+        // commands started before the daemon are only in the database
+        let history = match self.running.remove(id) {
+            Some(history) => Some(history),
+            None => self.db.load(id).await?,
+        };
+
+        let Some(mut history) = history else {
+            warn!("history entry is missing");
+            return Ok(());
+        };
+
+        if finish(&mut history, exit, duration)? {
+            self.finished.push_back(history);
+        }
+
+        Ok(())
+    }
+
+    /// Save waiting history, keeping it to try again if that fails
+    async fn try_flush(&mut self) {
+        if let Err(e) = self.flush().await {
+            warn!("failed to save history: {e}");
+        }
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if !self.started.is_empty() {
+            self.db.save_bulk(&self.started).await?;
+            self.started.clear();
+        }
+
+        if self.finished.is_empty() {
+            return Ok(());
+        }
+
+        // one at a time, so nothing is saved twice if this fails partway through
+        while let Some(history) = self.finished.front() {
+            self.db.update(history).await?;
+
+            if !history.is_local_only(self.settings) {
+                self.history_store.push(history.clone()).await?;
+            }
+
+            let history = self.finished.pop_front().expect("history was just saved");
+            self.add_to_index(&history);
+        }
+
+        let now = OffsetDateTime::now_utc();
+        self.running
+            .retain(|_, history| now - history.timestamp < RUNNING_EXPIRY);
+
+        self.written.notify_one();
+
+        Ok(())
+    }
+
+    /// Add a finished command to the index, if it's been loaded
+    fn add_to_index(&self, history: &History) {
+        // a search may still be using the old index, in which case this copies it
+        if let Some(index) = self.index.lock().expect("index lock poisoned").as_mut() {
+            aggregate(Arc::make_mut(index), history);
+        }
+    }
+}
+
+/// Add a command to an index, the same way as `all_with_count` aggregates them
+fn aggregate(index: &mut Vec<(History, i32)>, history: &History) {
+    let same = index
+        .iter()
+        .position(|(h, _)| h.command == history.command && h.exit == history.exit);
+
+    // the index is newest first
+    if let Some(same) = same {
+        let (mut h, count) = index.remove(same);
+
+        h.timestamp = h.timestamp.max(history.timestamp);
+        h.duration = h.duration.max(history.duration);
+        h.cwd = format!("{}:{}", h.cwd, history.cwd);
+        h.session = format!("{},{}", h.session, history.session);
+        h.hostname = format!("{},{}", h.hostname, history.hostname);
+
+        index.insert(0, (h, count + 1));
+    } else {
+        index.insert(0, (history.clone(), 1));
+    }
+}
+
+/// Answers searches from the index, and keeps it up to date with changes made elsewhere
+struct Searcher<'a, DB: Database> {
+    db: &'a DB,
+    downloaded: Arc<Notify>,
+    index: Index,
+    engine: SkimMatcherV2,
+}
+
+impl<DB: Database> Searcher<'_, DB> {
+    async fn run(&self, mut receiver: mpsc::Receiver<Message>) -> Result<()> {
+        let mut refresh = interval(INDEX_REFRESH);
+        refresh.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        refresh.tick().await;
+
+        loop {
+            tokio::select! {
+                Some((request, reply)) = receiver.recv() => {
+                    let response = self.handle(request).await.unwrap_or_else(|e| Response::Error {
+                        message: e.to_string(),
+                    });
+
+                    // the client may have given up waiting
+                    let _ = reply.send(response);
+                }
+
+                () = self.downloaded.notified() => self.reload_index().await,
+
+                _ = refresh.tick() => self.reload_index().await,
+            }
+        }
+    }
+
+    async fn handle(&self, request: Request) -> Result<Response> {
+        let Request::Search {
+            query,
+            filter_mode,
+            context,
+        } = request
+        else {
+            bail!("only searches are handled by the searcher");
+        };
+
+        let history = self.search(query, filter_mode, context).await?;
+
+        Ok(Response::History {
+            history: history.iter().map(HistoryJson::from).collect(),
+        })
+    }
+
+    fn loaded_index(&self) -> Option<Arc<Vec<(History, i32)>>> {
+        self.index.lock().expect("index lock poisoned").clone()
+    }
+
+    async fn load_index(&self) -> Result<Arc<Vec<(History, i32)>>> {
+        let index = Arc::new(self.db.all_with_count().await?);
+        *self.index.lock().expect("index lock poisoned") = Some(index.clone());
+
+        Ok(index)
+    }
+
+    /// Reload the index, if it's been loaded, as history changed without going through the daemon.
+    /// Searches keep using the old index if this fails
+    async fn reload_index(&self) {
+        if self.loaded_index().is_none() {
+            return;
+        }
+
+        if let Err(e) = self.load_index().await {
+            warn!("failed to reload the search index: {e}");
+        }
+    }
+
+    async fn search(
+        &self,
+        query: String,
+        filter_mode: FilterMode,
+        context: Context,
+    ) -> Result<Vec<History>> {
+        let index = match self.loaded_index() {
+            Some(index) => index,
+            None => self.load_index().await?,
+        };
+
+        let state = SearchState {
+            input: query.into(),
+            filter_mode,
+            context,
+        };
+
+        Ok(fuzzy_search(&self.engine, &state, &index).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+
+    use atuin_client::{
+        database::{Context, Database, Sqlite},
+        history::{store::HistoryStore, History},
+        record::sqlite_store::SqliteStore,
+        settings::{FilterMode, Settings},
+    };
+    use atuin_common::{record::HostId, utils::uuid_v7};
+    use fuzzy_matcher::skim::SkimMatcherV2;
+    use time::OffsetDateTime;
+    use tokio::sync::Notify;
+
+    use super::{Index, Request, Response, Searcher, Writer};
+
+    fn history(command: &str) -> History {
+        History::capture()
+            .timestamp(OffsetDateTime::now_utc())
+            .command(command)
+            .cwd("/home/user")
+            .build()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn batch_writes() {
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        let settings = Settings::default();
+        let mut writer = Writer {
+            settings: &settings,
+            db: &db,
+            history_store: HistoryStore::new(store, HostId(uuid_v7()), [0; 32]),
+            written: Arc::new(Notify::new()),
+            running: HashMap::new(),
+            started: Vec::new(),
+            finished: VecDeque::new(),
+            index: Arc::new(Mutex::new(Some(Arc::default()))),
+        };
+
+        let first = history("cargo build");
+        let second = history("cargo build");
+
+        for h in [&first, &second] {
+            let start = Request::Start { history: h.into() };
+            assert!(matches!(writer.handle(start).await.unwrap(), Response::Ok));
+        }
+
+        let end = Request::End {
+            id: first.id.0.clone(),
+            exit: 0,
+            duration: Some(100),
+        };
+        writer.handle(end).await.unwrap();
+
+        // nothing is written until the batch is saved
+        assert!(db.load(&first.id.0).await.unwrap().is_none());
+
+        writer.flush().await.unwrap();
+
+        let saved = db.load(&first.id.0).await.unwrap().unwrap();
+        assert_eq!(saved.duration, 100);
+        assert!(db.load(&second.id.0).await.unwrap().is_some());
+
+        // the second is still running, and ends after its start was saved
+        let end = Request::End {
+            id: second.id.0.clone(),
+            exit: 0,
+            duration: Some(200),
+        };
+        writer.handle(end).await.unwrap();
+        writer.flush().await.unwrap();
+
+        assert!(writer.running.is_empty());
+        assert_eq!(db.load(&second.id.0).await.unwrap().unwrap().duration, 200);
+
+        // both runs are counted in the index, as one command
+        let index = writer.index.lock().unwrap().clone().unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].0.command, "cargo build");
+        assert_eq!(index[0].1, 2);
+    }
+
+    #[tokio::test]
+    async fn search_index() {
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        db.save(&history("git status")).await.unwrap();
+
+        let settings = Settings::default();
+        let index = Index::default();
+        let mut writer = Writer {
+            settings: &settings,
+            db: &db,
+            history_store: HistoryStore::new(store, HostId(uuid_v7()), [0; 32]),
+            written: Arc::new(Notify::new()),
+            running: HashMap::new(),
+            started: Vec::new(),
+            finished: VecDeque::new(),
+            index: index.clone(),
+        };
+        let searcher = Searcher {
+            db: &db,
+            downloaded: Arc::new(Notify::new()),
+            index,
+            engine: SkimMatcherV2::default(),
+        };
+
+        let search = || Request::Search {
+            query: String::from("git"),
+            filter_mode: FilterMode::Global,
+            context: Context {
+                session: String::new(),
+                cwd: String::new(),
+                hostname: String::new(),
+                host_id: String::new(),
+                git_root: None,
+            },
+        };
+        let commands = |response| match response {
+            Response::History { history } => {
+                history.into_iter().map(|h| h.command).collect::<Vec<_>>()
+            }
+            _ => panic!("expected history"),
+        };
+
+        // the first search loads the index
+        let response = searcher.handle(search()).await.unwrap();
+        assert_eq!(commands(response), ["git status"]);
+
+        // history saved by the writer is searchable without reloading
+        let new = history("git push");
+        let start = Request::Start {
+            history: (&new).into(),
+        };
+        writer.handle(start).await.unwrap();
+        let end = Request::End {
+            id: new.id.0.clone(),
+            exit: 0,
+            duration: Some(100),
+        };
+        writer.handle(end).await.unwrap();
+        writer.flush().await.unwrap();
+
+        let response = searcher.handle(search()).await.unwrap();
+        assert_eq!(commands(response), ["git push", "git status"]);
+
+        // and the writer doesn't answer searches
+        assert!(writer.handle(search()).await.is_err());
+    }
+}
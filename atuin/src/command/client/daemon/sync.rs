@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eyre::Result;
+use log::{info, warn};
+use tokio::{
+    sync::Notify,
+    time::{interval, sleep, MissedTickBehavior},
+};
+
+use atuin_client::{
+    api_client::Client,
+    database::Database,
+    history::store::HistoryStore,
+    record::{sqlite_store::SqliteStore, store::Store, sync},
+    settings::Settings,
+};
+use atuin_common::record::{HostId, RecordIdx};
+
+/// How often to look for new local records to upload, written without the daemon
+const LOCAL_CHECK: Duration = Duration::from_secs(2);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Stay connected to the server, syncing as soon as records are added here or on another machine
+pub async fn run(
+    settings: &Settings,
+    db: &impl Database,
+    history_store: HistoryStore,
+    store: SqliteStore,
+    written: Arc<Notify>,
+    downloaded: Arc<Notify>,
+) -> Result<()> {
+    let frequency = parse_duration::parse(&settings.sync_frequency)
+        .map_err(|e| eyre::eyre!("failed to parse sync_frequency: {e}"))?;
+
+    // history end is handed to us, so without record sync we have to do its syncing
+    if !settings.sync.records {
+        return legacy(
+            settings,
+            db,
+            frequency.max(LOCAL_CHECK),
+            written,
+            downloaded,
+        )
+        .await;
+    }
+
+    if !settings.logged_in() {
+        info!("not syncing, as you are not logged in");
+        return std::future::pending().await;
+    }
+
+    let mut syncer = Syncer {
+        settings,
+        db,
+        history_store,
+        store,
+        host_id: Settings::host_id().expect("failed to get host_id"),
+        frequency: frequency.max(LOCAL_CHECK),
+        written,
+        downloaded,
+        synced: HashMap::new(),
+    };
+
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        let started = Instant::now();
+        let res = syncer.listen().await;
+
+        // a connection that lasted a while was working, so start over
+        if started.elapsed() > MAX_BACKOFF {
+            backoff = MIN_BACKOFF;
+        }
+
+        if let Err(e) = res {
+            warn!("sync failed, retrying in {}s: {e}", backoff.as_secs());
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Sync history without the record store, when `history end` would have: after a command, once
+/// `sync_frequency` has passed since the last sync
+async fn legacy(
+    settings: &Settings,
+    db: &impl Database,
+    frequency: Duration,
+    written: Arc<Notify>,
+    downloaded: Arc<Notify>,
+) -> Result<()> {
+    loop {
+        if let Err(e) = legacy_sync(settings, db, &downloaded).await {
+            warn!("sync failed: {e}");
+        }
+
+        tokio::select! {
+            () = written.notified() => {}
+            () = sleep(frequency) => {}
+        }
+    }
+}
+
+async fn legacy_sync(settings: &Settings, db: &impl Database, downloaded: &Notify) -> Result<()> {
+    if !settings.should_sync()? {
+        return Ok(());
+    }
+
+    let before = db.history_count(true).await?;
+    atuin_client::sync::sync(settings, false, db).await?;
+
+    if db.history_count(true).await? != before {
+        downloaded.notify_one();
+    }
+
+    Ok(())
+}
+
+struct Syncer<'a, DB: Database> {
+    settings: &'a Settings,
+    db: &'a DB,
+    history_store: HistoryStore,
+    store: SqliteStore,
+    host_id: HostId,
+    /// Sync at least this often, even if nothing seems to have changed
+    frequency: Duration,
+    written: Arc<Notify>,
+    downloaded: Arc<Notify>,
+    /// This host's records, as of the last sync
+    synced: HashMap<String, RecordIdx>,
+}
+
+impl<DB: Database> Syncer<'_, DB> {
+    /// Sync whenever the server says there are new records, until the connection ends
+    async fn listen(&mut self) -> Result<()> {
+        let client = Client::new(
+            &self.settings.sync_address,
+            &self.settings.session_token,
+            self.settings.network_connect_timeout,
+            self.settings.network_timeout,
+        )?;
+
+        // connect before syncing, so nothing uploaded in between is missed
+        let events = client.record_events().await?;
+        self.sync().await?;
+
+        let Some(mut events) = events else {
+            return self.poll().await;
+        };
+
+        let mut local = interval(LOCAL_CHECK);
+        local.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        let mut timer = interval(self.frequency);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        timer.tick().await;
+
+        loop {
+            tokio::select! {
+                event = events.next_event() => {
+                    let Some(event) = event? else {
+                        return Ok(());
+                    };
+
+                    // we already have the records we uploaded
+                    if event.host != self.host_id {
+                        self.sync().await?;
+                    }
+                }
+
+                () = self.written.notified() => self.sync().await?,
+
+                _ = timer.tick() => self.sync().await?,
+
+                _ = local.tick() => {
+                    if self.local_changed().await? {
+                        self.sync().await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The server is too old to send events, so sync every `sync_frequency`, or when there's
+    /// new history
+    async fn poll(&mut self) -> Result<()> {
+        loop {
+            tokio::select! {
+                () = self.written.notified() => {}
+                () = sleep(self.frequency) => {}
+            }
+
+            self.sync().await?;
+        }
+    }
+
+    async fn local_changed(&self) -> Result<bool> {
+        Ok(self.local_status().await? != self.synced)
+    }
+
+    async fn local_status(&self) -> Result<HashMap<String, RecordIdx>> {
+        let mut status = self.store.status().await?;
+
+        Ok(status.hosts.remove(&self.host_id).unwrap_or_default())
+    }
+
+    async fn sync(&mut self) -> Result<()> {
+        let (diff, _) = sync::diff(self.settings, &self.store).await?;
+        let operations = sync::operations(diff, &self.store).await?;
+        let (uploaded, downloaded) =
+            sync::sync_remote(operations, &self.store, self.settings).await?;
+
+        if downloaded > 0 {
+            self.history_store.incremental_build(self.db).await?;
+            self.downloaded.notify_one();
+        }
+
+        if uploaded > 0 || downloaded > 0 {
+            info!("{uploaded}/{downloaded} up/down to record store");
+        }
+
+        Settings::save_sync_time()?;
+
+        self.synced = self.local_status().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::{env, net::TcpListener, sync::Arc, time::Duration};
+
+    use atuin_client::{
+        api_client,
+        database::{Database, Sqlite},
+        history::{store::HistoryStore, History},
+        record::sqlite_store::SqliteStore,
+        settings::Settings,
+    };
+    use atuin_common::{record::HostId, utils::uuid_v7};
+    use atuin_server::{launch_with_tcp_listener, Settings as ServerSettings};
+    use atuin_server_database::DbSettings;
+    use atuin_server_sqlite::Sqlite as ServerSqlite;
+    use time::OffsetDateTime;
+    use tokio::{sync::Notify, time::sleep};
+
+    use super::run;
+
+    async fn start_server() -> String {
+        let path = env::temp_dir().join(format!("atuin-test-{}.db", uuid_v7().as_simple()));
+
+        let settings = ServerSettings {
+            host: "127.0.0.1".to_owned(),
+            port: 0,
+            path: String::new(),
+            open_registration: true,
+            max_history_length: 8192,
+            max_record_size: 1024 * 1024,
+            page_size: 1100,
+            session_expiry_days: 0,
+            register_webhook_url: None,
+            register_webhook_username: String::new(),
+            db_settings: DbSettings {
+                db_uri: format!("sqlite://{}", path.display()),
+            },
+            metrics: atuin_server::settings::Metrics::default(),
+            tls: atuin_server::settings::Tls::default(),
+            rate_limit: atuin_server::settings::RateLimit::default(),
+            quota: atuin_server::settings::Quota::default(),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(launch_with_tcp_listener::<ServerSqlite>(
+            settings,
+            listener,
+            std::future::pending(),
+        ));
+
+        // let the server come online
+        sleep(Duration::from_millis(200)).await;
+
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn legacy_sync() {
+        let address = start_server().await;
+        let username = uuid_v7().as_simple().to_string();
+        let session = api_client::register(
+            &address,
+            &username,
+            &format!("{username}@example.com"),
+            "password",
+        )
+        .await
+        .unwrap()
+        .session;
+
+        let dir = env::temp_dir().join(format!("atuin-test-{username}"));
+        fs_err::create_dir_all(&dir).unwrap();
+        fs_err::write(dir.join("session"), &session).unwrap();
+
+        let mut settings = Settings::default();
+        settings.sync.records = false;
+        settings.sync_frequency = "0".to_string();
+        settings.sync_address = address.clone();
+        settings.session_path = dir.join("session").to_string_lossy().to_string();
+        settings.session_token = session.clone();
+        settings.key_path = dir.join("key").to_string_lossy().to_string();
+
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let h: History = History::capture()
+            .timestamp(OffsetDateTime::now_utc())
+            .command("ls")
+            .cwd("/home/user")
+            .build()
+            .into();
+        db.save(&h).await.unwrap();
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let history_store = HistoryStore::new(store.clone(), HostId(uuid_v7()), [0; 32]);
+        let client = api_client::Client::new(&address, &session, 5, 30).unwrap();
+
+        // with record sync off, the daemon still uploads history, the old way
+        tokio::select! {
+            res = run(
+                &settings,
+                &db,
+                history_store,
+                store,
+                Arc::new(Notify::new()),
+                Arc::new(Notify::new()),
+            ) => panic!("sync stopped: {res:?}"),
+            () = async {
+                while client.count().await.unwrap() < 1 {
+                    sleep(Duration::from_millis(100)).await;
+                }
+            } => {}
+            () = sleep(Duration::from_secs(10)) => panic!("history was never synced"),
+        }
+    }
+}
@@ -8,14 +8,17 @@ use std::{
 use atuin_common::utils;
 use clap::Subcommand;
 use eyre::{Context, Result};
+use fs_err as fs;
 use runtime_format::{FormatKey, FormatKeyError, ParseSegment, ParsedFmt};
 
 use atuin_client::{
-    database::{current_context, Database},
-    encryption,
+    database::OptFilters,
+    database::{current_context, Context as DbContext, Database},
+    encryption::{self, protect::Locked},
+    export::{export, ExportFormat},
     history::{store::HistoryStore, History},
     record::{self, sqlite_store::SqliteStore},
-    settings::Settings,
+    settings::{FilterMode, Settings},
 };
 
 #[cfg(feature = "sync")]
@@ -23,6 +26,8 @@ use atuin_client::sync;
 use log::{debug, warn};
 use time::{macros::format_description, OffsetDateTime};
 
+#[cfg(unix)]
+use super::daemon;
 use super::search::format_duration_into;
 
 #[derive(Subcommand, Debug)]
@@ -66,7 +71,8 @@ pub enum Cmd {
         #[arg(action = clap::ArgAction::Set)]
         reverse: bool,
 
-        /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {exit} and {time}.
+        /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {exit}, {branch},
+        /// {commit}, {tag} and {time}.
         /// Example: --format "{time} - [{duration}] - {directory}$\t{command}"
         #[arg(long, short)]
         format: Option<String>,
@@ -81,7 +87,86 @@ pub enum Cmd {
         #[arg(long)]
         cmd_only: bool,
 
-        /// Available variables: {command}, {directory}, {duration}, {user}, {host} and {time}.
+        /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {branch},
+        /// {commit}, {tag} and {time}.
+        /// Example: --format "{time} - [{duration}] - {directory}$\t{command}"
+        #[arg(long, short)]
+        format: Option<String>,
+    },
+
+    /// Export history, for backups or to use with other tools
+    Export {
+        /// Output format
+        #[arg(long, short, value_enum, default_value = "jsonl")]
+        format: ExportFormat,
+
+        /// Filter by directory, or "." for the current directory
+        #[arg(long, short)]
+        cwd: Option<String>,
+
+        /// Exclude directory from results
+        #[arg(long = "exclude-cwd")]
+        exclude_cwd: Option<String>,
+
+        /// Filter by exit code
+        #[arg(long, short)]
+        exit: Option<i64>,
+
+        /// Exclude results with this exit code
+        #[arg(long = "exclude-exit")]
+        exclude_exit: Option<i64>,
+
+        /// Only include results added before this date
+        #[arg(long, short)]
+        before: Option<String>,
+
+        /// Only include results after this date
+        #[arg(long)]
+        after: Option<String>,
+
+        /// Only include results from this host, given as "hostname:username"
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Only include results from this session
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Only include results run on this git branch
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// Only include results recorded with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Filter mode to export with, defaults to global
+        #[arg(long = "filter-mode", default_value = "global")]
+        filter_mode: FilterMode,
+    },
+
+    /// Show every command run in a session, oldest first, as a timeline
+    Session {
+        /// The session to show, defaults to the current session
+        session: Option<String>,
+
+        /// Also include this many sessions run before it on the same host
+        #[arg(long, default_value = "0")]
+        previous: usize,
+
+        /// Also include this many sessions run after it on the same host
+        #[arg(long, default_value = "0")]
+        next: usize,
+
+        #[arg(long)]
+        human: bool,
+
+        /// Show only the text of the command
+        #[arg(long)]
+        cmd_only: bool,
+
+        /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {exit}, {branch},
+        /// {commit}, {tag} and {time}.
         /// Example: --format "{time} - [{duration}] - {directory}$\t{command}"
         #[arg(long, short)]
         format: Option<String>,
@@ -90,6 +175,10 @@ pub enum Cmd {
     /// Import all old history.db data into the record store. Do not run more than once, and do not
     /// run unless you know what you're doing (or the docs ask you to)
     InitStore,
+
+    /// Replay all history from the record store into history.db, including history synced from
+    /// other machines. History that is only in history.db is left as-is
+    Rebuild,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -111,6 +200,52 @@ impl ListMode {
     }
 }
 
+/// Load the commands of `session`, along with up to `previous` and `next` sessions either side of
+/// it on the same host. Each session is returned with its commands, oldest first.
+pub async fn session_timeline(
+    db: &impl Database,
+    context: &DbContext,
+    session: &str,
+    previous: usize,
+    next: usize,
+) -> Result<Vec<(String, Vec<History>)>> {
+    let mut sessions = vec![session.to_string()];
+
+    for _ in 0..previous {
+        match db.adjacent_session(&sessions[0], true).await? {
+            Some(session) => sessions.insert(0, session),
+            None => break,
+        }
+    }
+
+    for _ in 0..next {
+        match db
+            .adjacent_session(&sessions[sessions.len() - 1], false)
+            .await?
+        {
+            Some(session) => sessions.push(session),
+            None => break,
+        }
+    }
+
+    let mut timeline = Vec::with_capacity(sessions.len());
+
+    for session in sessions {
+        let filters = OptFilters {
+            session: Some(session.clone()),
+            ..OptFilters::default()
+        };
+
+        let history = db
+            .list_filtered(FilterMode::Global, context, filters)
+            .await?;
+
+        timeline.push((session, history));
+    }
+
+    Ok(timeline)
+}
+
 #[allow(clippy::cast_sign_loss)]
 pub fn print_list(
     h: &[History],
@@ -227,6 +362,10 @@ impl FormatKey for FmtHistory<'_> {
                     .map_or(&self.0.hostname, |(host, _)| host),
             )?,
             "user" => f.write_str(self.0.hostname.split_once(':').map_or("", |(_, user)| user))?,
+            "session" => f.write_str(&self.0.session)?,
+            "branch" => f.write_str(self.0.git_branch.as_deref().unwrap_or_default())?,
+            "commit" => f.write_str(self.0.git_commit.as_deref().unwrap_or_default())?,
+            "tag" => f.write_str(self.0.tag.as_deref().unwrap_or_default())?,
             _ => return Err(FormatKeyError::UnknownKey),
         }
         Ok(())
@@ -244,42 +383,107 @@ fn parse_fmt(format: &str) -> ParsedFmt {
     }
 }
 
+/// Capture a command that is starting, or None if it shouldn't be saved
+fn capture(settings: &Settings, command: &[String]) -> Option<History> {
+    let command = command.join(" ");
+
+    // It's better for atuin to silently fail here and attempt to
+    // store whatever is ran, than to throw an error to the terminal
+    let cwd = utils::get_current_dir();
+
+    let (git_branch, git_commit) = utils::in_git_repo(cwd.as_str())
+        .map(|root| utils::git_head(&root))
+        .unwrap_or_default();
+
+    let h: History = History::capture()
+        .timestamp(OffsetDateTime::now_utc())
+        .command(command)
+        .cwd(cwd)
+        .git_branch(git_branch)
+        .git_commit(git_commit)
+        .tag(settings.history_tag())
+        .build()
+        .into();
+
+    h.should_save(settings).then_some(h)
+}
+
+fn history_store(settings: &Settings, store: SqliteStore) -> Result<HistoryStore> {
+    let encryption_key: [u8; 32] = encryption::load_key(settings)
+        .context("could not load encryption key")?
+        .into();
+
+    let host_id = Settings::host_id().expect("failed to get host_id");
+
+    Ok(HistoryStore::new(store, host_id, encryption_key))
+}
+
+/// Warn, once a session, that history is only being saved locally because the key is locked
+fn warn_key_locked(settings: &Settings) -> Result<()> {
+    if !encryption::key_locked(settings)? {
+        return Ok(());
+    }
+
+    let session = env::var("ATUIN_SESSION").unwrap_or_default();
+    let path = utils::data_dir().join("key_locked_warning");
+
+    if fs::read_to_string(&path).ok().as_deref() == Some(session.as_str()) {
+        return Ok(());
+    }
+
+    eprintln!(
+        "atuin: the encryption key is locked, so history is only being saved locally. Run `eval \"$(atuin key unlock)\"` to sync it"
+    );
+    fs::write(path, session)?;
+
+    Ok(())
+}
+
+/// Record how a command finished. False if it already has been, which can happen if someone
+/// presses Ctrl-c at a prompt
+pub fn finish(h: &mut History, exit: i64, duration: Option<u64>) -> Result<bool> {
+    if h.duration > 0 {
+        debug!("cannot end history - already has duration");
+        return Ok(false);
+    }
+
+    h.exit = exit;
+    h.duration = match duration {
+        Some(value) => i64::try_from(value).context("command took over 292 years")?,
+        None => i64::try_from((OffsetDateTime::now_utc() - h.timestamp).whole_nanoseconds())
+            .context("command took over 292 years")?,
+    };
+
+    Ok(true)
+}
+
 impl Cmd {
-    #[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
     async fn handle_start(
         db: &impl Database,
         settings: &Settings,
         command: &[String],
     ) -> Result<()> {
-        let command = command.join(" ");
-
-        // It's better for atuin to silently fail here and attempt to
-        // store whatever is ran, than to throw an error to the terminal
-        let cwd = utils::get_current_dir();
-
-        let h: History = History::capture()
-            .timestamp(OffsetDateTime::now_utc())
-            .command(command)
-            .cwd(cwd)
-            .build()
-            .into();
-
-        if !h.should_save(settings) {
+        let Some(h) = capture(settings, command) else {
             return Ok(());
-        }
+        };
 
         // print the ID
         // we use this as the key for calling end
         println!("{}", h.id);
         db.save(&h).await?;
 
+        // end's output goes nowhere, so this is where we can say why history isn't syncing
+        if let Err(e) = warn_key_locked(settings) {
+            debug!("could not check whether the key is locked: {e}");
+        }
+
         Ok(())
     }
 
     async fn handle_end(
         db: &impl Database,
         store: SqliteStore,
-        history_store: HistoryStore,
+        history_store: Option<HistoryStore>,
         settings: &Settings,
         id: &str,
         exit: i64,
@@ -294,22 +498,23 @@ impl Cmd {
             return Ok(());
         };
 
-        if h.duration > 0 {
-            debug!("cannot end history - already has duration");
-
-            // returning OK as this can occur if someone Ctrl-c a prompt
+        if !finish(&mut h, exit, duration)? {
             return Ok(());
         }
 
-        h.exit = exit;
-        h.duration = match duration {
-            Some(value) => i64::try_from(value).context("command took over 292 years")?,
-            None => i64::try_from((OffsetDateTime::now_utc() - h.timestamp).whole_nanoseconds())
-                .context("command took over 292 years")?,
+        db.update(&h).await?;
+
+        // with the key locked, history is only kept in history.db. It can't be synced either
+        let Some(history_store) = history_store else {
+            debug!("encryption key is locked, not recording history in the store");
+            return Ok(());
         };
 
-        db.update(&h).await?;
-        history_store.push(h).await?;
+        // local-only history never goes in the record store, as records can't be held back once
+        // they're there
+        if !h.is_local_only(settings) {
+            history_store.push(h).await?;
+        }
 
         if settings.should_sync()? {
             #[cfg(feature = "sync")]
@@ -321,6 +526,8 @@ impl Cmd {
                         record::sync::sync_remote(operations, &store, settings).await?;
 
                     println!("{uploaded}/{downloaded} up/down to record store");
+
+                    history_store.incremental_build(db).await?;
                 }
 
                 debug!("running periodic background sync");
@@ -387,6 +594,7 @@ impl Cmd {
     }
 
     async fn init_store(
+        settings: &Settings,
         context: atuin_client::database::Context,
         db: &impl Database,
         store: HistoryStore,
@@ -404,6 +612,11 @@ impl Cmd {
             .await?;
 
         for i in history {
+            // local-only history is kept out of the record store, as it is when it's recorded
+            if i.is_local_only(settings) {
+                continue;
+            }
+
             println!("loaded {}", i.id);
 
             if i.deleted_at.is_some() {
@@ -417,6 +630,81 @@ impl Cmd {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_session(
+        db: &impl Database,
+        context: &DbContext,
+        session: Option<String>,
+        previous: usize,
+        next: usize,
+        mode: ListMode,
+        format: Option<String>,
+    ) -> Result<()> {
+        let session = session.unwrap_or_else(|| context.session.clone());
+
+        // the exit code matters when reading back through a session, so show it by default
+        let format = format.or_else(|| {
+            matches!(mode, ListMode::Regular)
+                .then(|| "{time}\t{exit}\t{duration}\t{command}".to_string())
+        });
+
+        let timeline = session_timeline(db, context, &session, previous, next).await?;
+
+        for (session, history) in timeline {
+            if !matches!(mode, ListMode::CmdOnly) {
+                let host = history.first().map_or("unknown", |h| h.hostname.as_str());
+                println!("# session {session} on {host}, {} commands", history.len());
+            }
+
+            print_list(&history, mode, format.as_deref(), false, false);
+        }
+
+        Ok(())
+    }
+
+    async fn rebuild(db: &impl Database, store: HistoryStore) -> Result<()> {
+        println!("Rebuilding history.db from the record store");
+
+        let applied = store.build(db).await?;
+
+        println!("Replayed {applied} history records");
+
+        Ok(())
+    }
+
+    /// Hand `start` and `end` to the daemon, if it's running, so they don't have to open the
+    /// databases. False if the command still needs running
+    #[cfg(unix)]
+    pub async fn run_with_daemon(&self, settings: &Settings) -> bool {
+        if !matches!(self, Self::Start { .. } | Self::End { .. }) {
+            return false;
+        }
+
+        let Some(mut daemon) = daemon::client::Client::connect(&settings.daemon.socket_path).await
+        else {
+            return false;
+        };
+
+        let res = match self {
+            Self::Start { command } => {
+                let Some(h) = capture(settings, command) else {
+                    return true;
+                };
+
+                daemon.start(&h).await.map(|()| println!("{}", h.id))
+            }
+            Self::End { id, exit, duration } => daemon.end(id, *exit, *duration).await,
+            _ => unreachable!(),
+        };
+
+        // if the daemon is broken, write the history ourselves
+        if let Err(e) = &res {
+            warn!("failed to send history to the daemon: {e}");
+        }
+
+        res.is_ok()
+    }
+
     pub async fn run(
         self,
         settings: &Settings,
@@ -425,16 +713,16 @@ impl Cmd {
     ) -> Result<()> {
         let context = current_context();
 
-        let encryption_key: [u8; 32] = encryption::load_key(settings)
-            .context("could not load encryption key")?
-            .into();
-
-        let host_id = Settings::host_id().expect("failed to get host_id");
-        let history_store = HistoryStore::new(store.clone(), host_id, encryption_key);
-
         match self {
             Self::Start { command } => Self::handle_start(db, settings, &command).await,
             Self::End { id, exit, duration } => {
+                // this runs after every command, so a locked key mustn't stop history being saved
+                let history_store = match history_store(settings, store.clone()) {
+                    Ok(history_store) => Some(history_store),
+                    Err(e) if e.is::<Locked>() => None,
+                    Err(e) => return Err(e),
+                };
+
                 Self::handle_end(db, store, history_store, settings, &id, exit, duration).await
             }
             Self::List {
@@ -453,6 +741,18 @@ impl Cmd {
                 .await
             }
 
+            Self::Session {
+                session,
+                previous,
+                next,
+                human,
+                cmd_only,
+                format,
+            } => {
+                let mode = ListMode::from_flags(human, cmd_only);
+                Self::handle_session(db, &context, session, previous, next, mode, format).await
+            }
+
             Self::Last {
                 human,
                 cmd_only,
@@ -471,7 +771,151 @@ impl Cmd {
                 Ok(())
             }
 
-            Self::InitStore => Self::init_store(context, db, history_store).await,
+            Self::Export {
+                format,
+                cwd,
+                exclude_cwd,
+                exit,
+                exclude_exit,
+                before,
+                after,
+                host,
+                session,
+                branch,
+                tag,
+                filter_mode,
+            } => {
+                let cwd = if cwd.as_deref() == Some(".") {
+                    Some(utils::get_current_dir())
+                } else {
+                    cwd
+                };
+
+                let filters = OptFilters {
+                    exit,
+                    exclude_exit,
+                    cwd,
+                    exclude_cwd,
+                    hostname: host,
+                    session,
+                    git_branch: branch,
+                    tag,
+                    before,
+                    after,
+                    ..OptFilters::default()
+                };
+
+                let history = db.list_filtered(filter_mode, &context, filters).await?;
+
+                let mut stdout = io::stdout().lock();
+                export(&mut stdout, format, &history)?;
+                stdout.flush()?;
+
+                Ok(())
+            }
+
+            Self::InitStore => {
+                let history_store = history_store(settings, store)?;
+                Self::init_store(settings, context, db, history_store).await
+            }
+            Self::Rebuild => Self::rebuild(db, history_store(settings, store)?).await,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use atuin_client::{
+        database::{Context, Database, Sqlite},
+        history::{store::HistoryStore, History},
+        record::sqlite_store::SqliteStore,
+        settings::{Globs, Settings},
+    };
+    use atuin_common::{record::HostId, utils::uuid_v7};
+    use time::OffsetDateTime;
+
+    use super::Cmd;
+
+    fn history(command: &str, cwd: &str) -> History {
+        History::import()
+            .timestamp(OffsetDateTime::now_utc())
+            .command(command)
+            .cwd(cwd)
+            .build()
+            .into()
+    }
+
+    fn context() -> Context {
+        Context {
+            session: String::new(),
+            cwd: String::new(),
+            hostname: String::new(),
+            host_id: String::new(),
+            git_root: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn end_with_key_locked() {
+        let settings = Settings::default();
+
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let h = history("make test", "/home/ellie");
+        db.save(&h).await.unwrap();
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        Cmd::handle_end(&db, store.clone(), None, &settings, &h.id.0, 2, Some(1000))
+            .await
+            .unwrap();
+
+        // still saved to history.db, just not the record store
+        let ended = db.load(&h.id.0).await.unwrap().unwrap();
+        assert_eq!(ended.exit, 2);
+        assert_eq!(ended.duration, 1000);
+
+        let rebuilt = Sqlite::new("sqlite::memory:").await.unwrap();
+        let history_store = HistoryStore::new(store, HostId(uuid_v7()), [0; 32]);
+        assert_eq!(history_store.build(&rebuilt).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn init_store_skips_local_only() {
+        let mut settings = Settings::default();
+        settings.sync.local_only_cwd = Globs::new(["/work/clients"]).unwrap();
+
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        db.save(&history("ls", "/home/ellie")).await.unwrap();
+        db.save(&history("make deploy", "/work/clients/acme"))
+            .await
+            .unwrap();
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let history_store = HistoryStore::new(store.clone(), HostId(uuid_v7()), [0; 32]);
+
+        Cmd::init_store(&settings, context(), &db, history_store)
+            .await
+            .unwrap();
+
+        // replaying the store elsewhere only has what may be synced
+        let rebuilt = Sqlite::new("sqlite::memory:").await.unwrap();
+        let history_store = HistoryStore::new(store, HostId(uuid_v7()), [0; 32]);
+        history_store.build(&rebuilt).await.unwrap();
+
+        let commands: Vec<_> = rebuilt
+            .list(
+                atuin_client::settings::FilterMode::Global,
+                &context(),
+                None,
+                false,
+                true,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|h| h.command)
+            .collect();
+
+        assert_eq!(commands, vec!["ls"]);
+    }
+}
@@ -1,7 +1,7 @@
-use std::env;
+use std::{env, path::PathBuf};
 
 use async_trait::async_trait;
-use clap::Parser;
+use clap::{Args, Parser};
 use eyre::Result;
 use indicatif::ProgressBar;
 
@@ -9,8 +9,18 @@ use atuin_client::{
     database::Database,
     history::History,
     import::{
-        bash::Bash, fish::Fish, nu::Nu, nu_histdb::NuHistDb, resh::Resh, zsh::Zsh,
-        zsh_histdb::ZshHistDb, Importer, Loader,
+        bash::Bash,
+        elvish::Elvish,
+        fish::Fish,
+        generic::{Fields, Format, Generic},
+        nu::Nu,
+        nu_histdb::NuHistDb,
+        powershell::PowerShell,
+        resh::Resh,
+        xonsh::Xonsh,
+        zsh::Zsh,
+        zsh_histdb::ZshHistDb,
+        Importer, Loader,
     },
 };
 
@@ -34,6 +44,74 @@ pub enum Cmd {
     Nu,
     /// Import history from the nu history file
     NuHistDb,
+    /// Import history from the xonsh JSON history directory
+    Xonsh,
+    /// Import history from the elvish database
+    Elvish,
+    /// Import history from the PowerShell history file
+    #[command(name = "powershell")]
+    PowerShell,
+    /// Import history from a file with a JSON object on each line, such as
+    /// `atuin history export --format jsonl` writes
+    Jsonl(GenericCmd),
+    /// Import history from a CSV file, with a header row naming the fields
+    Csv(GenericCmd),
+}
+
+/// Which field of each entry holds each part of the history. Only the command is required
+#[derive(Args, Debug)]
+pub struct GenericCmd {
+    /// The file to import
+    path: PathBuf,
+
+    /// The field holding the command
+    #[arg(long, default_value = "command")]
+    command: String,
+
+    /// The field holding when the command ran, as RFC 3339 or a Unix timestamp
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+
+    /// The field holding how long the command ran for, in nanoseconds
+    #[arg(long, default_value = "duration")]
+    duration: String,
+
+    /// The field holding the exit code
+    #[arg(long, default_value = "exit")]
+    exit: String,
+
+    /// The field holding the directory the command ran in
+    #[arg(long, default_value = "cwd")]
+    cwd: String,
+
+    /// The field holding the shell session
+    #[arg(long, default_value = "session")]
+    session: String,
+
+    /// The field holding the hostname, as host:user
+    #[arg(long, default_value = "hostname")]
+    hostname: String,
+
+    /// The field holding a unique ID, so importing the same entry twice only saves it once
+    #[arg(long, default_value = "id")]
+    id: String,
+}
+
+impl GenericCmd {
+    fn importer(&self, format: Format) -> Result<Generic> {
+        let fields = Fields {
+            id: self.id.clone(),
+            timestamp: self.timestamp.clone(),
+            duration: self.duration.clone(),
+            exit: self.exit.clone(),
+            command: self.command.clone(),
+            cwd: self.cwd.clone(),
+            session: self.session.clone(),
+            hostname: self.hostname.clone(),
+        };
+
+        Generic::from_file(&self.path, format, fields)
+    }
 }
 
 const BATCH_SIZE: usize = 100;
@@ -84,6 +162,15 @@ impl Cmd {
                         println!("Detected Nushell");
                         import::<Nu, DB>(db).await
                     }
+                } else if shell.ends_with("/xonsh") {
+                    println!("Detected Xonsh");
+                    import::<Xonsh, DB>(db).await
+                } else if shell.ends_with("/elvish") {
+                    println!("Detected Elvish");
+                    import::<Elvish, DB>(db).await
+                } else if shell.ends_with("/pwsh") || shell.ends_with("/powershell") {
+                    println!("Detected PowerShell");
+                    import::<PowerShell, DB>(db).await
                 } else {
                     println!("cannot import {shell} history");
                     Ok(())
@@ -97,6 +184,11 @@ impl Cmd {
             Self::Fish => import::<Fish, DB>(db).await,
             Self::Nu => import::<Nu, DB>(db).await,
             Self::NuHistDb => import::<NuHistDb, DB>(db).await,
+            Self::Xonsh => import::<Xonsh, DB>(db).await,
+            Self::Elvish => import::<Elvish, DB>(db).await,
+            Self::PowerShell => import::<PowerShell, DB>(db).await,
+            Self::Jsonl(cmd) => import_from(cmd.importer(Format::Jsonl)?, db).await,
+            Self::Csv(cmd) => import_from(cmd.importer(Format::Csv)?, db).await,
         }
     }
 }
@@ -139,10 +231,13 @@ impl<'db, DB: Database> Loader for HistoryImporter<'db, DB> {
 }
 
 async fn import<I: Importer + Send, DB: Database>(db: &DB) -> Result<()> {
+    import_from(I::new().await?, db).await
+}
+
+async fn import_from<I: Importer + Send, DB: Database>(mut importer: I, db: &DB) -> Result<()> {
     println!("Importing history from {}", I::NAME);
 
-    let mut importer = I::new().await?;
-    let len = importer.entries().await.unwrap();
+    let len = importer.entries().await?;
     let mut loader = HistoryImporter::new(db, len);
     importer.load(&mut loader).await?;
     loader.flush().await?;
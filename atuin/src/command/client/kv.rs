@@ -3,6 +3,8 @@ use eyre::{Context, Result};
 
 use atuin_client::{encryption, kv::KvStore, record::store::Store, settings::Settings};
 
+use super::{print_json, Output};
+
 #[derive(Subcommand, Debug)]
 #[command(infer_subcommands = true)]
 pub enum Cmd {
@@ -31,6 +33,10 @@ pub enum Cmd {
 
         #[arg(long, short)]
         all_namespaces: bool,
+
+        /// JSON output includes the value of each key
+        #[arg(long, short, value_enum, default_value = "human")]
+        output: Output,
     },
 }
 
@@ -68,10 +74,21 @@ impl Cmd {
             Self::List {
                 namespace,
                 all_namespaces,
+                output,
             } => {
                 // TODO: don't rebuild this every time lol
                 let map = kv_store.build_kv(store, &encryption_key).await?;
 
+                if *output == Output::Json {
+                    let records = map
+                        .iter()
+                        .filter(|(ns, _)| *all_namespaces || *ns == namespace)
+                        .flat_map(|(_, kv)| kv.values())
+                        .collect::<Vec<_>>();
+
+                    return print_json(&records);
+                }
+
                 // slower, but sorting is probably useful
                 if *all_namespaces {
                     for (ns, kv) in &map {
@@ -1,63 +1,229 @@
 use clap::Subcommand;
-use eyre::Result;
+use eyre::{bail, Context, Result};
+use serde::Serialize;
 
-use atuin_client::{record::store::Store, settings::Settings};
-use time::OffsetDateTime;
+use atuin_client::{
+    encryption,
+    record::{
+        store::Store,
+        verify::{self, ProblemKind},
+    },
+    settings::Settings,
+};
+use atuin_common::record::{HostId, RecordIdx};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{print_json, Output};
 
 #[derive(Subcommand, Debug)]
 #[command(infer_subcommands = true)]
 pub enum Cmd {
-    Status,
+    Status {
+        #[arg(long, short, value_enum, default_value = "human")]
+        output: Output,
+    },
+
+    /// Check every record chain for gaps, duplicates, records we can't decrypt or read, and
+    /// odd timestamps
+    Verify {
+        /// Quarantine bad records, and download missing ones from the server
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct HostStatus {
+    host: String,
+    current: bool,
+    tags: Vec<TagStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct TagStatus {
+    tag: String,
+    idx: RecordIdx,
+    first: Option<RecordSummary>,
+    last: Option<RecordSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecordSummary {
+    id: String,
+    created: String,
 }
 
 impl Cmd {
-    pub async fn run(
-        &self,
-        _settings: &Settings,
-        store: &(impl Store + Send + Sync),
-    ) -> Result<()> {
-        let host_id = Settings::host_id().expect("failed to get host_id");
-
-        let status = store.status().await?;
-
-        // TODO: should probs build some data structure and then pretty-print it or smth
-        for (host, st) in &status.hosts {
-            let host_string = if host == &host_id {
-                format!("host: {} <- CURRENT HOST", host.0.as_hyphenated())
-            } else {
-                format!("host: {}", host.0.as_hyphenated())
+    pub async fn run(&self, settings: &Settings, store: &(impl Store + Send + Sync)) -> Result<()> {
+        match self {
+            Self::Status { output } => {
+                let host_id = Settings::host_id().expect("failed to get host_id");
+                let hosts = status(store, host_id).await?;
+
+                match output {
+                    Output::Human => print_human(&hosts),
+                    Output::Json => print_json(&hosts)?,
+                }
+
+                Ok(())
+            }
+
+            Self::Verify { repair } => verify(settings, store, *repair).await,
+        }
+    }
+}
+
+async fn verify(
+    settings: &Settings,
+    store: &(impl Store + Send + Sync),
+    repair: bool,
+) -> Result<()> {
+    let key: [u8; 32] = encryption::load_key(settings)
+        .context("could not load encryption key")?
+        .into();
+
+    let mut problems = verify::verify(store, &key).await?;
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+
+    if repair && !problems.is_empty() {
+        let before = store.status().await?;
+
+        for problem in &problems {
+            if let Some(id) = problem.quarantine() {
+                store.quarantine(id, &problem.to_string()).await?;
+                println!("quarantined {}", id.0.as_hyphenated());
+            }
+        }
+
+        for problem in verify::gaps(store, &key, &before).await? {
+            let ProblemKind::Gap { start, end } = problem.kind else {
+                continue;
             };
 
-            println!("{host_string}");
+            let chain = format!("{}/{}", problem.host.0.as_simple(), problem.tag);
+
+            #[cfg(feature = "sync")]
+            match atuin_client::record::sync::download_range(
+                settings,
+                store,
+                &key,
+                problem.host,
+                &problem.tag,
+                start,
+                end,
+            )
+            .await
+            {
+                Ok(n) => println!("downloaded {n}/{} missing records for {chain}", end - start),
+                Err(e) => println!("could not download missing records for {chain}: {e}"),
+            }
 
-            for (tag, idx) in st {
-                println!("\tstore: {tag}");
+            #[cfg(not(feature = "sync"))]
+            println!(
+                "could not download missing records for {chain}: not compiled with sync support"
+            );
+        }
 
-                let first = store.first(*host, tag).await?;
-                let last = store.last(*host, tag).await?;
+        problems = verify::gaps(store, &key, &before).await?;
+        problems.extend(
+            verify::verify(store, &key)
+                .await?
+                .into_iter()
+                .filter(|p| !matches!(p.kind, ProblemKind::Gap { .. })),
+        );
 
-                println!("\t\tidx: {idx}");
+        println!();
+        for problem in &problems {
+            println!("{problem}");
+        }
+    }
 
-                if let Some(first) = first {
-                    println!("\t\tfirst: {}", first.id.0.as_hyphenated());
+    match problems.len() {
+        0 => {
+            println!("record store ok");
+            Ok(())
+        }
+        n if repair => bail!("{n} problems remain after repair"),
+        n => bail!("found {n} problems, run with --repair to try to fix them"),
+    }
+}
 
-                    let time =
-                        OffsetDateTime::from_unix_timestamp_nanos(i128::from(first.timestamp))?;
-                    println!("\t\t\tcreated: {time}");
-                }
+/// Every host and tag in the store, sorted so the output is stable
+async fn status(store: &(impl Store + Send + Sync), host_id: HostId) -> Result<Vec<HostStatus>> {
+    let status = store.status().await?;
 
-                if let Some(last) = last {
-                    println!("\t\tlast: {}", last.id.0.as_hyphenated());
+    let mut hosts = status.hosts.into_iter().collect::<Vec<_>>();
+    hosts.sort_by_key(|(host, _)| host.0);
 
-                    let time =
-                        OffsetDateTime::from_unix_timestamp_nanos(i128::from(last.timestamp))?;
-                    println!("\t\t\tcreated: {time}");
-                }
+    let mut out = Vec::with_capacity(hosts.len());
+
+    for (host, tags) in hosts {
+        let mut tags = tags.into_iter().collect::<Vec<_>>();
+        tags.sort();
+
+        let mut tag_status = Vec::with_capacity(tags.len());
+
+        for (tag, idx) in tags {
+            let first = store.first(host, &tag).await?;
+            let last = store.last(host, &tag).await?;
+
+            tag_status.push(TagStatus {
+                tag,
+                idx,
+                first: first
+                    .map(|r| summary(r.id.0.as_hyphenated().to_string(), r.timestamp))
+                    .transpose()?,
+                last: last
+                    .map(|r| summary(r.id.0.as_hyphenated().to_string(), r.timestamp))
+                    .transpose()?,
+            });
+        }
+
+        out.push(HostStatus {
+            host: host.0.as_hyphenated().to_string(),
+            current: host == host_id,
+            tags: tag_status,
+        });
+    }
+
+    Ok(out)
+}
+
+fn summary(id: String, timestamp: u64) -> Result<RecordSummary> {
+    let created = OffsetDateTime::from_unix_timestamp_nanos(i128::from(timestamp))?;
+
+    Ok(RecordSummary {
+        id,
+        created: created.format(&Rfc3339)?,
+    })
+}
+
+fn print_human(hosts: &[HostStatus]) {
+    for host in hosts {
+        if host.current {
+            println!("host: {} <- CURRENT HOST", host.host);
+        } else {
+            println!("host: {}", host.host);
+        }
+
+        for tag in &host.tags {
+            println!("\tstore: {}", tag.tag);
+            println!("\t\tidx: {}", tag.idx);
+
+            if let Some(first) = &tag.first {
+                println!("\t\tfirst: {}", first.id);
+                println!("\t\t\tcreated: {}", first.created);
             }
 
-            println!();
+            if let Some(last) = &tag.last {
+                println!("\t\tlast: {}", last.id);
+                println!("\t\t\tcreated: {}", last.created);
+            }
         }
 
-        Ok(())
+        println!();
     }
 }
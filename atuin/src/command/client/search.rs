@@ -1,11 +1,13 @@
 use atuin_common::utils;
 use clap::Parser;
-use eyre::Result;
+use eyre::{Context, Result};
 
 use atuin_client::{
     database::Database,
     database::{current_context, OptFilters},
-    history::History,
+    encryption,
+    history::{store::HistoryStore, History},
+    record::sqlite_store::SqliteStore,
     settings::{FilterMode, SearchMode, Settings},
 };
 
@@ -13,9 +15,12 @@ use super::history::ListMode;
 
 mod cursor;
 mod duration;
-mod engines;
+pub(super) mod engines;
 mod history_list;
+mod inspector;
 mod interactive;
+mod keybindings;
+mod theme;
 pub use duration::{format_duration, format_duration_into};
 
 #[allow(clippy::struct_excessive_bools, clippy::struct_field_names)]
@@ -45,6 +50,18 @@ pub struct Cmd {
     #[arg(long)]
     after: Option<String>,
 
+    /// Only include results run on this git branch
+    #[arg(long)]
+    branch: Option<String>,
+
+    /// Only include results run at this git commit, or a prefix of it
+    #[arg(long)]
+    commit: Option<String>,
+
+    /// Only include results recorded with this tag
+    #[arg(long)]
+    tag: Option<String>,
+
     /// How many entries to return at most
     #[arg(long)]
     limit: Option<i64>,
@@ -91,8 +108,8 @@ pub struct Cmd {
     #[arg(long, short)]
     reverse: bool,
 
-    /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {time}, {exit} and
-    /// {relativetime}.
+    /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {time}, {exit},
+    /// {branch}, {commit}, {tag} and {relativetime}.
     /// Example: --format "{time} - [{duration}] - {directory}$\t{command}"
     #[arg(long, short)]
     format: Option<String>,
@@ -103,7 +120,12 @@ pub struct Cmd {
 }
 
 impl Cmd {
-    pub async fn run(self, db: impl Database, settings: &mut Settings) -> Result<()> {
+    pub async fn run(
+        self,
+        db: impl Database,
+        settings: &mut Settings,
+        store: SqliteStore,
+    ) -> Result<()> {
         if (self.delete_it_all || self.delete) && self.limit.is_some() {
             // Because of how deletion is implemented, it will always delete all matches
             // and disregard the limit option. It is also not clear what deletion with a
@@ -140,8 +162,10 @@ impl Cmd {
 
         settings.shell_up_key_binding = self.shell_up_key_binding;
 
+        let mut deleter = Deleter::new(settings, store);
+
         if self.interactive {
-            let item = interactive::history(&self.query, settings, db).await?;
+            let item = interactive::history(&self.query, settings, db, &mut deleter).await?;
             eprintln!("{item}");
         } else {
             let list_mode = ListMode::from_flags(self.human, self.cmd_only);
@@ -153,9 +177,13 @@ impl Cmd {
                 exclude_cwd: self.exclude_cwd,
                 before: self.before,
                 after: self.after,
+                git_branch: self.branch,
+                git_commit: self.commit,
+                tag: self.tag,
                 limit: self.limit,
                 offset: self.offset,
                 reverse: self.reverse,
+                ..OptFilters::default()
             };
 
             let mut entries =
@@ -173,7 +201,7 @@ impl Cmd {
                 while !entries.is_empty() {
                     for entry in &entries {
                         eprintln!("deleting {}", entry.id);
-                        db.delete(entry.clone()).await?;
+                        deleter.delete(&db, entry.clone()).await?;
                     }
 
                     entries =
@@ -193,6 +221,56 @@ impl Cmd {
     }
 }
 
+/// Deletes history, recording each delete in the record store too if it is synced. The store,
+/// and so the encryption key, is only opened once something is deleted, so searching doesn't
+/// need the key
+pub struct Deleter<'a> {
+    settings: &'a Settings,
+    store: SqliteStore,
+    history_store: Option<HistoryStore>,
+}
+
+impl<'a> Deleter<'a> {
+    pub fn new(settings: &'a Settings, store: SqliteStore) -> Self {
+        Self {
+            settings,
+            store,
+            history_store: None,
+        }
+    }
+
+    pub async fn delete(&mut self, db: &impl Database, entry: History) -> Result<()> {
+        // local-only history was never in the record store, so its deletes aren't either
+        if self.settings.sync.records && !entry.is_local_only(self.settings) {
+            self.history_store()?.delete(entry.id.clone()).await?;
+        }
+
+        db.delete(entry).await?;
+
+        Ok(())
+    }
+
+    fn history_store(&mut self) -> Result<&HistoryStore> {
+        if self.history_store.is_none() {
+            let encryption_key: [u8; 32] = encryption::load_key(self.settings)
+                .context("could not load encryption key")?
+                .into();
+            let host_id = Settings::host_id().expect("failed to get host_id");
+
+            self.history_store = Some(HistoryStore::new(
+                self.store.clone(),
+                host_id,
+                encryption_key,
+            ));
+        }
+
+        Ok(self
+            .history_store
+            .as_ref()
+            .expect("history store was just opened"))
+    }
+}
+
 // This is supposed to more-or-less mirror the command line version, so ofc
 // it is going to have a lot of args
 #[allow(clippy::too_many_arguments, clippy::cast_possible_truncation)]
@@ -234,3 +312,98 @@ async fn run_non_interactive(
 
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use atuin_client::{
+        database::{Database, Sqlite},
+        history::{store::HistoryStore, History},
+        record::sqlite_store::SqliteStore,
+        settings::Settings,
+    };
+    use atuin_common::{record::HostId, utils::uuid_v7};
+    use time::OffsetDateTime;
+
+    use super::Deleter;
+
+    fn history(command: &str) -> History {
+        History::import()
+            .timestamp(OffsetDateTime::now_utc())
+            .command(command)
+            .build()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn delete_records_in_store() {
+        let mut settings = Settings::default();
+        settings.sync.records = true;
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let host_id = HostId(uuid_v7());
+        let history_store = HistoryStore::new(store.clone(), host_id, [0; 32]);
+
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let ls = history("ls");
+        let secret = history("export TOKEN=hunter2");
+        for h in [&ls, &secret] {
+            db.save(h).await.unwrap();
+            history_store.push(h.clone()).await.unwrap();
+        }
+
+        let mut deleter = Deleter {
+            settings: &settings,
+            store: store.clone(),
+            history_store: Some(history_store),
+        };
+        deleter.delete(&db, secret.clone()).await.unwrap();
+
+        let secret_row = db.load(&secret.id.0).await.unwrap().unwrap();
+        assert!(secret_row.deleted_at.is_some());
+        assert_ne!(secret_row.command, secret.command);
+
+        // another machine building from the store sees the delete too
+        let other = Sqlite::new("sqlite::memory:").await.unwrap();
+        HistoryStore::new(store, host_id, [0; 32])
+            .build(&other)
+            .await
+            .unwrap();
+        assert!(other
+            .load(&ls.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .deleted_at
+            .is_none());
+        assert!(other
+            .load(&secret.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .deleted_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_without_record_sync_skips_store() {
+        let settings = Settings::default();
+
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        let ls = history("ls");
+        db.save(&ls).await.unwrap();
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let mut deleter = Deleter::new(&settings, store);
+        deleter.delete(&db, ls.clone()).await.unwrap();
+
+        // the key was never needed, so the store was never opened
+        assert!(deleter.history_store.is_none());
+        assert!(db
+            .load(&ls.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .deleted_at
+            .is_some());
+    }
+}
@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use atuin_client::{
     database::{Context, Database},
     history::History,
-    settings::{FilterMode, SearchMode},
+    settings::{FilterMode, SearchMode, Settings},
 };
 use eyre::Result;
 
@@ -11,9 +11,9 @@ use super::cursor::Cursor;
 pub mod db;
 pub mod skim;
 
-pub fn engine(search_mode: SearchMode) -> Box<dyn SearchEngine> {
+pub fn engine(search_mode: SearchMode, settings: &Settings) -> Box<dyn SearchEngine> {
     match search_mode {
-        SearchMode::Skim => Box::new(skim::Search::new()) as Box<_>,
+        SearchMode::Skim => Box::new(skim::Search::new(settings)) as Box<_>,
         mode => Box::new(db::Search(mode)) as Box<_>,
     }
 }
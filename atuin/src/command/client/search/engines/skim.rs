@@ -1,25 +1,71 @@
 use std::path::Path;
 
 use async_trait::async_trait;
-use atuin_client::{database::Database, history::History, settings::FilterMode};
+use atuin_client::{
+    database::Database,
+    history::History,
+    settings::{FilterMode, Settings},
+};
 use eyre::Result;
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use itertools::Itertools;
+#[cfg(unix)]
+use log::warn;
 use time::OffsetDateTime;
 use tokio::task::yield_now;
 
 use super::{SearchEngine, SearchState};
+#[cfg(unix)]
+use crate::command::client::daemon::client::Client;
 
 pub struct Search {
     all_history: Vec<(History, i32)>,
     engine: SkimMatcherV2,
+    /// Where to find the daemon, which keeps history in memory so it doesn't all have to be
+    /// loaded here. None once we know it isn't running
+    #[cfg(unix)]
+    socket_path: Option<String>,
+    #[cfg(unix)]
+    daemon: Option<Client>,
 }
 
 impl Search {
-    pub fn new() -> Self {
+    pub fn new(settings: &Settings) -> Self {
         Search {
             all_history: vec![],
             engine: SkimMatcherV2::default(),
+            #[cfg(unix)]
+            socket_path: Some(settings.daemon.socket_path.clone()),
+            #[cfg(unix)]
+            daemon: None,
+        }
+    }
+
+    #[cfg(unix)]
+    async fn query_daemon(&mut self, state: &SearchState) -> Option<Vec<History>> {
+        let socket_path = self.socket_path.as_deref()?;
+
+        if self.daemon.is_none() {
+            self.daemon = Client::connect(socket_path).await;
+        }
+
+        let Some(daemon) = &mut self.daemon else {
+            self.socket_path = None;
+            return None;
+        };
+
+        let res = daemon
+            .search(state.input.as_str(), state.filter_mode, &state.context)
+            .await;
+
+        match res {
+            Ok(history) => Some(history),
+            Err(e) => {
+                warn!("failed to search with the daemon: {e}");
+                self.socket_path = None;
+                self.daemon = None;
+                None
+            }
         }
     }
 }
@@ -32,6 +78,11 @@ impl SearchEngine for Search {
         db: &mut dyn Database,
     ) -> Result<Vec<History>> {
         if self.all_history.is_empty() {
+            #[cfg(unix)]
+            if let Some(history) = self.query_daemon(state).await {
+                return Ok(history);
+            }
+
             self.all_history = db.all_with_count().await.unwrap();
         }
 
@@ -39,7 +90,7 @@ impl SearchEngine for Search {
     }
 }
 
-async fn fuzzy_search(
+pub async fn fuzzy_search(
     engine: &SkimMatcherV2,
     state: &SearchState,
     all_history: &[(History, i32)],
@@ -4,17 +4,18 @@ use atuin_client::history::History;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::Style,
     widgets::{Block, StatefulWidget, Widget},
 };
 use time::OffsetDateTime;
 
-use super::format_duration;
+use super::{format_duration, theme::Theme};
 
 pub struct HistoryList<'a> {
     history: &'a [History],
     block: Option<Block<'a>>,
     inverted: bool,
+    theme: &'a Theme,
 }
 
 #[derive(Default)]
@@ -64,6 +65,7 @@ impl<'a> StatefulWidget for HistoryList<'a> {
             y: 0,
             state,
             inverted: self.inverted,
+            theme: self.theme,
         };
 
         for item in self.history.iter().skip(state.offset).take(end - start) {
@@ -80,11 +82,12 @@ impl<'a> StatefulWidget for HistoryList<'a> {
 }
 
 impl<'a> HistoryList<'a> {
-    pub fn new(history: &'a [History], inverted: bool) -> Self {
+    pub fn new(history: &'a [History], inverted: bool, theme: &'a Theme) -> Self {
         Self {
             history,
             block: None,
             inverted,
+            theme,
         }
     }
 
@@ -115,6 +118,7 @@ struct DrawState<'a> {
     y: u16,
     state: &'a ListState,
     inverted: bool,
+    theme: &'a Theme,
 }
 
 // longest line prefix I could come up with
@@ -130,22 +134,22 @@ impl DrawState<'_> {
         let i = self.y as usize + self.state.offset;
         let i = i.checked_sub(self.state.selected);
         let i = i.unwrap_or(10).min(10) * 2;
-        self.draw(&SLICES[i..i + 3], Style::default());
+        self.draw(&SLICES[i..i + 3], self.theme.index);
     }
 
     fn duration(&mut self, h: &History) {
-        let status = Style::default().fg(if h.success() {
-            Color::Green
+        let status = if h.success() {
+            self.theme.success
         } else {
-            Color::Red
-        });
+            self.theme.failure
+        };
         let duration = Duration::from_nanos(u64::try_from(h.duration).unwrap_or(0));
         self.draw(&format_duration(duration), status);
     }
 
     #[allow(clippy::cast_possible_truncation)] // we know that time.len() will be <6
     fn time(&mut self, h: &History) {
-        let style = Style::default().fg(Color::Blue);
+        let style = self.theme.time;
 
         // Account for the chance that h.timestamp is "in the future"
         // This would mean that "since" is negative, and the unwrap here
@@ -163,10 +167,11 @@ impl DrawState<'_> {
     }
 
     fn command(&mut self, h: &History) {
-        let mut style = Style::default();
-        if self.y as usize + self.state.offset == self.state.selected {
-            style = style.fg(Color::Red).add_modifier(Modifier::BOLD);
-        }
+        let style = if self.y as usize + self.state.offset == self.state.selected {
+            self.theme.selected
+        } else {
+            self.theme.command
+        };
 
         for section in h.command.split_ascii_whitespace() {
             self.x += 1;
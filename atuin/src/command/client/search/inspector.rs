@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use atuin_client::{
+    history::{History, HistoryStats},
+    settings::Settings,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+    Frame,
+};
+use time::{macros::format_description, UtcOffset};
+
+use super::{format_duration, theme::Theme};
+
+static TIME_FMT: &[time::format_description::FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour repr:24]:[minute]:[second]");
+
+/// Draw everything we know about `history` into `chunk`
+#[allow(clippy::cast_possible_truncation)]
+pub fn draw(
+    f: &mut Frame,
+    chunk: Rect,
+    history: &History,
+    stats: &HistoryStats,
+    settings: &Settings,
+    theme: &Theme,
+) {
+    let command_height = history.command.lines().count().clamp(1, 8) as u16 + 2;
+
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(command_height),
+            Constraint::Min(5),
+            Constraint::Length(5),
+        ])
+        .split(chunk);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+        .split(vertical[1]);
+
+    let command = Paragraph::new(history.command.as_str())
+        .wrap(Wrap { trim: false })
+        .block(block("Command", theme));
+    f.render_widget(command, vertical[0]);
+
+    let details = Paragraph::new(details(history, settings, theme))
+        .wrap(Wrap { trim: false })
+        .block(block("Details", theme));
+    f.render_widget(details, columns[0]);
+
+    let runs = Paragraph::new(runs(stats, theme))
+        .wrap(Wrap { trim: false })
+        .block(block("Runs", theme));
+    f.render_widget(runs, columns[1]);
+
+    let session = Paragraph::new(session(history, stats, theme)).block(block("Session", theme));
+    f.render_widget(session, vertical[2]);
+}
+
+fn block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border)
+        .title(Span::styled(title, theme.label))
+}
+
+fn field<'a>(name: &'a str, value: impl Into<Span<'a>>, theme: &Theme) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{name:<13}"), theme.label),
+        value.into(),
+    ])
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn duration(nanos: i64) -> String {
+    if nanos < 0 {
+        "unknown".to_string()
+    } else {
+        format_duration(Duration::from_nanos(nanos as u64))
+    }
+}
+
+fn exit(exit: i64, theme: &Theme) -> Span<'static> {
+    match exit {
+        -1 => Span::raw("unknown"),
+        0 => Span::styled("0", theme.success),
+        n => Span::styled(n.to_string(), theme.failure),
+    }
+}
+
+fn details<'a>(history: &'a History, settings: &Settings, theme: &Theme) -> Text<'a> {
+    let time = history
+        .timestamp
+        .to_offset(settings.local_tz.unwrap_or(UtcOffset::UTC))
+        .format(TIME_FMT)
+        .unwrap_or_default();
+
+    let (host, user) = history
+        .hostname
+        .split_once(':')
+        .unwrap_or((history.hostname.as_str(), ""));
+
+    let mut lines = vec![
+        field("Time", time, theme),
+        field("Directory", history.cwd.as_str(), theme),
+        field("Host", host, theme),
+        field("User", user, theme),
+        field("Session", history.session.as_str(), theme),
+        field("Exit", exit(history.exit, theme), theme),
+        field("Duration", duration(history.duration), theme),
+    ];
+
+    if let Some(branch) = &history.git_branch {
+        lines.push(field("Git branch", branch.as_str(), theme));
+    }
+
+    if let Some(commit) = &history.git_commit {
+        lines.push(field("Git commit", commit.as_str(), theme));
+    }
+
+    if let Some(tag) = &history.tag {
+        lines.push(field("Tag", tag.as_str(), theme));
+    }
+
+    Text::from(lines)
+}
+
+fn runs(stats: &HistoryStats, theme: &Theme) -> Text<'static> {
+    let success_rate = stats
+        .success_rate()
+        .map_or_else(|| "unknown".to_string(), |r| format!("{:.1}%", r * 100.0));
+
+    let average_duration = stats
+        .average_duration
+        .map_or_else(|| "unknown".to_string(), duration);
+
+    let mut lines = vec![
+        field("Total runs", stats.total.to_string(), theme),
+        field("Success rate", success_rate, theme),
+        field("Avg duration", average_duration, theme),
+        Line::default(),
+        Line::styled("Exit codes", theme.label),
+    ];
+
+    lines.extend(stats.exits.iter().map(|(code, count)| {
+        Line::from(vec![
+            Span::raw("  "),
+            exit(*code, theme),
+            Span::raw(format!(": {count}")),
+        ])
+    }));
+
+    Text::from(lines)
+}
+
+fn session<'a>(history: &'a History, stats: &'a HistoryStats, theme: &Theme) -> Text<'a> {
+    let line = |label: &'a str, history: Option<&'a History>, style: Style| {
+        let command = history.map_or("", |h| h.command.lines().next().unwrap_or_default());
+
+        field(label, Span::styled(command, style), theme)
+    };
+
+    Text::from(vec![
+        line("Before", stats.previous.as_ref(), theme.faded),
+        line("This", Some(history), theme.command),
+        line("After", stats.next.as_ref(), theme.faded),
+    ])
+}
@@ -5,8 +5,9 @@ use std::{
 
 use atuin_common::utils;
 use crossterm::{
+    cursor::SetCursorStyle,
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
         KeyboardEnhancementFlags, MouseEvent, PopKeyboardEnhancementFlags,
         PushKeyboardEnhancementFlags,
     },
@@ -19,20 +20,28 @@ use unicode_width::UnicodeWidthStr;
 
 use atuin_client::{
     database::{current_context, Database},
-    history::History,
-    settings::{ExitMode, FilterMode, SearchMode, Settings},
+    history::{History, HistoryId, HistoryStats},
+    settings::{ExitMode, FilterMode, KeymapStyle, SearchMode, Settings},
 };
 
 use super::{
     cursor::Cursor,
     engines::{SearchEngine, SearchState},
     history_list::{HistoryList, ListState, PREFIX_LENGTH},
+    inspector,
+    keybindings::{Action, KeyChord, Keymap, Mode},
+    theme::Theme,
+};
+use crate::{
+    command::client::{
+        history::session_timeline,
+        search::{engines, Deleter},
+    },
+    VERSION,
 };
-use crate::{command::client::search::engines, VERSION};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders, Paragraph},
     Frame, Terminal, TerminalOptions, Viewport,
@@ -41,6 +50,10 @@ use ratatui::{
 enum InputAction {
     Accept(usize),
     Copy(usize),
+    Delete(usize),
+    Timeline(usize),
+    TimelinePrevious,
+    TimelineNext,
     ReturnOriginal,
     ReturnQuery,
     Continue,
@@ -57,10 +70,33 @@ struct State {
     results_len: usize,
     accept: bool,
 
+    /// Whether the inspector is open, in place of the results list
+    inspecting: bool,
+    /// Stats for the selected entry, loaded when the inspector needs them
+    stats: Option<(usize, HistoryStats)>,
+    /// When set, the results are a session timeline rather than search results
+    timeline: Option<Timeline>,
+    /// The result waiting for the user to confirm it should be deleted
+    confirm_delete: Option<usize>,
+
+    keymap: Keymap,
+    theme: Theme,
+    /// Whether keys type into the query, or are vim normal mode commands
+    mode: Mode,
+
     search: SearchState,
     engine: Box<dyn SearchEngine>,
 }
 
+/// A session, and how many sessions either side of it to show along with it
+struct Timeline {
+    session: String,
+    previous: usize,
+    next: usize,
+    /// The sessions currently shown, oldest first
+    sessions: Vec<String>,
+}
+
 #[derive(Clone, Copy)]
 struct StyleState {
     compact: bool,
@@ -74,6 +110,50 @@ impl State {
 
         self.results_state.select(0);
         self.results_len = results.len();
+        self.stats = None;
+
+        Ok(results)
+    }
+
+    /// Load the results for the current timeline, keeping `keep` selected if it is still there
+    async fn timeline_results(
+        &mut self,
+        db: &impl Database,
+        keep: Option<HistoryId>,
+    ) -> Result<Vec<History>> {
+        let Some(timeline) = &mut self.timeline else {
+            return Ok(Vec::new());
+        };
+
+        let sessions = session_timeline(
+            db,
+            &self.search.context,
+            &timeline.session,
+            timeline.previous,
+            timeline.next,
+        )
+        .await?;
+
+        // don't keep asking for sessions that don't exist
+        let anchor = sessions
+            .iter()
+            .position(|(s, _)| *s == timeline.session)
+            .unwrap_or_default();
+        timeline.previous = anchor;
+        timeline.next = sessions.len() - anchor - 1;
+        timeline.sessions = sessions.iter().map(|(s, _)| s.clone()).collect();
+
+        // results are shown newest first
+        let mut results: Vec<History> = sessions.into_iter().flat_map(|(_, h)| h).collect();
+        results.reverse();
+
+        let selected = keep
+            .and_then(|id| results.iter().position(|h| h.id == id))
+            .unwrap_or_default();
+
+        self.results_state.select(selected);
+        self.results_len = results.len();
+        self.stats = None;
 
         Ok(results)
     }
@@ -95,9 +175,23 @@ impl State {
             _ => InputAction::Continue,
         };
         execute!(w, DisableMouseCapture)?;
+        self.set_cursor_style(w)?;
         Ok(r)
     }
 
+    /// Show which vim mode we're in with the cursor shape: a block in normal mode, a bar when typing
+    fn set_cursor_style<W: Write>(&self, w: &mut W) -> Result<()> {
+        if self.keymap.style == KeymapStyle::Vim {
+            let style = if self.mode == Mode::Normal {
+                SetCursorStyle::SteadyBlock
+            } else {
+                SetCursorStyle::SteadyBar
+            };
+            execute!(w, style)?;
+        }
+        Ok(())
+    }
+
     fn handle_mouse_input(&mut self, input: MouseEvent) -> InputAction {
         match input.kind {
             event::MouseEventKind::ScrollDown => {
@@ -118,6 +212,30 @@ impl State {
         InputAction::Continue
     }
 
+    /// `y`, or pressing delete again, deletes the entry. Anything else cancels
+    fn handle_confirm_delete_input(&self, index: usize, input: &KeyEvent) -> InputAction {
+        let delete_again =
+            self.keymap.action(KeyChord::from(input), self.bindings()) == Some(Action::DeleteEntry);
+
+        if delete_again || matches!(input.code, KeyCode::Char('y' | 'Y')) {
+            InputAction::Delete(index)
+        } else {
+            InputAction::Continue
+        }
+    }
+
+    /// The bindings in use: those of the inspector or timeline while they're open, otherwise those
+    /// of the search's mode
+    fn bindings(&self) -> Mode {
+        if self.inspecting {
+            Mode::Inspector
+        } else if self.timeline.is_some() {
+            Mode::Timeline
+        } else {
+            self.mode
+        }
+    }
+
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::cognitive_complexity)]
     fn handle_key_input(&mut self, settings: &Settings, input: &KeyEvent) -> InputAction {
@@ -125,101 +243,80 @@ impl State {
             return InputAction::Continue;
         }
 
-        let ctrl = input.modifiers.contains(KeyModifiers::CONTROL);
-        let alt = input.modifiers.contains(KeyModifiers::ALT);
-
-        // Use Ctrl-n instead of Alt-n?
-        let modfr = if settings.ctrl_n_shortcuts { ctrl } else { alt };
+        if let Some(index) = self.confirm_delete.take() {
+            return self.handle_confirm_delete_input(index, input);
+        }
 
         // reset the state, will be set to true later if user really did change it
         self.switched_search_mode = false;
-        match input.code {
-            KeyCode::Char('c' | 'g') if ctrl => return InputAction::ReturnOriginal,
-            KeyCode::Esc => {
-                return match settings.exit_mode {
-                    ExitMode::ReturnOriginal => InputAction::ReturnOriginal,
-                    ExitMode::ReturnQuery => InputAction::ReturnQuery,
-                }
-            }
-            KeyCode::Tab => {
-                return InputAction::Accept(self.results_state.selected());
+
+        let bindings = self.bindings();
+
+        if let Some(action) = self.keymap.action(KeyChord::from(input), bindings) {
+            return self.handle_action(settings, action);
+        }
+
+        // anything unbound is typed into the query, unless we're in vim's normal mode or
+        // looking at something else
+        if let KeyCode::Char(c) = input.code {
+            if bindings == Mode::Insert {
+                self.search.input.insert(c);
             }
-            KeyCode::Enter => {
+        }
+
+        InputAction::Continue
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn handle_action(&mut self, settings: &Settings, action: Action) -> InputAction {
+        let exit = match settings.exit_mode {
+            ExitMode::ReturnOriginal => InputAction::ReturnOriginal,
+            ExitMode::ReturnQuery => InputAction::ReturnQuery,
+        };
+        let selected = self.results_state.selected();
+
+        match action {
+            Action::Exit => return exit,
+            Action::ReturnOriginal => return InputAction::ReturnOriginal,
+            Action::ReturnQuery => return InputAction::ReturnQuery,
+            Action::Accept => {
                 if settings.enter_accept {
                     self.accept = true;
                 }
 
-                return InputAction::Accept(self.results_state.selected());
-            }
-            KeyCode::Char('y') if ctrl => {
-                return InputAction::Copy(self.results_state.selected());
-            }
-            KeyCode::Char(c @ '1'..='9') if modfr => {
-                return c.to_digit(10).map_or(InputAction::Continue, |c| {
-                    InputAction::Accept(self.results_state.selected() + c as usize)
-                })
-            }
-            KeyCode::Left if ctrl => self
-                .search
-                .input
-                .prev_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Char('b') if alt => self
-                .search
-                .input
-                .prev_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Left => {
-                self.search.input.left();
+                return InputAction::Accept(selected);
             }
-            KeyCode::Char('b') if ctrl => {
-                self.search.input.left();
+            Action::Edit => return InputAction::Accept(selected),
+            Action::Execute => {
+                self.accept = true;
+                return InputAction::Accept(selected);
             }
-            KeyCode::Right if ctrl => self
-                .search
-                .input
-                .next_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Char('f') if alt => self
-                .search
-                .input
-                .next_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Right => self.search.input.right(),
-            KeyCode::Char('f') if ctrl => self.search.input.right(),
-            KeyCode::Char('a') if ctrl => self.search.input.start(),
-            KeyCode::Home => self.search.input.start(),
-            KeyCode::Char('e') if ctrl => self.search.input.end(),
-            KeyCode::End => self.search.input.end(),
-            KeyCode::Backspace if ctrl => self
-                .search
-                .input
-                .remove_prev_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Backspace => {
-                self.search.input.back();
+            Action::AcceptNth(n) => return InputAction::Accept(selected + n),
+            Action::Copy => return InputAction::Copy(selected),
+            Action::Inspect => self.inspecting = self.results_len > 0,
+            Action::Timeline => {
+                self.inspecting = false;
+                return InputAction::Timeline(selected);
             }
-            KeyCode::Delete if ctrl => self
-                .search
-                .input
-                .remove_next_word(&settings.word_chars, settings.word_jump_mode),
-            KeyCode::Delete => {
-                self.search.input.remove();
-            }
-            KeyCode::Char('d') if ctrl => {
-                if self.search.input.as_str().is_empty() {
-                    return InputAction::ReturnOriginal;
+            Action::DeleteEntry => {
+                if self.results_len > 0 {
+                    self.confirm_delete = Some(selected);
                 }
-                self.search.input.remove();
             }
-            KeyCode::Char('w') if ctrl => {
-                // remove the first batch of whitespace
-                while matches!(self.search.input.back(), Some(c) if c.is_whitespace()) {}
-                while self.search.input.left() {
-                    if self.search.input.char().unwrap().is_whitespace() {
-                        self.search.input.right(); // found whitespace, go back right
-                        break;
-                    }
-                    self.search.input.remove();
+            Action::Back => {
+                if self.inspecting {
+                    self.inspecting = false;
+                } else {
+                    self.timeline = None;
                 }
             }
-            KeyCode::Char('u') if ctrl => self.search.input.clear(),
-            KeyCode::Char('r') if ctrl => {
+            Action::PreviousSession if self.timeline.is_some() => {
+                return InputAction::TimelinePrevious;
+            }
+            Action::NextSession if self.timeline.is_some() => return InputAction::TimelineNext,
+            // moving through sessions does nothing outside the timeline
+            Action::None | Action::PreviousSession | Action::NextSession => {}
+            Action::CycleFilterMode => {
                 let filter_modes = if settings.workspaces && self.search.context.git_root.is_some()
                 {
                     vec![
@@ -242,69 +339,105 @@ impl State {
                 let i = (i + 1) % filter_modes.len();
                 self.search.filter_mode = filter_modes[i];
             }
-            KeyCode::Char('s') if ctrl => {
+            Action::CycleSearchMode => {
                 self.switched_search_mode = true;
                 self.search_mode = self.search_mode.next(settings);
-                self.engine = engines::engine(self.search_mode);
+                self.engine = engines::engine(self.search_mode, settings);
             }
-            KeyCode::Down if !settings.invert && self.results_state.selected() == 0 => {
-                return match settings.exit_mode {
-                    ExitMode::ReturnOriginal => InputAction::ReturnOriginal,
-                    ExitMode::ReturnQuery => InputAction::ReturnQuery,
+            Action::UpOrExit if settings.invert && selected == 0 => return exit,
+            Action::DownOrExit if !settings.invert && selected == 0 => return exit,
+            Action::Up | Action::UpOrExit => {
+                if settings.invert {
+                    self.scroll_down(1);
+                } else {
+                    self.scroll_up(1);
                 }
             }
-            KeyCode::Up if settings.invert && self.results_state.selected() == 0 => {
-                return match settings.exit_mode {
-                    ExitMode::ReturnOriginal => InputAction::ReturnOriginal,
-                    ExitMode::ReturnQuery => InputAction::ReturnQuery,
+            Action::Down | Action::DownOrExit => {
+                if settings.invert {
+                    self.scroll_up(1);
+                } else {
+                    self.scroll_down(1);
                 }
             }
-            KeyCode::Down if !settings.invert => {
-                self.scroll_down(1);
-            }
-            KeyCode::Up if settings.invert => {
-                self.scroll_down(1);
-            }
-            KeyCode::Char('n' | 'j') if ctrl && !settings.invert => {
-                self.scroll_down(1);
-            }
-            KeyCode::Char('n' | 'j') if ctrl && settings.invert => {
-                self.scroll_up(1);
+            Action::PageUp => {
+                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
+                if settings.invert {
+                    self.scroll_down(scroll_len);
+                } else {
+                    self.scroll_up(scroll_len);
+                }
             }
-            KeyCode::Up if !settings.invert => {
-                self.scroll_up(1);
+            Action::PageDown => {
+                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
+                if settings.invert {
+                    self.scroll_up(scroll_len);
+                } else {
+                    self.scroll_down(scroll_len);
+                }
             }
-            KeyCode::Down if settings.invert => {
-                self.scroll_up(1);
+            Action::CursorLeft => {
+                self.search.input.left();
             }
-            KeyCode::Char('p' | 'k') if ctrl && !settings.invert => {
-                self.scroll_up(1);
+            Action::CursorRight => self.search.input.right(),
+            Action::CursorWordLeft => self
+                .search
+                .input
+                .prev_word(&settings.word_chars, settings.word_jump_mode),
+            Action::CursorWordRight => self
+                .search
+                .input
+                .next_word(&settings.word_chars, settings.word_jump_mode),
+            Action::CursorStart => self.search.input.start(),
+            Action::CursorEnd => self.search.input.end(),
+            Action::DeleteCharBefore => {
+                self.search.input.back();
             }
-            KeyCode::Char('p' | 'k') if ctrl && settings.invert => {
-                self.scroll_down(1);
+            Action::DeleteCharAfter => {
+                self.search.input.remove();
             }
-            KeyCode::Char('l') if ctrl => {
-                return InputAction::Redraw;
+            Action::DeleteWordBefore => self
+                .search
+                .input
+                .remove_prev_word(&settings.word_chars, settings.word_jump_mode),
+            Action::DeleteWordAfter => self
+                .search
+                .input
+                .remove_next_word(&settings.word_chars, settings.word_jump_mode),
+            Action::DeleteCharOrExit => {
+                if self.search.input.as_str().is_empty() {
+                    return InputAction::ReturnOriginal;
+                }
+                self.search.input.remove();
             }
-            KeyCode::Char(c) => self.search.input.insert(c),
-            KeyCode::PageDown if !settings.invert => {
-                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
-                self.scroll_down(scroll_len);
+            Action::UnixWordRubout => {
+                // remove the first batch of whitespace
+                while matches!(self.search.input.back(), Some(c) if c.is_whitespace()) {}
+                while self.search.input.left() {
+                    if self.search.input.char().unwrap().is_whitespace() {
+                        self.search.input.right(); // found whitespace, go back right
+                        break;
+                    }
+                    self.search.input.remove();
+                }
             }
-            KeyCode::PageDown if settings.invert => {
-                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
-                self.scroll_up(scroll_len);
+            Action::ClearLine => self.search.input.clear(),
+            Action::Redraw => return InputAction::Redraw,
+            Action::VimNormal => self.mode = Mode::Normal,
+            Action::VimInsert => self.mode = Mode::Insert,
+            Action::VimAppend => {
+                self.search.input.right();
+                self.mode = Mode::Insert;
             }
-            KeyCode::PageUp if !settings.invert => {
-                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
-                self.scroll_up(scroll_len);
+            Action::VimInsertStart => {
+                self.search.input.start();
+                self.mode = Mode::Insert;
             }
-            KeyCode::PageUp if settings.invert => {
-                let scroll_len = self.results_state.max_entries() - settings.scroll_context_lines;
-                self.scroll_down(scroll_len);
+            Action::VimAppendEnd => {
+                self.search.input.end();
+                self.mode = Mode::Insert;
             }
-            _ => {}
-        };
+        }
 
         InputAction::Continue
     }
@@ -319,8 +452,24 @@ impl State {
         self.results_state.select(i.min(self.results_len - 1));
     }
 
+    /// Make sure the stats for the selected entry are loaded, if the inspector is open
+    async fn load_stats(&mut self, results: &[History], db: &dyn Database) -> Result<()> {
+        let selected = self.results_state.selected();
+
+        if !self.inspecting || matches!(self.stats, Some((i, _)) if i == selected) {
+            return Ok(());
+        }
+
+        if let Some(history) = results.get(selected) {
+            self.stats = Some((selected, db.stats(history).await?));
+        }
+
+        Ok(())
+    }
+
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::bool_to_int_with_if)]
+    #[allow(clippy::too_many_lines)]
     fn draw(&mut self, f: &mut Frame, results: &[History], settings: &Settings) {
         let compact = match settings.style {
             atuin_client::settings::Style::Auto => f.size().height < 14,
@@ -330,7 +479,9 @@ impl State {
         let invert = settings.invert;
         let border_size = if compact { 0 } else { 1 };
         let preview_width = f.size().width - 2;
-        let preview_height = if settings.show_preview {
+        let preview_height = if self.inspecting {
+            0
+        } else if settings.show_preview {
             let longest_command = results
                 .iter()
                 .max_by(|h1, h2| h1.command.len().cmp(&h2.command.len()));
@@ -401,23 +552,40 @@ impl State {
         let title = self.build_title();
         f.render_widget(title, header_chunks[0]);
 
-        let help = self.build_help();
+        let help = if self.inspecting {
+            Self::build_inspector_help(&self.theme)
+        } else if self.timeline.is_some() {
+            Self::build_timeline_help(&self.theme)
+        } else {
+            self.build_help()
+        };
         f.render_widget(help, header_chunks[1]);
 
         let stats = self.build_stats();
         f.render_widget(stats, header_chunks[2]);
 
-        let results_list = Self::build_results_list(style, results);
-        f.render_stateful_widget(results_list, results_list_chunk, &mut self.results_state);
+        match (&self.stats, results.get(self.results_state.selected())) {
+            (Some((_, stats)), Some(history)) if self.inspecting => {
+                inspector::draw(f, results_list_chunk, history, stats, settings, &self.theme);
+            }
+            _ => {
+                let results_list = Self::build_results_list(style, results, &self.theme);
+                f.render_stateful_widget(results_list, results_list_chunk, &mut self.results_state);
+            }
+        }
 
-        let input = self.build_input(style);
+        let prompt = self.delete_prompt(results);
+        let input = self.build_input(style, prompt.as_deref());
         f.render_widget(input, input_chunk);
 
         let preview =
             self.build_preview(results, compact, preview_width, preview_chunk.width.into());
         f.render_widget(preview, preview_chunk);
 
-        let extra_width = UnicodeWidthStr::width(self.search.input.substring());
+        let extra_width = prompt.as_deref().map_or_else(
+            || UnicodeWidthStr::width(self.search.input.substring()),
+            UnicodeWidthStr::width,
+        );
 
         let cursor_offset = if compact { 0 } else { 1 };
         f.set_cursor(
@@ -431,12 +599,12 @@ impl State {
         let title = if self.update_needed.is_some() {
             Paragraph::new(Text::from(Span::styled(
                 format!("Atuin v{VERSION} - UPGRADE"),
-                Style::default().add_modifier(Modifier::BOLD).fg(Color::Red),
+                self.theme.update,
             )))
         } else {
             Paragraph::new(Text::from(Span::styled(
                 format!("Atuin v{VERSION}"),
-                Style::default().add_modifier(Modifier::BOLD),
+                self.theme.title,
             )))
         };
         title.alignment(Alignment::Left)
@@ -445,36 +613,95 @@ impl State {
     #[allow(clippy::unused_self)]
     fn build_help(&mut self) -> Paragraph {
         let help = Paragraph::new(Text::from(Line::from(vec![
-            Span::styled("<esc>", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("<esc>", self.theme.help_key),
             Span::raw(": exit"),
             Span::raw(", "),
-            Span::styled("<tab>", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("<tab>", self.theme.help_key),
             Span::raw(": edit"),
             Span::raw(", "),
-            Span::styled("<enter>", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("<enter>", self.theme.help_key),
             Span::raw(": execute"),
             Span::raw(", "),
-            Span::styled("<ctrl-r>", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("<ctrl-r>", self.theme.help_key),
             Span::raw(": filter toggle"),
+            Span::raw(", "),
+            Span::styled("<ctrl-o>", self.theme.help_key),
+            Span::raw(": inspect"),
         ])))
-        .style(Style::default().fg(Color::DarkGray))
+        .style(self.theme.help)
         .alignment(Alignment::Center);
 
         help
     }
 
+    fn build_inspector_help(theme: &Theme) -> Paragraph<'static> {
+        let key = |k| Span::styled(k, theme.help_key);
+
+        Paragraph::new(Text::from(Line::from(vec![
+            key("<esc>"),
+            Span::raw(": back"),
+            Span::raw(", "),
+            key("<up/down>"),
+            Span::raw(": select"),
+            Span::raw(", "),
+            key("<y>"),
+            Span::raw(": copy"),
+            Span::raw(", "),
+            key("<e>"),
+            Span::raw(": edit"),
+            Span::raw(", "),
+            key("<d>"),
+            Span::raw(": delete"),
+            Span::raw(", "),
+            key("<s>"),
+            Span::raw(": session"),
+        ])))
+        .style(theme.help)
+        .alignment(Alignment::Center)
+    }
+
+    fn build_timeline_help(theme: &Theme) -> Paragraph<'static> {
+        let key = |k| Span::styled(k, theme.help_key);
+
+        Paragraph::new(Text::from(Line::from(vec![
+            key("<esc>"),
+            Span::raw(": back"),
+            Span::raw(", "),
+            key("<[>"),
+            Span::raw(": previous session"),
+            Span::raw(", "),
+            key("<]>"),
+            Span::raw(": next session"),
+            Span::raw(", "),
+            key("<ctrl-o>"),
+            Span::raw(": inspect"),
+        ])))
+        .style(theme.help)
+        .alignment(Alignment::Center)
+    }
+
     fn build_stats(&mut self) -> Paragraph {
-        let stats = Paragraph::new(Text::from(Span::raw(format!(
-            "history count: {}",
-            self.history_count,
-        ))))
-        .style(Style::default().fg(Color::DarkGray))
-        .alignment(Alignment::Right);
+        let stats = match &self.timeline {
+            Some(timeline) => format!(
+                "sessions: {}, commands: {}",
+                timeline.sessions.len(),
+                self.results_len
+            ),
+            None => format!("history count: {}", self.history_count),
+        };
+
+        let stats = Paragraph::new(Text::from(Span::raw(stats)))
+            .style(self.theme.help)
+            .alignment(Alignment::Right);
         stats
     }
 
-    fn build_results_list(style: StyleState, results: &[History]) -> HistoryList {
-        let results_list = HistoryList::new(results, style.invert);
+    fn build_results_list<'a>(
+        style: StyleState,
+        results: &'a [History],
+        theme: &'a Theme,
+    ) -> HistoryList<'a> {
+        let results_list = HistoryList::new(results, style.invert, theme);
         if style.compact {
             results_list
         } else if style.invert {
@@ -482,21 +709,35 @@ impl State {
                 Block::default()
                     .borders(Borders::LEFT | Borders::RIGHT)
                     .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
                     .title(format!("{:─>width$}", "", width = style.inner_width - 2)),
             )
         } else {
             results_list.block(
                 Block::default()
                     .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
-                    .border_type(BorderType::Rounded),
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border),
             )
         }
     }
 
-    fn build_input(&mut self, style: StyleState) -> Paragraph {
+    /// The question to ask while waiting for a delete to be confirmed
+    fn delete_prompt(&self, results: &[History]) -> Option<String> {
+        let history = results.get(self.confirm_delete?)?;
+        let command = history.command.lines().next().unwrap_or_default();
+
+        Some(format!("delete `{command}` from history? [y/N]"))
+    }
+
+    fn build_input(&mut self, style: StyleState, prompt: Option<&str>) -> Paragraph {
         /// Max width of the UI box showing current mode
         const MAX_WIDTH: usize = 14;
-        let (pref, mode) = if self.switched_search_mode {
+        let (pref, mode) = if prompt.is_some() {
+            ("", "DELETE")
+        } else if self.timeline.is_some() {
+            ("", "TIMELINE")
+        } else if self.switched_search_mode {
             (" SRCH:", self.search_mode.as_str())
         } else {
             ("", self.search.filter_mode.as_str())
@@ -504,21 +745,31 @@ impl State {
         let mode_width = MAX_WIDTH - pref.len();
         // sanity check to ensure we don't exceed the layout limits
         debug_assert!(mode_width >= mode.len(), "mode name '{mode}' is too long!");
-        let input = format!("[{pref}{mode:^mode_width$}] {}", self.search.input.as_str(),);
-        let input = Paragraph::new(input);
+        let text = prompt.unwrap_or_else(|| {
+            self.timeline
+                .as_ref()
+                .map_or(self.search.input.as_str(), |t| t.session.as_str())
+        });
+        let input = Paragraph::new(Line::from(vec![
+            Span::styled(format!("[{pref}{mode:^mode_width$}]"), self.theme.mode),
+            Span::raw(" "),
+            Span::styled(text.to_string(), self.theme.input),
+        ]));
         if style.compact {
             input
         } else if style.invert {
             input.block(
                 Block::default()
                     .borders(Borders::LEFT | Borders::RIGHT | Borders::TOP)
-                    .border_type(BorderType::Rounded),
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme.border),
             )
         } else {
             input.block(
                 Block::default()
                     .borders(Borders::LEFT | Borders::RIGHT)
                     .border_type(BorderType::Rounded)
+                    .border_style(self.theme.border)
                     .title(format!("{:─>width$}", "", width = style.inner_width - 2)),
             )
         }
@@ -549,12 +800,13 @@ impl State {
                 .join("\n")
         };
         let preview = if compact {
-            Paragraph::new(command).style(Style::default().fg(Color::DarkGray))
+            Paragraph::new(command).style(self.theme.faded)
         } else {
-            Paragraph::new(command).block(
+            Paragraph::new(command).style(self.theme.preview).block(
                 Block::default()
                     .borders(Borders::BOTTOM | Borders::LEFT | Borders::RIGHT)
                     .border_type(BorderType::Rounded)
+                    .border_style(self.theme.border)
                     .title(format!("{:─>width$}", "", width = chunk_width - 2)),
             )
         };
@@ -627,7 +879,12 @@ pub async fn history(
     query: &[String],
     settings: &Settings,
     mut db: impl Database,
+    deleter: &mut Deleter<'_>,
 ) -> Result<String> {
+    // check the key bindings before taking over the terminal, so any errors are readable
+    let keymap = Keymap::new(settings)?;
+    let theme = Theme::load(settings)?;
+
     let stdout = Stdout::new(settings.inline_height > 0)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::with_options(
@@ -678,20 +935,30 @@ pub async fn history(
             },
             context,
         },
-        engine: engines::engine(search_mode),
+        engine: engines::engine(search_mode, settings),
         results_len: 0,
         accept: false,
+        inspecting: false,
+        stats: None,
+        timeline: None,
+        confirm_delete: None,
+        keymap,
+        theme,
+        mode: Mode::Insert,
     };
 
     let mut results = app.query_results(&mut db).await?;
+    app.set_cursor_style(&mut std::io::stdout())?;
 
     let accept;
     let result = 'render: loop {
+        app.load_stats(&results, &db).await?;
         terminal.draw(|f| app.draw(f, &results, settings))?;
 
         let initial_input = app.search.input.as_str().to_owned();
         let initial_filter_mode = app.search.filter_mode;
         let initial_search_mode = app.search_mode;
+        let initial_timeline = app.timeline.is_some();
 
         let event_ready = tokio::task::spawn_blocking(|| event::poll(Duration::from_millis(250)));
 
@@ -705,6 +972,45 @@ pub async fn history(
                                 terminal.clear()?;
                                 terminal.draw(|f| app.draw(f, &results, settings))?;
                             },
+                            InputAction::Delete(index) => {
+                                if let Some(entry) = results.get(index) {
+                                    deleter.delete(&db, entry.clone()).await?;
+                                }
+
+                                app.inspecting = false;
+                                app.history_count = db.history_count(false).await?;
+                                results = if app.timeline.is_some() {
+                                    app.timeline_results(&db, None).await?
+                                } else {
+                                    app.query_results(&mut db).await?
+                                };
+                                app.results_state.select(index.min(results.len().saturating_sub(1)));
+                            },
+                            InputAction::Timeline(index) => {
+                                if let Some(entry) = results.get(index) {
+                                    app.timeline = Some(Timeline {
+                                        session: entry.session.clone(),
+                                        previous: 0,
+                                        next: 0,
+                                        sessions: Vec::new(),
+                                    });
+                                    let keep = Some(entry.id.clone());
+                                    results = app.timeline_results(&db, keep).await?;
+                                }
+                            },
+                            action @ (InputAction::TimelinePrevious | InputAction::TimelineNext) => {
+                                let keep = results.get(app.results_state.selected()).map(|h| h.id.clone());
+
+                                if let Some(timeline) = &mut app.timeline {
+                                    if matches!(action, InputAction::TimelinePrevious) {
+                                        timeline.previous += 1;
+                                    } else {
+                                        timeline.next += 1;
+                                    }
+                                }
+
+                                results = app.timeline_results(&db, keep).await?;
+                            },
                             r => {
                                 accept = app.accept;
                                 break 'render r;
@@ -724,11 +1030,16 @@ pub async fn history(
         if initial_input != app.search.input.as_str()
             || initial_filter_mode != app.search.filter_mode
             || initial_search_mode != app.search_mode
+            || (initial_timeline && app.timeline.is_none())
         {
             results = app.query_results(&mut db).await?;
         }
     };
 
+    if app.keymap.style == KeymapStyle::Vim {
+        execute!(std::io::stdout(), SetCursorStyle::DefaultUserShape)?;
+    }
+
     if settings.inline_height > 0 {
         terminal.clear()?;
     }
@@ -755,8 +1066,173 @@ pub async fn history(
             // * out of bounds -> usually implies no selected entry so we return the input
             Ok(app.search.input.into_inner())
         }
-        InputAction::Continue | InputAction::Redraw => {
+        InputAction::Continue
+        | InputAction::Redraw
+        | InputAction::Delete(_)
+        | InputAction::Timeline(_)
+        | InputAction::TimelinePrevious
+        | InputAction::TimelineNext => {
             unreachable!("should have been handled!")
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use atuin_client::{
+        database::Context,
+        settings::{SearchMode, Settings},
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{InputAction, State, Timeline};
+    use crate::command::client::search::{
+        cursor::Cursor,
+        engines::{self, SearchState},
+        history_list::ListState,
+        keybindings::{Keymap, Mode},
+        theme::Theme,
+    };
+
+    fn state(settings: &Settings) -> State {
+        State {
+            history_count: 2,
+            results_state: ListState::default(),
+            update_needed: None,
+            switched_search_mode: false,
+            search_mode: SearchMode::Fuzzy,
+            search: SearchState {
+                input: Cursor::from(String::new()),
+                filter_mode: settings.filter_mode,
+                context: Context {
+                    session: String::new(),
+                    cwd: String::new(),
+                    hostname: String::new(),
+                    host_id: String::new(),
+                    git_root: None,
+                },
+            },
+            engine: engines::engine(SearchMode::Fuzzy, settings),
+            results_len: 2,
+            accept: false,
+            inspecting: false,
+            stats: None,
+            timeline: None,
+            confirm_delete: None,
+            keymap: Keymap::new(settings).unwrap(),
+            theme: Theme::default(),
+            mode: Mode::Insert,
+        }
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn delete_needs_confirmation() {
+        let settings = Settings::default();
+        let mut app = state(&settings);
+        app.results_state.select(1);
+
+        let delete = key(KeyCode::Delete, KeyModifiers::SHIFT);
+        assert!(matches!(
+            app.handle_key_input(&settings, &delete),
+            InputAction::Continue
+        ));
+        assert_eq!(app.confirm_delete, Some(1));
+
+        let yes = key(KeyCode::Char('y'), KeyModifiers::NONE);
+        assert!(matches!(
+            app.handle_key_input(&settings, &yes),
+            InputAction::Delete(1)
+        ));
+        assert_eq!(app.confirm_delete, None);
+
+        // pressing the delete key again confirms too
+        app.handle_key_input(&settings, &delete);
+        assert!(matches!(
+            app.handle_key_input(&settings, &delete),
+            InputAction::Delete(1)
+        ));
+    }
+
+    #[test]
+    fn delete_cancelled_by_other_keys() {
+        let settings = Settings::default();
+        let mut app = state(&settings);
+
+        app.handle_key_input(&settings, &key(KeyCode::Delete, KeyModifiers::SHIFT));
+        assert_eq!(app.confirm_delete, Some(0));
+
+        let no = key(KeyCode::Char('n'), KeyModifiers::NONE);
+        assert!(matches!(
+            app.handle_key_input(&settings, &no),
+            InputAction::Continue
+        ));
+        assert_eq!(app.confirm_delete, None);
+        // the key that cancelled isn't typed into the query
+        assert_eq!(app.search.input.as_str(), "");
+    }
+
+    #[test]
+    fn inspector_keys() {
+        let settings = Settings::default();
+        let mut app = state(&settings);
+        let press = |c| key(KeyCode::Char(c), KeyModifiers::NONE);
+
+        app.handle_key_input(&settings, &key(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        assert!(app.inspecting);
+
+        // keys aren't typed into the query while inspecting
+        app.handle_key_input(&settings, &press('d'));
+        assert_eq!(app.confirm_delete, Some(0));
+        assert!(matches!(
+            app.handle_key_input(&settings, &press('d')),
+            InputAction::Delete(0)
+        ));
+        assert_eq!(app.search.input.as_str(), "");
+
+        assert!(matches!(
+            app.handle_key_input(&settings, &press('s')),
+            InputAction::Timeline(0)
+        ));
+        assert!(!app.inspecting);
+    }
+
+    #[test]
+    fn timeline_keys() {
+        let mut settings = Settings::default();
+        settings
+            .keys
+            .timeline
+            .insert("h".to_string(), "previous-session".to_string());
+        let mut app = state(&settings);
+        let press = |c| key(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // sessions can only be moved through in the timeline
+        assert!(matches!(
+            app.handle_key_input(&settings, &press('[')),
+            InputAction::Continue
+        ));
+
+        app.timeline = Some(Timeline {
+            session: String::new(),
+            previous: 0,
+            next: 0,
+            sessions: Vec::new(),
+        });
+
+        assert!(matches!(
+            app.handle_key_input(&settings, &press('h')),
+            InputAction::TimelinePrevious
+        ));
+        assert!(matches!(
+            app.handle_key_input(&settings, &press(']')),
+            InputAction::TimelineNext
+        ));
+
+        app.handle_key_input(&settings, &key(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.timeline.is_none());
+    }
+}
@@ -0,0 +1,624 @@
+use std::{collections::HashMap, str::FromStr};
+
+use atuin_client::settings::{KeymapStyle, Settings};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use eyre::{bail, eyre, Result, WrapErr};
+
+/// A key, along with the modifiers held down with it.
+///
+/// Shift is folded into the character for character keys, so `shift-a` and `A` are the same
+/// chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => Self {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => Self {
+                code: KeyCode::BackTab,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            KeyCode::BackTab => Self {
+                code,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            _ => Self { code, modifiers },
+        }
+    }
+}
+
+impl From<&KeyEvent> for KeyChord {
+    fn from(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = eyre::Report;
+
+    /// Parse a chord such as `ctrl-r`, `alt-shift-f` or `pagedown`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+
+        loop {
+            let lower = rest.to_ascii_lowercase();
+
+            let (modifier, len) = if lower.starts_with("ctrl-") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt-") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift-") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+
+            // a trailing "-" is the key itself, as in "ctrl--"
+            if rest.len() == len - 1 {
+                break;
+            }
+
+            modifiers |= modifier;
+            rest = &rest[len..];
+        }
+
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('f') => {
+                let n = f[1..]
+                    .parse()
+                    .map_err(|_| eyre!("unknown key {rest:?} in {s:?}"))?;
+                KeyCode::F(n)
+            }
+            _ => {
+                let mut chars = rest.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!("unknown key {rest:?} in {s:?}"),
+                }
+            }
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+/// Everything a key can be bound to in interactive search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Do nothing. Useful to unbind a default
+    None,
+
+    /// Exit, returning either the original command line or the query, as set by `exit_mode`
+    Exit,
+    ReturnOriginal,
+    ReturnQuery,
+
+    /// Run the selected command if `enter_accept` is set, otherwise put it on the command line
+    Accept,
+    /// Put the selected command on the command line, to edit before running
+    Edit,
+    /// Run the selected command
+    Execute,
+    /// Put the command this many entries above the selected one on the command line
+    AcceptNth(usize),
+    Copy,
+    Inspect,
+    Timeline,
+    DeleteEntry,
+    /// Leave the inspector or timeline, back to the search
+    Back,
+    /// Move the timeline to the session before or after the ones shown
+    PreviousSession,
+    NextSession,
+
+    CycleFilterMode,
+    CycleSearchMode,
+
+    Up,
+    Down,
+    /// Like `up` and `down`, but exit when moving past the newest entry
+    UpOrExit,
+    DownOrExit,
+    PageUp,
+    PageDown,
+
+    CursorLeft,
+    CursorRight,
+    CursorWordLeft,
+    CursorWordRight,
+    CursorStart,
+    CursorEnd,
+
+    DeleteCharBefore,
+    DeleteCharAfter,
+    DeleteWordBefore,
+    DeleteWordAfter,
+    /// Delete the character after the cursor, or exit if the query is empty
+    DeleteCharOrExit,
+    /// Delete back to the previous whitespace
+    UnixWordRubout,
+    ClearLine,
+
+    Redraw,
+
+    VimNormal,
+    VimInsert,
+    VimAppend,
+    VimInsertStart,
+    VimAppendEnd,
+}
+
+impl FromStr for Action {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let action = match s {
+            "none" => Action::None,
+            "exit" => Action::Exit,
+            "return-original" => Action::ReturnOriginal,
+            "return-query" => Action::ReturnQuery,
+            "accept" => Action::Accept,
+            "edit" => Action::Edit,
+            "execute" => Action::Execute,
+            "copy" => Action::Copy,
+            "inspect" => Action::Inspect,
+            "timeline" => Action::Timeline,
+            "delete-entry" => Action::DeleteEntry,
+            "back" => Action::Back,
+            "previous-session" => Action::PreviousSession,
+            "next-session" => Action::NextSession,
+            "cycle-filter-mode" => Action::CycleFilterMode,
+            "cycle-search-mode" => Action::CycleSearchMode,
+            "up" => Action::Up,
+            "down" => Action::Down,
+            "up-or-exit" => Action::UpOrExit,
+            "down-or-exit" => Action::DownOrExit,
+            "page-up" => Action::PageUp,
+            "page-down" => Action::PageDown,
+            "cursor-left" => Action::CursorLeft,
+            "cursor-right" => Action::CursorRight,
+            "cursor-word-left" => Action::CursorWordLeft,
+            "cursor-word-right" => Action::CursorWordRight,
+            "cursor-start" => Action::CursorStart,
+            "cursor-end" => Action::CursorEnd,
+            "delete-char-before" => Action::DeleteCharBefore,
+            "delete-char-after" => Action::DeleteCharAfter,
+            "delete-word-before" => Action::DeleteWordBefore,
+            "delete-word-after" => Action::DeleteWordAfter,
+            "delete-char-or-exit" => Action::DeleteCharOrExit,
+            "unix-word-rubout" => Action::UnixWordRubout,
+            "clear-line" => Action::ClearLine,
+            "redraw" => Action::Redraw,
+            "vim-normal" => Action::VimNormal,
+            "vim-insert" => Action::VimInsert,
+            "vim-append" => Action::VimAppend,
+            "vim-insert-start" => Action::VimInsertStart,
+            "vim-append-end" => Action::VimAppendEnd,
+            _ => match s.strip_prefix("accept-").map(str::parse) {
+                Some(Ok(n @ 1..=9)) => Action::AcceptNth(n),
+                _ => bail!("unknown action {s:?}"),
+            },
+        };
+
+        Ok(action)
+    }
+}
+
+/// Which set of bindings is in use. Only vim-style bindings ever leave insert mode, and the
+/// inspector and timeline have their own, whichever mode the search is in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Insert,
+    Normal,
+    Inspector,
+    Timeline,
+}
+
+/// The key bindings in effect, built from the defaults for the configured style with the user's
+/// bindings on top
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub style: KeymapStyle,
+    insert: HashMap<KeyChord, Action>,
+    normal: HashMap<KeyChord, Action>,
+    inspector: HashMap<KeyChord, Action>,
+    timeline: HashMap<KeyChord, Action>,
+}
+
+fn bind(map: &mut HashMap<KeyChord, Action>, chords: &[&str], action: Action) {
+    for chord in chords {
+        let chord = chord.parse().expect("invalid default key binding");
+        map.insert(chord, action);
+    }
+}
+
+fn apply(map: &mut HashMap<KeyChord, Action>, bindings: &HashMap<String, String>) -> Result<()> {
+    for (chord, action) in bindings {
+        let chord: KeyChord = chord.parse().wrap_err("invalid key binding")?;
+        let action: Action = action
+            .parse()
+            .wrap_err_with(|| format!("invalid action for key binding {chord:?}"))?;
+
+        map.insert(chord, action);
+    }
+
+    Ok(())
+}
+
+impl Keymap {
+    pub fn new(settings: &Settings) -> Result<Self> {
+        let style = settings.keys.style;
+        let mut insert = Self::default_insert(settings);
+        let mut normal = if style == KeymapStyle::Vim {
+            bind(&mut insert, &["esc"], Action::VimNormal);
+            Self::default_normal()
+        } else {
+            HashMap::new()
+        };
+
+        let mut inspector = Self::default_inspector();
+        let mut timeline = Self::default_timeline();
+
+        apply(&mut insert, &settings.keys.insert)?;
+        apply(&mut normal, &settings.keys.normal)?;
+        apply(&mut inspector, &settings.keys.inspector)?;
+        apply(&mut timeline, &settings.keys.timeline)?;
+
+        Ok(Self {
+            style,
+            insert,
+            normal,
+            inspector,
+            timeline,
+        })
+    }
+
+    /// The action bound to `chord` in `mode`
+    pub fn action(&self, chord: KeyChord, mode: Mode) -> Option<Action> {
+        match mode {
+            Mode::Insert => self.insert.get(&chord).copied(),
+            Mode::Normal => self.normal.get(&chord).copied(),
+            Mode::Inspector => self.inspector.get(&chord).copied(),
+            Mode::Timeline => self.timeline.get(&chord).copied(),
+        }
+    }
+
+    fn default_insert(settings: &Settings) -> HashMap<KeyChord, Action> {
+        let mut map = HashMap::new();
+
+        bind(&mut map, &["ctrl-c", "ctrl-g"], Action::ReturnOriginal);
+        bind(&mut map, &["esc"], Action::Exit);
+        bind(&mut map, &["tab"], Action::Edit);
+        bind(&mut map, &["enter"], Action::Accept);
+        bind(&mut map, &["ctrl-y"], Action::Copy);
+        bind(&mut map, &["ctrl-o"], Action::Inspect);
+        bind(&mut map, &["ctrl-t"], Action::Timeline);
+        bind(&mut map, &["shift-delete"], Action::DeleteEntry);
+
+        let modifier = if settings.ctrl_n_shortcuts {
+            "ctrl"
+        } else {
+            "alt"
+        };
+        for n in 1..=9 {
+            bind(
+                &mut map,
+                &[&format!("{modifier}-{n}")],
+                Action::AcceptNth(n),
+            );
+        }
+
+        bind(&mut map, &["ctrl-left", "alt-b"], Action::CursorWordLeft);
+        bind(&mut map, &["left", "ctrl-b"], Action::CursorLeft);
+        bind(&mut map, &["ctrl-right", "alt-f"], Action::CursorWordRight);
+        bind(&mut map, &["right", "ctrl-f"], Action::CursorRight);
+        bind(&mut map, &["ctrl-a", "home"], Action::CursorStart);
+        bind(&mut map, &["ctrl-e", "end"], Action::CursorEnd);
+
+        bind(&mut map, &["ctrl-backspace"], Action::DeleteWordBefore);
+        bind(&mut map, &["backspace"], Action::DeleteCharBefore);
+        bind(&mut map, &["ctrl-delete"], Action::DeleteWordAfter);
+        bind(&mut map, &["delete"], Action::DeleteCharAfter);
+        bind(&mut map, &["ctrl-d"], Action::DeleteCharOrExit);
+        bind(&mut map, &["ctrl-w"], Action::UnixWordRubout);
+        bind(&mut map, &["ctrl-u"], Action::ClearLine);
+
+        bind(&mut map, &["ctrl-r"], Action::CycleFilterMode);
+        bind(&mut map, &["ctrl-s"], Action::CycleSearchMode);
+
+        bind(&mut map, &["up"], Action::UpOrExit);
+        bind(&mut map, &["down"], Action::DownOrExit);
+        bind(&mut map, &["ctrl-p", "ctrl-k"], Action::Up);
+        bind(&mut map, &["ctrl-n", "ctrl-j"], Action::Down);
+        bind(&mut map, &["pageup"], Action::PageUp);
+        bind(&mut map, &["pagedown"], Action::PageDown);
+
+        bind(&mut map, &["ctrl-l"], Action::Redraw);
+
+        map
+    }
+
+    fn default_normal() -> HashMap<KeyChord, Action> {
+        let mut map = HashMap::new();
+
+        bind(&mut map, &["ctrl-c", "ctrl-g"], Action::ReturnOriginal);
+        bind(&mut map, &["esc", "q"], Action::Exit);
+        bind(&mut map, &["tab"], Action::Edit);
+        bind(&mut map, &["enter"], Action::Accept);
+        bind(&mut map, &["y", "ctrl-y"], Action::Copy);
+        bind(&mut map, &["o", "ctrl-o"], Action::Inspect);
+        bind(&mut map, &["s", "ctrl-t"], Action::Timeline);
+        bind(&mut map, &["d", "shift-delete"], Action::DeleteEntry);
+
+        bind(&mut map, &["i"], Action::VimInsert);
+        bind(&mut map, &["a"], Action::VimAppend);
+        bind(&mut map, &["I"], Action::VimInsertStart);
+        bind(&mut map, &["A"], Action::VimAppendEnd);
+
+        bind(&mut map, &["h", "left"], Action::CursorLeft);
+        bind(&mut map, &["l", "right"], Action::CursorRight);
+        bind(&mut map, &["b"], Action::CursorWordLeft);
+        bind(&mut map, &["w", "e"], Action::CursorWordRight);
+        bind(&mut map, &["0", "^", "home"], Action::CursorStart);
+        bind(&mut map, &["$", "end"], Action::CursorEnd);
+        bind(&mut map, &["x", "delete"], Action::DeleteCharAfter);
+        bind(&mut map, &["X", "backspace"], Action::DeleteCharBefore);
+        bind(&mut map, &["D"], Action::ClearLine);
+
+        bind(&mut map, &["ctrl-r"], Action::CycleFilterMode);
+        bind(&mut map, &["ctrl-s"], Action::CycleSearchMode);
+
+        bind(&mut map, &["k"], Action::Up);
+        bind(&mut map, &["j"], Action::Down);
+        bind(&mut map, &["up"], Action::UpOrExit);
+        bind(&mut map, &["down"], Action::DownOrExit);
+        bind(&mut map, &["ctrl-u", "ctrl-b", "pageup"], Action::PageUp);
+        bind(
+            &mut map,
+            &["ctrl-d", "ctrl-f", "pagedown"],
+            Action::PageDown,
+        );
+
+        bind(&mut map, &["ctrl-l"], Action::Redraw);
+
+        map
+    }
+
+    fn default_inspector() -> HashMap<KeyChord, Action> {
+        let mut map = HashMap::new();
+
+        bind(&mut map, &["ctrl-c", "ctrl-g"], Action::ReturnOriginal);
+        bind(&mut map, &["esc", "q", "ctrl-o"], Action::Back);
+        bind(&mut map, &["tab", "e"], Action::Edit);
+        bind(&mut map, &["enter"], Action::Accept);
+        bind(&mut map, &["y"], Action::Copy);
+        bind(&mut map, &["d"], Action::DeleteEntry);
+        bind(&mut map, &["s"], Action::Timeline);
+
+        bind(&mut map, &["up", "k"], Action::Up);
+        bind(&mut map, &["down", "j"], Action::Down);
+
+        map
+    }
+
+    fn default_timeline() -> HashMap<KeyChord, Action> {
+        let mut map = HashMap::new();
+
+        bind(&mut map, &["ctrl-c", "ctrl-g"], Action::ReturnOriginal);
+        bind(&mut map, &["esc", "q", "ctrl-t"], Action::Back);
+        bind(&mut map, &["ctrl-o"], Action::Inspect);
+        bind(&mut map, &["ctrl-y"], Action::Copy);
+        bind(&mut map, &["tab"], Action::Edit);
+        bind(&mut map, &["enter"], Action::Accept);
+        bind(&mut map, &["["], Action::PreviousSession);
+        bind(&mut map, &["]"], Action::NextSession);
+
+        bind(&mut map, &["up", "k"], Action::Up);
+        bind(&mut map, &["down", "j"], Action::Down);
+        bind(&mut map, &["pageup"], Action::PageUp);
+        bind(&mut map, &["pagedown"], Action::PageDown);
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_client::settings::{KeymapStyle, Settings};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Action, KeyChord, Keymap, Mode};
+
+    fn chord(s: &str) -> KeyChord {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_chords() {
+        assert_eq!(
+            chord("ctrl-r"),
+            KeyChord::from(&KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            chord("Alt-Shift-F"),
+            KeyChord::from(&KeyEvent::new(
+                KeyCode::Char('F'),
+                KeyModifiers::ALT | KeyModifiers::SHIFT
+            ))
+        );
+        assert_eq!(chord("shift-a"), chord("A"));
+        assert_eq!(chord("shift-tab"), chord("backtab"));
+        assert_eq!(
+            chord("ctrl--"),
+            KeyChord::from(&KeyEvent::new(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            chord("f5"),
+            KeyChord::from(&KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            chord("space"),
+            KeyChord::from(&KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+
+        assert!("ctrl-".parse::<KeyChord>().is_err());
+        assert!("hyper-x".parse::<KeyChord>().is_err());
+        assert!("fx".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn parse_actions() {
+        assert_eq!(
+            "cycle-filter-mode".parse::<Action>().unwrap(),
+            Action::CycleFilterMode
+        );
+        assert_eq!("accept-3".parse::<Action>().unwrap(), Action::AcceptNth(3));
+        assert!("accept-0".parse::<Action>().is_err());
+        assert!("accept-10".parse::<Action>().is_err());
+        assert!("explode".parse::<Action>().is_err());
+    }
+
+    #[test]
+    fn defaults() {
+        let keymap = Keymap::new(&Settings::default()).unwrap();
+
+        assert_eq!(
+            keymap.action(chord("ctrl-r"), Mode::Insert),
+            Some(Action::CycleFilterMode)
+        );
+        assert_eq!(
+            keymap.action(chord("alt-4"), Mode::Insert),
+            Some(Action::AcceptNth(4))
+        );
+        assert_eq!(keymap.action(chord("ctrl-4"), Mode::Insert), None);
+        assert_eq!(
+            keymap.action(chord("esc"), Mode::Insert),
+            Some(Action::Exit)
+        );
+        assert_eq!(keymap.action(chord("j"), Mode::Normal), None);
+
+        // the inspector and timeline have their own
+        assert_eq!(
+            keymap.action(chord("d"), Mode::Inspector),
+            Some(Action::DeleteEntry)
+        );
+        assert_eq!(
+            keymap.action(chord("ctrl-o"), Mode::Inspector),
+            Some(Action::Back)
+        );
+        assert_eq!(
+            keymap.action(chord("ctrl-o"), Mode::Timeline),
+            Some(Action::Inspect)
+        );
+        assert_eq!(
+            keymap.action(chord("]"), Mode::Timeline),
+            Some(Action::NextSession)
+        );
+
+        let settings = Settings {
+            ctrl_n_shortcuts: true,
+            ..Settings::default()
+        };
+        let keymap = Keymap::new(&settings).unwrap();
+        assert_eq!(
+            keymap.action(chord("ctrl-4"), Mode::Insert),
+            Some(Action::AcceptNth(4))
+        );
+    }
+
+    #[test]
+    fn user_bindings() {
+        let mut settings = Settings::default();
+        settings.keys.style = KeymapStyle::Vim;
+        settings
+            .keys
+            .insert
+            .insert("ctrl-k".to_string(), "cursor-end".to_string());
+        settings
+            .keys
+            .insert
+            .insert("ctrl-u".to_string(), "none".to_string());
+        settings
+            .keys
+            .normal
+            .insert("shift-g".to_string(), "page-down".to_string());
+        settings
+            .keys
+            .inspector
+            .insert("x".to_string(), "delete-entry".to_string());
+        settings
+            .keys
+            .timeline
+            .insert("h".to_string(), "previous-session".to_string());
+
+        let keymap = Keymap::new(&settings).unwrap();
+
+        assert_eq!(
+            keymap.action(chord("esc"), Mode::Insert),
+            Some(Action::VimNormal)
+        );
+        assert_eq!(
+            keymap.action(chord("esc"), Mode::Normal),
+            Some(Action::Exit)
+        );
+        assert_eq!(keymap.action(chord("j"), Mode::Normal), Some(Action::Down));
+        assert_eq!(
+            keymap.action(chord("ctrl-k"), Mode::Insert),
+            Some(Action::CursorEnd)
+        );
+        assert_eq!(
+            keymap.action(chord("ctrl-u"), Mode::Insert),
+            Some(Action::None)
+        );
+        assert_eq!(
+            keymap.action(chord("G"), Mode::Normal),
+            Some(Action::PageDown)
+        );
+        assert_eq!(
+            keymap.action(chord("x"), Mode::Inspector),
+            Some(Action::DeleteEntry)
+        );
+        assert_eq!(
+            keymap.action(chord("d"), Mode::Inspector),
+            Some(Action::DeleteEntry)
+        );
+        assert_eq!(
+            keymap.action(chord("h"), Mode::Timeline),
+            Some(Action::PreviousSession)
+        );
+
+        settings
+            .keys
+            .insert
+            .insert("ctrl-x".to_string(), "explode".to_string());
+        assert!(Keymap::new(&settings).is_err());
+    }
+}
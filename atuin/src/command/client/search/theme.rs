@@ -0,0 +1,335 @@
+use std::{collections::HashMap, env, path::Path};
+
+use atuin_client::settings::Settings;
+use config::{Config, File, FileFormat};
+use eyre::{bail, eyre, Result, WrapErr};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+/// The styles used to draw interactive search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Durations of commands that succeeded, and zero exit codes in the inspector
+    pub success: Style,
+    /// Durations of commands that failed, and non-zero exit codes in the inspector
+    pub failure: Style,
+    /// How long ago each command ran
+    pub time: Style,
+    /// The ` > ` and numbers in front of each result
+    pub index: Style,
+    pub command: Style,
+    /// The command in the selected result
+    pub selected: Style,
+    pub preview: Style,
+    /// The `[ GLOBAL ]` box showing the current mode
+    pub mode: Style,
+    pub input: Style,
+    pub title: Style,
+    /// The title when an update is available
+    pub update: Style,
+    /// The help line and the history count
+    pub help: Style,
+    /// Keys named in the help line
+    pub help_key: Style,
+    pub border: Style,
+    /// Field names and panel titles in the inspector
+    pub label: Style,
+    /// The commands either side of the inspected one
+    pub faded: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            success: Style::default().fg(Color::Green),
+            failure: Style::default().fg(Color::Red),
+            time: Style::default().fg(Color::Blue),
+            index: Style::default(),
+            command: Style::default(),
+            selected: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            preview: Style::default(),
+            mode: Style::default(),
+            input: Style::default(),
+            title: Style::default().add_modifier(Modifier::BOLD),
+            update: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            help: Style::default().fg(Color::DarkGray),
+            help_key: Style::default().add_modifier(Modifier::BOLD),
+            border: Style::default(),
+            label: Style::default().add_modifier(Modifier::BOLD),
+            faded: Style::default().fg(Color::DarkGray),
+        }
+    }
+}
+
+/// A theme file, from the `themes` directory in the config dir
+#[derive(Debug, Deserialize)]
+struct ThemeFile {
+    /// The bundled theme to start from
+    base: Option<String>,
+
+    #[serde(default)]
+    styles: HashMap<String, StyleFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StyleFile {
+    fg: Option<String>,
+    bg: Option<String>,
+
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+impl StyleFile {
+    fn style(&self) -> Result<Style> {
+        let color = |name: &str| {
+            name.parse::<Color>()
+                .map_err(|_| eyre!("unknown color {name:?}"))
+        };
+
+        let mut style = Style::default();
+
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+
+        for name in &self.modifiers {
+            let modifier = Modifier::from_name(&name.to_ascii_uppercase())
+                .ok_or_else(|| eyre!("unknown modifier {name:?}"))?;
+            style = style.add_modifier(modifier);
+        }
+
+        Ok(style)
+    }
+}
+
+impl Theme {
+    pub const BUNDLED: [&'static str; 3] = ["default", "light", "no-color"];
+
+    /// Load the theme named in the settings, or the no-color theme if `NO_COLOR` is set
+    pub fn load(settings: &Settings) -> Result<Self> {
+        if env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty()) {
+            return Ok(Self::no_color());
+        }
+
+        let dir = Settings::config_dir().join("themes");
+        Self::named(&settings.theme, &dir)
+    }
+
+    /// A theme file in `dir` called `{name}.toml`, or else the bundled theme called `name`
+    fn named(name: &str, dir: &Path) -> Result<Self> {
+        let path = dir.join(format!("{name}.toml"));
+
+        if path.exists() {
+            return Self::from_file(&path)
+                .wrap_err_with(|| format!("could not load theme {}", path.display()));
+        }
+
+        Self::bundled(name).ok_or_else(|| {
+            eyre!(
+                "unknown theme {name:?}: it is not one of {:?}, and {path:?} does not exist",
+                Self::BUNDLED
+            )
+        })
+    }
+
+    fn bundled(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "light" => Some(Self::light()),
+            "no-color" => Some(Self::no_color()),
+            _ => None,
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let file: ThemeFile = Config::builder()
+            .add_source(File::new(&path.to_string_lossy(), FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+
+        let mut theme = match &file.base {
+            Some(base) => Self::bundled(base).ok_or_else(|| {
+                eyre!(
+                    "unknown base theme {base:?}, expected one of {:?}",
+                    Self::BUNDLED
+                )
+            })?,
+            None => Self::default(),
+        };
+
+        for (name, style) in &file.styles {
+            let style = style
+                .style()
+                .wrap_err_with(|| format!("invalid style for {name:?}"))?;
+            theme.set(name, style)?;
+        }
+
+        Ok(theme)
+    }
+
+    fn set(&mut self, name: &str, style: Style) -> Result<()> {
+        let field = match name {
+            "success" => &mut self.success,
+            "failure" => &mut self.failure,
+            "time" => &mut self.time,
+            "index" => &mut self.index,
+            "command" => &mut self.command,
+            "selected" => &mut self.selected,
+            "preview" => &mut self.preview,
+            "mode" => &mut self.mode,
+            "input" => &mut self.input,
+            "title" => &mut self.title,
+            "update" => &mut self.update,
+            "help" => &mut self.help,
+            "help_key" => &mut self.help_key,
+            "border" => &mut self.border,
+            "label" => &mut self.label,
+            "faded" => &mut self.faded,
+            _ => bail!("unknown style {name:?}"),
+        };
+
+        *field = style;
+
+        Ok(())
+    }
+
+    /// For terminals with a light background
+    fn light() -> Self {
+        Self {
+            success: Style::default().fg(Color::Green),
+            failure: Style::default().fg(Color::Red),
+            time: Style::default().fg(Color::Blue),
+            selected: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Gray)
+                .add_modifier(Modifier::BOLD),
+            update: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            help: Style::default().fg(Color::DarkGray),
+            faded: Style::default().fg(Color::DarkGray),
+            ..Self::no_color()
+        }
+    }
+
+    /// No colors at all, only bold and dim text
+    fn no_color() -> Self {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let dim = Style::default().add_modifier(Modifier::DIM);
+
+        Self {
+            success: Style::default(),
+            failure: bold,
+            time: Style::default(),
+            index: Style::default(),
+            command: Style::default(),
+            selected: bold.add_modifier(Modifier::UNDERLINED),
+            preview: Style::default(),
+            mode: Style::default(),
+            input: Style::default(),
+            title: bold,
+            update: bold,
+            help: dim,
+            help_key: bold,
+            border: Style::default(),
+            label: bold,
+            faded: dim,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use atuin_common::utils::uuid_v4;
+    use ratatui::style::{Color, Modifier, Style};
+
+    use super::Theme;
+
+    fn themes_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("atuin-themes-{}", uuid_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn bundled() {
+        let dir = themes_dir();
+
+        for name in Theme::BUNDLED {
+            Theme::named(name, &dir).unwrap();
+        }
+
+        assert_eq!(Theme::named("default", &dir).unwrap(), Theme::default());
+        assert!(Theme::named("missing", &dir).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn from_file() {
+        let dir = themes_dir();
+
+        fs::write(
+            dir.join("mine.toml"),
+            r##"
+base = "no-color"
+
+[styles]
+selected = { fg = "#ff8800", modifiers = ["bold", "italic"] }
+time = { fg = "light blue", bg = "238" }
+"##,
+        )
+        .unwrap();
+
+        let theme = Theme::named("mine", &dir).unwrap();
+
+        assert_eq!(
+            theme.selected,
+            Style::default()
+                .fg(Color::Rgb(0xff, 0x88, 0x00))
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+        );
+        assert_eq!(
+            theme.time,
+            Style::default()
+                .fg(Color::LightBlue)
+                .bg(Color::Indexed(238))
+        );
+        assert_eq!(theme.help, Theme::no_color().help);
+
+        // a file takes precedence over the bundled theme of the same name
+        fs::write(
+            dir.join("light.toml"),
+            "[styles]\nhelp = { fg = \"magenta\" }\n",
+        )
+        .unwrap();
+
+        let theme = Theme::named("light", &dir).unwrap();
+        assert_eq!(theme.help, Style::default().fg(Color::Magenta));
+        assert_eq!(theme.selected, Theme::default().selected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_file() {
+        let dir = themes_dir();
+
+        for (name, contents) in [
+            ("color", "[styles]\ntime = { fg = \"blurple\" }\n"),
+            ("modifier", "[styles]\ntime = { modifiers = [\"shiny\"] }\n"),
+            ("style", "[styles]\nwhatever = { fg = \"red\" }\n"),
+            ("base", "base = \"missing\"\n"),
+        ] {
+            fs::write(dir.join(format!("{name}.toml")), contents).unwrap();
+            assert!(Theme::named(name, &dir).is_err(), "{name} loaded");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
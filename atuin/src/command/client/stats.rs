@@ -1,16 +1,38 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use clap::Parser;
+use atuin_common::utils;
+use clap::{Parser, ValueEnum};
 use crossterm::style::{Color, ResetColor, SetAttribute, SetForegroundColor};
 use eyre::{bail, Result};
 use interim::parse_date_string;
 
 use atuin_client::{
-    database::{current_context, Database},
+    database::{current_context, Database, OptFilters},
     history::History,
     settings::{FilterMode, Settings},
 };
-use time::{Duration, OffsetDateTime, Time};
+use serde::Serialize;
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+
+use super::{print_json, search::format_duration, Output};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    /// The most used commands
+    Commands,
+    /// The commands that fail most often, and their failure rate
+    Failures,
+    /// The commands that take up the most time, in total and on average
+    Durations,
+    /// How many commands are run in each hour of the day
+    Hours,
+    /// How many commands are run on each day of the week
+    Days,
+    /// The directories the most commands are run in
+    Directories,
+    /// The current and longest runs of consecutive days with history
+    Streaks,
+}
 
 #[derive(Parser, Debug)]
 #[command(infer_subcommands = true)]
@@ -21,32 +43,292 @@ pub struct Cmd {
     /// How many top commands to list
     #[arg(long, short, default_value = "10")]
     count: usize,
+
+    /// How to print the statistics. JSON output includes every statistic
+    #[arg(long, short, value_enum, default_value = "human")]
+    output: Output,
+
+    /// Which statistics to show, separated by commas
+    #[arg(
+        long,
+        short,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "commands"
+    )]
+    show: Vec<Metric>,
+
+    /// Only include commands run in this directory, or "." for the current directory
+    #[arg(long)]
+    cwd: Option<String>,
+
+    /// Exclude commands run in this directory
+    #[arg(long = "exclude-cwd")]
+    exclude_cwd: Option<String>,
+
+    /// Only include commands with this exit code
+    #[arg(long, short)]
+    exit: Option<i64>,
+
+    /// Exclude commands with this exit code
+    #[arg(long = "exclude-exit")]
+    exclude_exit: Option<i64>,
+
+    /// Only include commands run before this date
+    #[arg(long, short)]
+    before: Option<String>,
+
+    /// Only include commands run after this date
+    #[arg(long)]
+    after: Option<String>,
+
+    /// Only include commands run on this host, given as "hostname:username"
+    #[arg(long)]
+    host: Option<String>,
+
+    /// Only include commands run in this session
+    #[arg(long)]
+    session: Option<String>,
+
+    /// Only include commands matching a filter mode, relative to the current host, session and
+    /// directory
+    #[arg(long = "filter-mode")]
+    filter_mode: Option<FilterMode>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CommandCount {
+    pub command: String,
+    pub count: usize,
+}
+
+/// How often a command fails. Runs with an unknown exit code are not counted
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CommandFailures {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    /// The fraction of runs that failed, from 0 to 1
+    pub rate: f64,
+}
+
+/// How long a command takes. Runs with an unknown duration are not counted
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CommandDurations {
+    pub command: String,
+    pub runs: usize,
+    /// In nanoseconds
+    pub total: u64,
+    /// In nanoseconds
+    pub average: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DirectoryStats {
+    pub directory: String,
+    pub count: usize,
+    /// The command run most often in this directory
+    pub top_command: String,
+}
+
+/// Runs of consecutive days, in local time, on which at least one command was run
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Streaks {
+    /// The run ending today, or yesterday if nothing has been run yet today
+    pub current: usize,
+    pub longest: usize,
+    pub longest_start: Option<Date>,
+    pub longest_end: Option<Date>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total: usize,
+    pub unique: usize,
+    /// The most used commands, and how many times each was run
+    pub top: Vec<CommandCount>,
+    pub failures: Vec<CommandFailures>,
+    pub durations: Vec<CommandDurations>,
+    /// How many commands were run in each hour of the day, from midnight
+    pub hours: [usize; 24],
+    /// How many commands were run on each day of the week, from Monday
+    pub days: [usize; 7],
+    pub directories: Vec<DirectoryStats>,
+    pub streaks: Streaks,
+}
+
+/// The `count` largest entries, largest first. Ties are broken by name, so output is stable
+fn top_n<T>(mut entries: Vec<T>, count: usize, key: impl Fn(&T) -> (u64, &str)) -> Vec<T> {
+    entries.sort_by(|a, b| {
+        let (a_n, a_name) = key(a);
+        let (b_n, b_name) = key(b);
+        b_n.cmp(&a_n).then_with(|| a_name.cmp(b_name))
+    });
+    entries.truncate(count);
+    entries
+}
+
+fn compute_streaks(days: &BTreeSet<Date>, today: Date) -> Streaks {
+    let mut streaks = Streaks::default();
+    let mut run: Option<(Date, Date, usize)> = None;
+
+    for &day in days {
+        let (start, end, length) = match run {
+            Some((start, end, length)) if end.next_day() == Some(day) => (start, day, length + 1),
+            _ => (day, day, 1),
+        };
+
+        if length > streaks.longest {
+            streaks.longest = length;
+            streaks.longest_start = Some(start);
+            streaks.longest_end = Some(end);
+        }
+
+        run = Some((start, end, length));
+    }
+
+    if let Some((_, end, length)) = run {
+        if end == today || end.next_day() == Some(today) {
+            streaks.current = length;
+        }
+    }
+
+    streaks
 }
 
-fn compute_stats(settings: &Settings, history: &[History], count: usize) -> Result<()> {
+#[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn compute_stats(
+    settings: &Settings,
+    history: &[History],
+    count: usize,
+    now: OffsetDateTime,
+) -> Result<Stats> {
+    let offset = now.offset();
+
     let mut commands = HashSet::<&str>::with_capacity(history.len());
     let mut prefixes = HashMap::<&str, usize>::with_capacity(history.len());
+    let mut failures = HashMap::<&str, (usize, usize)>::new();
+    let mut durations = HashMap::<&str, (usize, u64)>::new();
+    let mut directories = HashMap::<&str, HashMap<&str, usize>>::new();
+    let mut hours = [0; 24];
+    let mut days = [0; 7];
+    let mut dates = BTreeSet::new();
+
     for i in history {
         // just in case it somehow has a leading tab or space or something (legacy atuin didn't ignore space prefixes)
         let command = i.command.trim();
+        let prefix = interesting_command(settings, command);
         commands.insert(command);
-        *prefixes
-            .entry(interesting_command(settings, command))
+        *prefixes.entry(prefix).or_default() += 1;
+
+        if i.exit != -1 {
+            let (runs, failed) = failures.entry(prefix).or_default();
+            *runs += 1;
+            *failed += usize::from(i.exit != 0);
+        }
+
+        if i.duration >= 0 {
+            let (runs, total) = durations.entry(prefix).or_default();
+            *runs += 1;
+            *total += i.duration as u64;
+        }
+
+        *directories
+            .entry(i.cwd.as_str())
+            .or_default()
+            .entry(prefix)
             .or_default() += 1;
+
+        let local = i.timestamp.to_offset(offset);
+        hours[usize::from(local.hour())] += 1;
+        days[usize::from(local.weekday().number_days_from_monday())] += 1;
+        dates.insert(local.date());
     }
 
     let unique = commands.len();
-    let mut top = prefixes.into_iter().collect::<Vec<_>>();
-    top.sort_unstable_by_key(|x| std::cmp::Reverse(x.1));
-    top.truncate(count);
+    let top = top_n(prefixes.into_iter().collect(), count, |x| (x.1 as u64, x.0));
     if top.is_empty() {
         bail!("No commands found");
     }
 
-    let max = top.iter().map(|x| x.1).max().unwrap();
+    let failures = failures
+        .into_iter()
+        .filter(|(_, (_, failed))| *failed > 0)
+        .map(|(command, (runs, failures))| CommandFailures {
+            command: command.to_string(),
+            runs,
+            failures,
+            rate: failures as f64 / runs as f64,
+        })
+        .collect();
+
+    let durations = durations
+        .into_iter()
+        .map(|(command, (runs, total))| CommandDurations {
+            command: command.to_string(),
+            runs,
+            total,
+            average: total / runs as u64,
+        })
+        .collect();
+
+    let directories = directories
+        .into_iter()
+        .map(|(directory, commands)| {
+            let (top_command, _) = top_n(commands.iter().collect(), 1, |(c, n)| (**n as u64, c))[0];
+
+            DirectoryStats {
+                directory: directory.to_string(),
+                count: commands.values().sum(),
+                top_command: (*top_command).to_string(),
+            }
+        })
+        .collect();
+
+    Ok(Stats {
+        total: history.len(),
+        unique,
+        top: top
+            .into_iter()
+            .map(|(command, count)| CommandCount {
+                command: command.to_string(),
+                count,
+            })
+            .collect(),
+        failures: top_n(failures, count, |x: &CommandFailures| {
+            (x.failures as u64, &x.command)
+        }),
+        durations: top_n(durations, count, |x: &CommandDurations| {
+            (x.total, &x.command)
+        }),
+        hours,
+        days,
+        directories: top_n(directories, count, |x: &DirectoryStats| {
+            (x.count as u64, &x.directory)
+        }),
+        streaks: compute_streaks(&dates, now.date()),
+    })
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+/// A bar `width` wide when `count` is `max`
+fn bar(count: usize, max: usize, width: usize) -> String {
+    let filled = (width * count).checked_div(max).unwrap_or(0);
+    format!("{}{}", "▮".repeat(filled), " ".repeat(width - filled))
+}
+
+fn print_commands(stats: &Stats) {
+    let max = stats.top.iter().map(|x| x.count).max().unwrap();
     let num_pad = max.ilog10() as usize + 1;
 
-    for (command, count) in top {
+    for CommandCount { command, count } in &stats.top {
         let gray = SetForegroundColor(Color::Grey);
         let bold = SetAttribute(crossterm::style::Attribute::Bold);
 
@@ -68,10 +350,93 @@ fn compute_stats(settings: &Settings, history: &[History], count: usize) -> Resu
 
         println!("{ResetColor}] {gray}{count:num_pad$}{ResetColor} {bold}{command}{ResetColor}");
     }
-    println!("Total commands:   {}", history.len());
-    println!("Unique commands:  {unique}");
+    println!("Total commands:   {}", stats.total);
+    println!("Unique commands:  {}", stats.unique);
+}
 
-    Ok(())
+fn print_failures(stats: &Stats) {
+    if stats.failures.is_empty() {
+        println!("No failed commands");
+        return;
+    }
+
+    let bold = SetAttribute(crossterm::style::Attribute::Bold);
+    let pad = stats
+        .failures
+        .iter()
+        .map(|x| x.runs)
+        .max()
+        .unwrap()
+        .ilog10() as usize
+        + 1;
+
+    for failures in &stats.failures {
+        println!(
+            "{:>pad$}/{:<pad$} failed {:>5.1}%  {bold}{}{ResetColor}",
+            failures.failures,
+            failures.runs,
+            failures.rate * 100.0,
+            failures.command,
+        );
+    }
+}
+
+fn print_durations(stats: &Stats) {
+    let bold = SetAttribute(crossterm::style::Attribute::Bold);
+
+    for durations in &stats.durations {
+        println!(
+            "{:>8} total {:>8} average {:>10}  {bold}{}{ResetColor}",
+            format_duration(std::time::Duration::from_nanos(durations.total)),
+            format_duration(std::time::Duration::from_nanos(durations.average)),
+            plural(durations.runs, "run"),
+            durations.command,
+        );
+    }
+}
+
+fn print_histogram<'a>(rows: impl Iterator<Item = (String, &'a usize)> + Clone) {
+    let gray = SetForegroundColor(Color::Grey);
+    let max = rows.clone().map(|(_, n)| *n).max().unwrap_or_default();
+    let num_pad = max.max(1).ilog10() as usize + 1;
+
+    for (label, count) in rows {
+        println!(
+            "{label} [{}] {gray}{count:num_pad$}{ResetColor}",
+            bar(*count, max, 30)
+        );
+    }
+}
+
+fn print_directories(stats: &Stats) {
+    let bold = SetAttribute(crossterm::style::Attribute::Bold);
+    let gray = SetForegroundColor(Color::Grey);
+    let pad = stats
+        .directories
+        .first()
+        .map_or(1, |x| x.count.ilog10() as usize + 1);
+
+    for dir in &stats.directories {
+        println!(
+            "{:>pad$} {bold}{}{ResetColor} {gray}(mostly {}){ResetColor}",
+            dir.count, dir.directory, dir.top_command
+        );
+    }
+}
+
+fn print_streaks(stats: &Stats) {
+    let streaks = &stats.streaks;
+    println!("Current streak: {}", plural(streaks.current, "day"));
+
+    match (streaks.longest_start, streaks.longest_end) {
+        (Some(start), Some(end)) => {
+            println!(
+                "Longest streak: {}, from {start} to {end}",
+                plural(streaks.longest, "day")
+            );
+        }
+        _ => println!("Longest streak: 0 days"),
+    }
 }
 
 impl Cmd {
@@ -84,34 +449,97 @@ impl Cmd {
         };
 
         let now = OffsetDateTime::now_utc();
-        let now = settings.local_tz.map_or(now, |local| now.to_offset(local));
+        let now = now.to_offset(settings.local_tz.unwrap_or(UtcOffset::UTC));
         let last_night = now.replace_time(Time::MIDNIGHT);
 
-        let history = if words.as_str() == "all" {
-            db.list(FilterMode::Global, &context, None, false, false)
-                .await?
+        let range = if words.as_str() == "all" {
+            None
         } else if words.trim() == "today" {
             let start = last_night;
             let end = start + Duration::days(1);
-            db.range(start, end).await?
+            Some((start, end))
         } else if words.trim() == "month" {
             let end = last_night;
             let start = end - Duration::days(31);
-            db.range(start, end).await?
+            Some((start, end))
         } else if words.trim() == "week" {
             let end = last_night;
             let start = end - Duration::days(7);
-            db.range(start, end).await?
+            Some((start, end))
         } else if words.trim() == "year" {
             let end = last_night;
             let start = end - Duration::days(365);
-            db.range(start, end).await?
+            Some((start, end))
         } else {
             let start = parse_date_string(&words, now, settings.dialect.into())?;
             let end = start + Duration::days(1);
-            db.range(start, end).await?
+            Some((start, end))
+        };
+
+        let cwd = if self.cwd.as_deref() == Some(".") {
+            Some(utils::get_current_dir())
+        } else {
+            self.cwd.clone()
         };
-        compute_stats(settings, &history, self.count)?;
+
+        let filters = OptFilters {
+            exit: self.exit,
+            exclude_exit: self.exclude_exit,
+            cwd,
+            exclude_cwd: self.exclude_cwd.clone(),
+            hostname: self.host.clone(),
+            session: self.session.clone(),
+            before: self.before.clone(),
+            after: self.after.clone(),
+            ..OptFilters::default()
+        };
+
+        let filter_mode = self.filter_mode.unwrap_or(FilterMode::Global);
+        let mut history = db.list_filtered(filter_mode, &context, filters).await?;
+
+        if let Some((start, end)) = range {
+            history.retain(|h| h.timestamp >= start && h.timestamp < end);
+        }
+
+        let stats = compute_stats(settings, &history, self.count, now)?;
+
+        if self.output == Output::Json {
+            return print_json(&stats);
+        }
+
+        for (i, metric) in self.show.iter().enumerate() {
+            if self.show.len() > 1 {
+                if i > 0 {
+                    println!();
+                }
+
+                let heading = metric.to_possible_value().expect("no skipped metrics");
+                let bold = SetAttribute(crossterm::style::Attribute::Bold);
+                println!("{bold}{}{ResetColor}", heading.get_name());
+            }
+
+            match metric {
+                Metric::Commands => print_commands(&stats),
+                Metric::Failures => print_failures(&stats),
+                Metric::Durations => print_durations(&stats),
+                Metric::Hours => print_histogram(
+                    stats
+                        .hours
+                        .iter()
+                        .enumerate()
+                        .map(|(hour, n)| (format!("{hour:02}:00"), n)),
+                ),
+                Metric::Days => print_histogram(
+                    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+                        .iter()
+                        .zip(&stats.days)
+                        .map(|(day, n)| ((*day).to_string(), n)),
+                ),
+                Metric::Directories => print_directories(&stats),
+                Metric::Streaks => print_streaks(&stats),
+            }
+        }
+
         Ok(())
     }
 }
@@ -175,9 +603,132 @@ fn interesting_command<'a>(settings: &Settings, mut command: &'a str) -> &'a str
 
 #[cfg(test)]
 mod tests {
-    use atuin_client::settings::Settings;
+    use std::collections::BTreeSet;
+
+    use atuin_client::{history::History, settings::Settings};
+    use time::macros::{date, datetime};
 
-    use super::interesting_command;
+    use super::{
+        compute_stats, compute_streaks, interesting_command, CommandCount, CommandFailures,
+    };
+
+    #[test]
+    fn stats() {
+        let settings = Settings::default();
+        let history: Vec<History> = [
+            (
+                "cargo build",
+                "/a",
+                0,
+                2_000,
+                datetime!(2024-01-01 09:00 UTC),
+            ),
+            (
+                "cargo build --release",
+                "/a",
+                101,
+                6_000,
+                datetime!(2024-01-01 09:30 UTC),
+            ),
+            (
+                "cargo test",
+                "/a",
+                0,
+                1_000,
+                datetime!(2024-01-02 17:00 UTC),
+            ),
+            ("ls", "/b", 0, 10, datetime!(2024-01-02 17:05 UTC)),
+            ("ls", "/b", -1, -1, datetime!(2024-01-04 23:00 UTC)),
+        ]
+        .into_iter()
+        .map(|(command, cwd, exit, duration, timestamp)| {
+            History::import()
+                .timestamp(timestamp)
+                .command(command)
+                .cwd(cwd)
+                .exit(exit)
+                .duration(duration)
+                .build()
+                .into()
+        })
+        .collect();
+
+        let stats =
+            compute_stats(&settings, &history, 10, datetime!(2024-01-05 12:00 UTC)).unwrap();
+
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.unique, 4);
+        assert_eq!(
+            stats.top,
+            [("cargo build", 2), ("ls", 2), ("cargo test", 1)]
+                .into_iter()
+                .map(|(command, count)| CommandCount {
+                    command: command.to_string(),
+                    count,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            stats.failures,
+            vec![CommandFailures {
+                command: "cargo build".to_string(),
+                runs: 2,
+                failures: 1,
+                rate: 0.5,
+            }]
+        );
+
+        assert_eq!(stats.durations[0].command, "cargo build");
+        assert_eq!(stats.durations[0].total, 8_000);
+        assert_eq!(stats.durations[0].average, 4_000);
+        // the unknown duration isn't counted
+        assert_eq!(stats.durations[2].command, "ls");
+        assert_eq!(stats.durations[2].runs, 1);
+
+        assert_eq!(stats.hours[9], 2);
+        assert_eq!(stats.hours[17], 2);
+        assert_eq!(stats.hours[23], 1);
+        // Monday, Tuesday and Thursday
+        assert_eq!(stats.days, [2, 2, 0, 1, 0, 0, 0]);
+
+        assert_eq!(stats.directories[0].directory, "/a");
+        assert_eq!(stats.directories[0].count, 3);
+        assert_eq!(stats.directories[0].top_command, "cargo build");
+        assert_eq!(stats.directories[1].directory, "/b");
+
+        assert_eq!(stats.streaks.longest, 2);
+        assert_eq!(stats.streaks.longest_start, Some(date!(2024 - 01 - 01)));
+        assert_eq!(stats.streaks.current, 1);
+
+        assert!(compute_stats(&settings, &[], 10, datetime!(2024-01-05 12:00 UTC)).is_err());
+    }
+
+    #[test]
+    fn streaks() {
+        let days = BTreeSet::from([
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 03),
+            date!(2024 - 01 - 04),
+            date!(2024 - 01 - 05),
+            date!(2024 - 01 - 09),
+            date!(2024 - 01 - 10),
+        ]);
+
+        let streaks = compute_streaks(&days, date!(2024 - 01 - 10));
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.longest, 3);
+        assert_eq!(streaks.longest_start, Some(date!(2024 - 01 - 03)));
+        assert_eq!(streaks.longest_end, Some(date!(2024 - 01 - 05)));
+
+        // nothing yet today still counts
+        assert_eq!(compute_streaks(&days, date!(2024 - 01 - 11)).current, 2);
+        assert_eq!(compute_streaks(&days, date!(2024 - 01 - 12)).current, 0);
+        assert_eq!(
+            compute_streaks(&BTreeSet::new(), date!(2024 - 01 - 12)).longest,
+            0
+        );
+    }
 
     #[test]
     fn interesting_commands() {
@@ -3,13 +3,16 @@ use eyre::{Result, WrapErr};
 
 use atuin_client::{
     database::Database,
-    record::{store::Store, sync},
+    encryption,
+    history::store::HistoryStore,
+    record::{sqlite_store::SqliteStore, sync},
     settings::Settings,
 };
 
+mod key;
 mod status;
 
-use crate::command::client::account;
+use crate::command::client::{account, Output};
 
 #[derive(Subcommand, Debug)]
 #[command(infer_subcommands = true)]
@@ -30,14 +33,13 @@ pub enum Cmd {
     /// Register with the configured server
     Register(account::register::Cmd),
 
-    /// Print the encryption key for transfer to another machine
-    Key {
-        /// Switch to base64 output of the key
-        #[arg(long)]
-        base64: bool,
-    },
+    /// Print the encryption key for transfer to another machine, or rotate it
+    Key(key::Cmd),
 
-    Status,
+    Status {
+        #[arg(long, short, value_enum, default_value = "human")]
+        output: Output,
+    },
 }
 
 impl Cmd {
@@ -45,28 +47,15 @@ impl Cmd {
         self,
         settings: Settings,
         db: &impl Database,
-        store: &(impl Store + Send + Sync),
+        store: SqliteStore,
     ) -> Result<()> {
         match self {
             Self::Sync { force } => run(&settings, force, db, store).await,
             Self::Login(l) => l.run(&settings).await,
             Self::Logout => account::logout::run(&settings),
             Self::Register(r) => r.run(&settings).await,
-            Self::Status => status::run(&settings, db).await,
-            Self::Key { base64 } => {
-                use atuin_client::encryption::{encode_key, load_key};
-                let key = load_key(&settings).wrap_err("could not load encryption key")?;
-
-                if base64 {
-                    let encode = encode_key(&key).wrap_err("could not encode encryption key")?;
-                    println!("{encode}");
-                } else {
-                    let mnemonic = bip39::Mnemonic::from_entropy(&key, bip39::Language::English)
-                        .map_err(|_| eyre::eyre!("invalid key"))?;
-                    println!("{mnemonic}");
-                }
-                Ok(())
-            }
+            Self::Status { output } => status::run(&settings, db, output).await,
+            Self::Key(key) => key.run(&settings, db, store).await,
         }
     }
 }
@@ -75,14 +64,22 @@ async fn run(
     settings: &Settings,
     force: bool,
     db: &impl Database,
-    store: &(impl Store + Send + Sync),
+    store: SqliteStore,
 ) -> Result<()> {
     if settings.sync.records {
-        let (diff, _) = sync::diff(settings, store).await?;
-        let operations = sync::operations(diff, store).await?;
-        let (uploaded, downloaded) = sync::sync_remote(operations, store, settings).await?;
+        let (diff, _) = sync::diff(settings, &store).await?;
+        let operations = sync::operations(diff, &store).await?;
+        let (uploaded, downloaded) = sync::sync_remote(operations, &store, settings).await?;
 
         println!("{uploaded}/{downloaded} up/down to record store");
+
+        let encryption_key: [u8; 32] = encryption::load_key(settings)
+            .context("could not load encryption key")?
+            .into();
+        let host_id = Settings::host_id().expect("failed to get host_id");
+        let history_store = HistoryStore::new(store, host_id, encryption_key);
+
+        history_store.incremental_build(db).await?;
     }
 
     atuin_client::sync::sync(settings, force, db).await?;
@@ -0,0 +1,367 @@
+use std::{env, path::PathBuf};
+
+use clap::{Args, Subcommand};
+use eyre::{bail, ensure, Result, WrapErr};
+use rpassword::prompt_password;
+
+use atuin_client::{
+    api_client,
+    database::Database,
+    encryption::{
+        decode_key, encode_key, generate_key, load_key,
+        protect::{self, is_protected, PASSPHRASE_VAR, UNLOCK_VAR},
+        save_key, Key,
+    },
+    history::store::HistoryStore,
+    record::{rotate, sqlite_store::SqliteStore},
+    settings::Settings,
+};
+use atuin_common::record::HostId;
+
+use crate::command::client::account::login::parse_key;
+
+/// How many records to send to the server at once
+const REPLACE_PAGE_SIZE: usize = 100;
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cmd {
+    /// Switch to base64 output of the key
+    #[arg(long)]
+    base64: bool,
+
+    #[command(subcommand)]
+    cmd: Option<Subcmd>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Subcmd {
+    /// Replace the encryption key with a new one, and re-encrypt every record, both here and on
+    /// the server
+    Rotate {
+        /// Rotate to this key, rather than a new one. Use this on your other machines, with the
+        /// key printed by `atuin key` where you first rotated
+        #[arg(long, short)]
+        key: Option<String>,
+    },
+
+    /// Encrypt the key file with a passphrase
+    Protect,
+
+    /// Decrypt the key file, storing the key in plain text again
+    Unprotect,
+
+    /// Unlock a protected key for this shell. Use as `eval "$(atuin key unlock)"`
+    Unlock {
+        /// How long the key stays unlocked for
+        #[arg(long, short, default_value = "8h")]
+        timeout: String,
+    },
+
+    /// Lock the key unlocked for this shell
+    Lock,
+}
+
+impl Cmd {
+    pub async fn run(
+        self,
+        settings: &Settings,
+        db: &impl Database,
+        store: SqliteStore,
+    ) -> Result<()> {
+        match self.cmd {
+            None => print(settings, self.base64),
+            Some(Subcmd::Rotate { key }) => {
+                let host_id = Settings::host_id().expect("failed to get host_id");
+                rotate(settings, db, store, host_id, key).await
+            }
+            Some(Subcmd::Protect) => protect(settings),
+            Some(Subcmd::Unprotect) => unprotect(settings),
+            Some(Subcmd::Unlock { timeout }) => unlock(settings, &timeout),
+            Some(Subcmd::Lock) => lock(),
+        }
+    }
+}
+
+fn print(settings: &Settings, base64: bool) -> Result<()> {
+    let key = load_key(settings).wrap_err("could not load encryption key")?;
+
+    if base64 {
+        let encode = encode_key(&key).wrap_err("could not encode encryption key")?;
+        println!("{encode}");
+    } else {
+        let mnemonic = bip39::Mnemonic::from_entropy(&key, bip39::Language::English)
+            .map_err(|_| eyre::eyre!("invalid key"))?;
+        println!("{mnemonic}");
+    }
+
+    Ok(())
+}
+
+async fn rotate(
+    settings: &Settings,
+    db: &impl Database,
+    store: SqliteStore,
+    host_id: HostId,
+    key: Option<String>,
+) -> Result<()> {
+    if settings.logged_in() && !settings.sync.records {
+        bail!("key rotation needs record sync. Set `records = true` in the [sync] section of your config, and sync, first");
+    }
+
+    // If the key file is protected, the new one is protected with the same passphrase
+    let contents = fs_err::read_to_string(&settings.key_path).unwrap_or_default();
+    let passphrase = if is_protected(&contents) {
+        Some(passphrase()?)
+    } else {
+        None
+    };
+
+    let old_key = match &passphrase {
+        Some(passphrase) => protect::unprotect(&contents, passphrase)?,
+        None => load_key(settings).wrap_err("could not load encryption key")?,
+    };
+
+    // The new key is kept next to the old one until everything has been re-encrypted, so that if
+    // we are interrupted, running rotate again picks up where it left off
+    let pending = PathBuf::from(format!("{}.new", settings.key_path));
+
+    let first = key.is_none();
+
+    let save = |key: &Key| -> Result<()> {
+        let contents = match &passphrase {
+            Some(passphrase) => protect::protect(key, passphrase),
+            None => encode_key(key)?,
+        };
+
+        fs_err::write(&pending, contents).wrap_err("could not save the new encryption key")
+    };
+
+    let new_key = if let Some(key) = key {
+        let key = decode_key(parse_key(key)?)?;
+        save(&key)?;
+        key
+    } else if pending.exists() {
+        println!("Resuming an interrupted key rotation");
+
+        let key = fs_err::read_to_string(&pending)?;
+        match &passphrase {
+            Some(passphrase) if is_protected(&key) => protect::unprotect(&key, passphrase),
+            _ => decode_key(key),
+        }
+        .wrap_err("could not load the new encryption key")?
+    } else {
+        let key = generate_key();
+        save(&key)?;
+        key
+    };
+
+    let records = rotate::rotate(&store, &old_key.into(), &new_key.into()).await?;
+    println!("Re-encrypted {} local records", records.len());
+
+    if settings.logged_in() {
+        let client = api_client::Client::new(
+            &settings.sync_address,
+            &settings.session_token,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+        )?;
+
+        let mut replaced = 0;
+        for page in records.chunks(REPLACE_PAGE_SIZE) {
+            replaced += client.replace_records(page).await.wrap_err(
+                "could not replace records on the server. Run `atuin key rotate` again to retry",
+            )?;
+        }
+
+        println!("Re-encrypted {replaced} records on the server");
+    }
+
+    // replaces, and so retires, the old key
+    fs_err::rename(&pending, &settings.key_path)?;
+
+    // history synced from machines that rotated first couldn't be read until now, and was skipped
+    HistoryStore::new(store, host_id, new_key.into())
+        .build(db)
+        .await
+        .wrap_err("could not rebuild history. Run `atuin history rebuild` to retry")?;
+
+    println!("Your encryption key has been rotated");
+
+    if passphrase.is_some() && env::var_os(UNLOCK_VAR).is_some() {
+        println!("Run `eval \"$(atuin key unlock)\"` to unlock the new key");
+    }
+
+    if first {
+        println!(
+            "On your other machines, run `atuin key rotate --key <KEY>` with the new key from `atuin key`"
+        );
+    }
+
+    Ok(())
+}
+
+/// The passphrase from the environment, or else ask for it
+fn passphrase() -> Result<String> {
+    match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => Ok(prompt_password("Passphrase: ")?),
+    }
+}
+
+fn protect(settings: &Settings) -> Result<()> {
+    let path = PathBuf::from(&settings.key_path);
+
+    if path.exists() && is_protected(&fs_err::read_to_string(&path)?) {
+        bail!("the key file is already protected");
+    }
+
+    let key = load_key(settings).wrap_err("could not load encryption key")?;
+
+    let passphrase = if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        passphrase
+    } else {
+        let passphrase = prompt_password("New passphrase: ")?;
+        let repeated = prompt_password("Repeat passphrase: ")?;
+        ensure!(passphrase == repeated, "the passphrases do not match");
+        passphrase
+    };
+    ensure!(!passphrase.is_empty(), "the passphrase can't be empty");
+
+    fs_err::write(&path, protect::protect(&key, &passphrase))?;
+
+    println!("Your key file is now protected. Run `eval \"$(atuin key unlock)\"` to use it");
+
+    Ok(())
+}
+
+fn unprotect(settings: &Settings) -> Result<()> {
+    let contents = fs_err::read_to_string(&settings.key_path)?;
+
+    if !is_protected(&contents) {
+        bail!("the key file is not protected");
+    }
+
+    let key = protect::unprotect(&contents, &passphrase()?)?;
+    save_key(&settings.key_path, &key)?;
+
+    println!("Your key file is no longer protected");
+
+    Ok(())
+}
+
+fn unlock(settings: &Settings, timeout: &str) -> Result<()> {
+    let contents = fs_err::read_to_string(&settings.key_path)?;
+
+    if !is_protected(&contents) {
+        bail!("the key file is not protected, so there is nothing to unlock");
+    }
+
+    let timeout = parse_duration::parse(timeout).wrap_err("invalid timeout")?;
+    let unlocked = protect::unlock(&contents, &passphrase()?, timeout)?;
+
+    // printed for the shell to eval
+    println!("export {UNLOCK_VAR}={unlocked}");
+
+    Ok(())
+}
+
+fn lock() -> Result<()> {
+    if protect::lock()? {
+        eprintln!("Locked your encryption key");
+    } else {
+        eprintln!("Your encryption key was not unlocked");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use atuin_client::{
+        database::{Database, Sqlite},
+        encryption::{encode_key, generate_key, save_key},
+        history::{store::HistoryStore, History},
+        record::sqlite_store::SqliteStore,
+        settings::{FilterMode, Settings},
+    };
+    use atuin_common::{record::HostId, utils::uuid_v7};
+    use time::OffsetDateTime;
+
+    use super::rotate;
+
+    fn history(command: &str) -> History {
+        History::import()
+            .timestamp(OffsetDateTime::now_utc())
+            .command(command)
+            .cwd("/home/ellie")
+            .build()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn rotate_rebuilds_history() {
+        let dir = env::temp_dir().join(format!("atuin-test-{}", uuid_v7().as_simple()));
+        fs_err::create_dir_all(&dir).unwrap();
+
+        let settings = Settings {
+            key_path: dir.join("key").to_string_lossy().to_string(),
+            session_path: dir.join("session").to_string_lossy().to_string(),
+            ..Settings::default()
+        };
+
+        let old_key = generate_key();
+        let new_key = generate_key();
+        save_key(&settings.key_path, &old_key).unwrap();
+
+        // another machine recorded history, then rotated, and recorded more with the new key
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let other = HostId(uuid_v7());
+        HistoryStore::new(store.clone(), other, old_key.into())
+            .push(history("ls"))
+            .await
+            .unwrap();
+        HistoryStore::new(store.clone(), other, new_key.into())
+            .push(history("cd src"))
+            .await
+            .unwrap();
+
+        // which we synced before we had the new key, so only the first could be read
+        let db = Sqlite::new("sqlite::memory:").await.unwrap();
+        HistoryStore::new(store.clone(), HostId(uuid_v7()), old_key.into())
+            .incremental_build(&db)
+            .await
+            .unwrap();
+        assert_eq!(db.history_count(false).await.unwrap(), 1);
+
+        rotate(
+            &settings,
+            &db,
+            store,
+            HostId(uuid_v7()),
+            Some(encode_key(&new_key).unwrap()),
+        )
+        .await
+        .unwrap();
+
+        let context = atuin_client::database::Context {
+            session: String::new(),
+            cwd: String::new(),
+            hostname: String::new(),
+            host_id: String::new(),
+            git_root: None,
+        };
+        let mut commands: Vec<_> = db
+            .list(FilterMode::Global, &context, None, false, false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|h| h.command)
+            .collect();
+        commands.sort();
+
+        assert_eq!(commands, ["cd src", "ls"]);
+    }
+}
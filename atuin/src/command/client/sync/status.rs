@@ -1,9 +1,43 @@
-use crate::{SHA, VERSION};
+use crate::{
+    command::client::{print_json, Output},
+    SHA, VERSION,
+};
 use atuin_client::{api_client, database::Database, settings::Settings};
+use atuin_common::api::{QuotaUsage, UsageResponse};
 use colored::Colorize;
 use eyre::Result;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
 
-pub async fn run(settings: &Settings, db: &impl Database) -> Result<()> {
+#[derive(Debug, Serialize)]
+struct SyncStatus {
+    version: &'static str,
+    build: &'static str,
+    local: LocalStatus,
+    /// Only set if auto sync is enabled
+    remote: Option<RemoteStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct LocalStatus {
+    /// Only set if auto sync is enabled
+    sync_frequency: Option<String>,
+    /// Only set if auto sync is enabled
+    last_sync: Option<String>,
+    history_count: i64,
+    deleted_history_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoteStatus {
+    address: String,
+    username: String,
+    history_count: i64,
+    /// Not set if the server is too old to report it
+    usage: Option<UsageResponse>,
+}
+
+pub async fn run(settings: &Settings, db: &impl Database, output: Output) -> Result<()> {
     let client = api_client::Client::new(
         &settings.sync_address,
         &settings.session_token,
@@ -12,28 +46,127 @@ pub async fn run(settings: &Settings, db: &impl Database) -> Result<()> {
     )?;
 
     let status = client.status().await?;
+    let usage = if settings.auto_sync {
+        client.usage().await.ok()
+    } else {
+        None
+    };
     let last_sync = Settings::last_sync()?;
     let local_count = db.history_count(false).await?;
     let deleted_count = db.history_count(true).await? - local_count;
 
-    println!("Atuin v{VERSION} - Build rev {SHA}\n");
+    let status = SyncStatus {
+        version: VERSION,
+        build: SHA,
+        local: LocalStatus {
+            sync_frequency: settings.auto_sync.then(|| settings.sync_frequency.clone()),
+            last_sync: if settings.auto_sync {
+                Some(last_sync.format(&Rfc3339)?)
+            } else {
+                None
+            },
+            history_count: local_count,
+            deleted_history_count: deleted_count,
+        },
+        remote: settings.auto_sync.then(|| RemoteStatus {
+            address: settings.sync_address.clone(),
+            username: status.username,
+            history_count: status.count,
+            usage,
+        }),
+    };
+
+    match output {
+        Output::Human => print_human(&status),
+        Output::Json => print_json(&status)?,
+    }
+
+    Ok(())
+}
+
+fn print_human(status: &SyncStatus) {
+    println!("Atuin v{} - Build rev {}\n", status.version, status.build);
 
     println!("{}", "[Local]".green());
 
-    if settings.auto_sync {
-        println!("Sync frequency: {}", settings.sync_frequency);
+    if let (Some(frequency), Some(last_sync)) =
+        (&status.local.sync_frequency, &status.local.last_sync)
+    {
+        println!("Sync frequency: {frequency}");
         println!("Last sync: {last_sync}");
     }
 
-    println!("History count: {local_count}");
-    println!("Deleted history count: {deleted_count}\n");
+    println!("History count: {}", status.local.history_count);
+    println!(
+        "Deleted history count: {}\n",
+        status.local.deleted_history_count
+    );
 
-    if settings.auto_sync {
+    if let Some(remote) = &status.remote {
         println!("{}", "[Remote]".green());
-        println!("Address: {}", settings.sync_address);
-        println!("Username: {}", status.username);
-        println!("History count: {}", status.count);
+        println!("Address: {}", remote.address);
+        println!("Username: {}", remote.username);
+        println!("History count: {}", remote.history_count);
+
+        if let Some(usage) = &remote.usage {
+            print_usage(usage);
+        }
     }
+}
 
-    Ok(())
+fn print_usage(usage: &UsageResponse) {
+    println!(
+        "Records: {}",
+        format_quota(usage.records.count, |n| n.to_string())
+    );
+    println!(
+        "Record storage: {}",
+        format_quota(usage.records.bytes, format_bytes)
+    );
+
+    for (tag, tag_usage) in &usage.tags {
+        println!(
+            "  {tag}: {} records, {}",
+            format_quota(tag_usage.count, |n| n.to_string()),
+            format_quota(tag_usage.bytes, format_bytes)
+        );
+    }
+
+    // history synced without records is already counted above, so only show it if it is limited
+    if usage.history.count.limit.is_some() || usage.history.bytes.limit.is_some() {
+        println!(
+            "History quota: {} items, {}",
+            format_quota(usage.history.count, |n| n.to_string()),
+            format_quota(usage.history.bytes, format_bytes)
+        );
+    }
+}
+
+fn format_quota(quota: QuotaUsage, format: impl Fn(u64) -> String) -> String {
+    let used = format(quota.used);
+
+    if let Some(limit) = quota.limit {
+        format!("{used} of {}", format(limit))
+    } else {
+        used
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
 }
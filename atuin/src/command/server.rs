@@ -1,13 +1,17 @@
 use std::net::SocketAddr;
 
+use atuin_server_database::{Database, DbSettings, DbType};
 use atuin_server_postgres::Postgres;
+use atuin_server_sqlite::Sqlite;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 use clap::Parser;
-use eyre::{Context, Result};
+use eyre::{bail, Context, Result};
 
 use atuin_server::{example_config, launch, launch_metrics_server, Settings};
 
+mod user;
+
 #[derive(Parser, Debug)]
 #[clap(infer_subcommands = true)]
 pub enum Cmd {
@@ -24,6 +28,10 @@ pub enum Cmd {
 
     /// Print server example configuration
     DefaultConfig,
+
+    /// Manage the users of this server, directly in its database
+    #[command(subcommand)]
+    User(user::Cmd),
 }
 
 impl Cmd {
@@ -38,7 +46,8 @@ impl Cmd {
 
         match self {
             Self::Start { host, port } => {
-                let settings = Settings::new().wrap_err("could not load server settings")?;
+                let settings: Settings<DbSettings> =
+                    Settings::new().wrap_err("could not load server settings")?;
                 let host = host.as_ref().unwrap_or(&settings.host).clone();
                 let port = port.unwrap_or(settings.port);
                 let addr = SocketAddr::new(host.parse()?, port);
@@ -50,12 +59,30 @@ impl Cmd {
                     ));
                 }
 
-                launch::<Postgres>(settings, addr).await
+                match settings.db_settings.db_type() {
+                    DbType::Postgres => launch::<Postgres>(settings, addr).await,
+                    DbType::Sqlite => launch::<Sqlite>(settings, addr).await,
+                    DbType::Unknown => {
+                        bail!("db_uri must start with postgres://, postgresql:// or sqlite:")
+                    }
+                }
             }
             Self::DefaultConfig => {
                 println!("{}", example_config());
                 Ok(())
             }
+            Self::User(cmd) => {
+                let settings: Settings<DbSettings> =
+                    Settings::new().wrap_err("could not load server settings")?;
+
+                match settings.db_settings.db_type() {
+                    DbType::Postgres => cmd.run(&Postgres::new(&settings.db_settings).await?).await,
+                    DbType::Sqlite => cmd.run(&Sqlite::new(&settings.db_settings).await?).await,
+                    DbType::Unknown => {
+                        bail!("db_uri must start with postgres://, postgresql:// or sqlite:")
+                    }
+                }
+            }
         }
     }
 }
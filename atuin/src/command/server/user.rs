@@ -0,0 +1,271 @@
+use atuin_server::{hash_secret, valid_username};
+use atuin_server_database::{
+    models::{NewUser, User},
+    Database, DbError,
+};
+use clap::Subcommand;
+use eyre::{bail, ensure, Result};
+use rpassword::prompt_password;
+
+#[derive(Subcommand, Debug)]
+#[command(infer_subcommands = true)]
+pub enum Cmd {
+    /// List every user, with how much history and how many records they have synced
+    List,
+
+    /// Create a user
+    Create {
+        username: String,
+
+        #[arg(long, short)]
+        email: String,
+
+        /// The user's password. If not given, it is prompted for
+        #[arg(long, short)]
+        password: Option<String>,
+    },
+
+    /// Delete a user, and everything they have synced
+    Delete { username: String },
+
+    /// Stop a user from logging in or syncing, without deleting anything
+    Disable { username: String },
+
+    /// Let a disabled user log in and sync again
+    Enable { username: String },
+
+    /// Set a new password for a user
+    ResetPassword {
+        username: String,
+
+        /// The new password. If not given, it is prompted for
+        #[arg(long, short)]
+        password: Option<String>,
+    },
+}
+
+impl Cmd {
+    pub async fn run(self, db: &impl Database) -> Result<()> {
+        match self {
+            Self::List => list(db).await,
+            Self::Create {
+                username,
+                email,
+                password,
+            } => create(db, username, email, password).await,
+            Self::Delete { username } => {
+                let user = get_user(db, &username).await?;
+                db.delete_user(&user).await?;
+
+                println!("Deleted user {username}");
+                Ok(())
+            }
+            Self::Disable { username } => {
+                let user = get_user(db, &username).await?;
+                db.disable_user(&user).await?;
+
+                println!("Disabled user {username}");
+                Ok(())
+            }
+            Self::Enable { username } => {
+                let user = get_user(db, &username).await?;
+                db.enable_user(&user).await?;
+
+                println!("Enabled user {username}");
+                Ok(())
+            }
+            Self::ResetPassword { username, password } => {
+                let user = get_user(db, &username).await?;
+                let password = password.map_or_else(read_password, Ok)?;
+                db.update_password(&user, &hash_secret(&password)).await?;
+
+                // whoever knew the old password may already be logged in
+                db.delete_user_sessions(&user).await?;
+
+                println!("Reset the password of user {username}, and ended their sessions");
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn get_user(db: &impl Database, username: &str) -> Result<User> {
+    match db.get_user(username).await {
+        Ok(user) => Ok(user),
+        Err(DbError::NotFound) => bail!("user {username} not found"),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_password() -> Result<String> {
+    let password = prompt_password("Password: ")?;
+    let repeated = prompt_password("Repeat password: ")?;
+    ensure!(password == repeated, "the passwords do not match");
+
+    Ok(password)
+}
+
+async fn list(db: &impl Database) -> Result<()> {
+    let users = db.list_users().await?;
+
+    let mut rows = Vec::with_capacity(users.len());
+    for user in users {
+        let history = db.count_history(&user).await?;
+        let records = db.count_records(&user).await?;
+        rows.push((user, history, records));
+    }
+
+    let username_width = rows
+        .iter()
+        .map(|(user, _, _)| user.username.len())
+        .max()
+        .unwrap_or_default()
+        .max("USERNAME".len());
+    let email_width = rows
+        .iter()
+        .map(|(user, _, _)| user.email.len())
+        .max()
+        .unwrap_or_default()
+        .max("EMAIL".len());
+
+    println!(
+        "{:<username_width$}  {:<email_width$}  {:>10}  {:>10}  STATUS",
+        "USERNAME", "EMAIL", "HISTORY", "RECORDS"
+    );
+
+    for (user, history, records) in rows {
+        let status = if user.disabled { "disabled" } else { "active" };
+
+        println!(
+            "{:<username_width$}  {:<email_width$}  {history:>10}  {records:>10}  {status}",
+            user.username, user.email
+        );
+    }
+
+    Ok(())
+}
+
+async fn create(
+    db: &impl Database,
+    username: String,
+    email: String,
+    password: Option<String>,
+) -> Result<()> {
+    ensure!(
+        valid_username(&username),
+        "only alphanumeric characters and hyphens (-) are allowed in usernames"
+    );
+
+    match db.get_user(&username).await {
+        Ok(_) => bail!("user {username} already exists"),
+        Err(DbError::NotFound) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let password = password.map_or_else(read_password, Ok)?;
+
+    db.add_user(&NewUser {
+        username: username.clone(),
+        email,
+        password: hash_secret(&password),
+    })
+    .await?;
+
+    println!("Created user {username}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_server_database::{models::NewSession, Database, DbSettings};
+    use atuin_server_sqlite::Sqlite;
+
+    use super::{create, Cmd};
+
+    async fn db() -> Sqlite {
+        Sqlite::new(&DbSettings {
+            db_uri: String::from("sqlite::memory:"),
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn login(db: &Sqlite, username: &str, token: &str) {
+        let user = db.get_user(username).await.unwrap();
+
+        db.add_session(&NewSession {
+            user_id: user.id,
+            token: token.to_owned(),
+            device: String::new(),
+            expires_at: None,
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn disable() {
+        let db = db().await;
+        create(
+            &db,
+            "ellie".into(),
+            "ellie@example.com".into(),
+            Some("hunter2".into()),
+        )
+        .await
+        .unwrap();
+        login(&db, "ellie", "laptop").await;
+
+        let disable = Cmd::Disable {
+            username: "ellie".into(),
+        };
+        disable.run(&db).await.unwrap();
+
+        let users = db.list_users().await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(users[0].disabled);
+        assert_eq!(db.count_records(&users[0]).await.unwrap(), 0);
+
+        // the session is kept, for when they're enabled again
+        assert!(db.get_session_user("laptop").await.unwrap().disabled);
+
+        let enable = Cmd::Enable {
+            username: "ellie".into(),
+        };
+        enable.run(&db).await.unwrap();
+        assert!(!db.get_user("ellie").await.unwrap().disabled);
+    }
+
+    #[tokio::test]
+    async fn reset_password() {
+        let db = db().await;
+        create(
+            &db,
+            "ellie".into(),
+            "ellie@example.com".into(),
+            Some("hunter2".into()),
+        )
+        .await
+        .unwrap();
+        login(&db, "ellie", "laptop").await;
+
+        let old_password = db.get_user("ellie").await.unwrap().password;
+
+        let reset = Cmd::ResetPassword {
+            username: "ellie".into(),
+            password: Some("correct horse battery staple".into()),
+        };
+        reset.run(&db).await.unwrap();
+
+        assert_ne!(db.get_user("ellie").await.unwrap().password, old_password);
+        assert!(db.get_session_user("laptop").await.is_err());
+
+        // unknown users are an error
+        let reset = Cmd::ResetPassword {
+            username: "nobody".into(),
+            password: Some("hunter2".into()),
+        };
+        assert!(reset.run(&db).await.is_err());
+    }
+}